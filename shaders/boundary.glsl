@@ -0,0 +1,40 @@
+// Boundary conditions of the world square from -1 to 1, mirroring
+// `cpu/boundary.rs`. The values are the ones of `Boundary`.
+
+const uint BOUNDARY_OPEN = 0;
+const uint BOUNDARY_PERIODIC = 1;
+const uint BOUNDARY_REFLECTING = 2;
+const uint BOUNDARY_ABSORBING = 3;
+
+// Folds one coordinate back into the square, mirroring the velocity after
+// an odd number of crossings
+void reflectCoordinate(inout float pos, inout float vel) {
+    float folded = mod(pos + 1, 4);
+    if(folded > 2) {
+        pos = 3 - folded;
+        vel = -vel;
+    } else {
+        pos = folded - 1;
+    }
+}
+
+// Puts a particle that left the square back into it, or removes it by
+// making its position non-finite
+void applyBoundary(uint boundary, inout vec2 pos, inout vec2 vel) {
+    if(boundary == BOUNDARY_PERIODIC) {
+        pos = mod(pos + 1, 2) - 1;
+    } else if(boundary == BOUNDARY_REFLECTING) {
+        reflectCoordinate(pos.x, vel.x);
+        reflectCoordinate(pos.y, vel.y);
+    } else if(boundary == BOUNDARY_ABSORBING) {
+        if(abs(pos.x) > 1 || abs(pos.y) > 1) {
+            pos = vec2(uintBitsToFloat(0x7fc00000u));
+        }
+    }
+}
+
+// A displacement in normalized units, the square being one long, to the
+// nearest periodic image
+vec2 minimumImage(uint boundary, vec2 d) {
+    return boundary == BOUNDARY_PERIODIC ? d - floor(d + 0.5) : d;
+}
@@ -2,4 +2,10 @@ glslc shader.vert -o vert.spv
 glslc shader.frag -o frag.spv
 
 glslc gravity.comp -o gravity.comp.spv
+glslc domain.comp -o domain.comp.spv
 glslc mass.comp -o mass.comp.spv
+glslc reduce.comp -o reduce.comp.spv
+glslc refine.comp -o refine.comp.spv
+glslc direct.comp -o direct.comp.spv
+glslc block.comp -o block.comp.spv
+glslc integrate.comp -o integrate.comp.spv
@@ -0,0 +1,68 @@
+// 64 bit fixed point numbers of two words, low word first, as deposited by
+// `mass.comp` and `refine.comp`
+
+// Splits a whole number below 2^64 into its words
+uvec2 fixedPoint(float value) {
+    float high = floor(value * (1.0 / 4294967296.0));
+    return uvec2(uint(value - high * 4294967296.0), uint(high));
+}
+
+float toFloat(uint low, uint high) {
+    return float(high) * 4294967296.0 + float(low);
+}
+
+// Product of two 64 bit numbers as four words, low word first
+uvec4 multiplyWide(uvec2 a, uvec2 b) {
+    uvec4 low;
+    uvec4 high;
+    umulExtended(a.x, b.x, high.x, low.x);
+    umulExtended(a.x, b.y, high.y, low.y);
+    umulExtended(a.y, b.x, high.z, low.z);
+    umulExtended(a.y, b.y, high.w, low.w);
+
+    uint carryA, carryB, carryC, carryD, carryE;
+    uint word1 = uaddCarry(high.x, low.y, carryA);
+    word1 = uaddCarry(word1, low.z, carryB);
+    uint word2 = uaddCarry(high.y, high.z, carryC);
+    word2 = uaddCarry(word2, low.w, carryD);
+    word2 = uaddCarry(word2, carryA + carryB, carryE);
+    return uvec4(low.x, word1, word2, high.w + carryC + carryD + carryE);
+}
+
+// Second moment about the center of mass of the sums `square` of a times b
+// and `a` and `b` over `mass`, `(square * mass - a * b) / mass^2`. The
+// numerator is subtracted in 128 bits, since subtracting the squared center
+// in floats cancels on pixels whose mass lies close together. Follows
+// `central_moment` in `cpu/mass_field.rs`.
+float centralMoment(uvec2 square, uvec2 a, uvec2 b, uvec2 mass, float massFloat) {
+    uvec4 left = multiplyWide(square, mass);
+    uvec4 right = multiplyWide(a, b);
+
+    uvec4 difference;
+    uint borrow0, borrow1, borrow2, borrow3, borrowA, borrowB;
+    difference.x = usubBorrow(left.x, right.x, borrow0);
+    difference.y = usubBorrow(left.y, right.y, borrowA);
+    difference.y = usubBorrow(difference.y, borrow0, borrowB);
+    borrow1 = borrowA + borrowB;
+    difference.z = usubBorrow(left.z, right.z, borrowA);
+    difference.z = usubBorrow(difference.z, borrow1, borrowB);
+    borrow2 = borrowA + borrowB;
+    difference.w = usubBorrow(left.w, right.w, borrowA);
+    difference.w = usubBorrow(difference.w, borrow2, borrowB);
+    borrow3 = borrowA + borrowB;
+
+    // Negative results borrow from beyond the top word, negate them
+    bool negative = borrow3 != 0;
+    if(negative) {
+        difference = ~difference;
+        uint carry;
+        difference.x = uaddCarry(difference.x, 1, carry);
+        difference.y = uaddCarry(difference.y, carry, carry);
+        difference.z = uaddCarry(difference.z, carry, carry);
+        difference.w += carry;
+    }
+
+    float value = ((float(difference.w) * 4294967296.0 + float(difference.z)) * 4294967296.0 + float(difference.y)) * 4294967296.0 + float(difference.x);
+    float moment = value / massFloat / massFloat;
+    return negative ? -moment : moment;
+}
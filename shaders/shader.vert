@@ -2,9 +2,16 @@
 
 layout(location = 0) in vec2 inPosition;
 layout(location = 1) in vec2 velocity;
+layout(location = 2) in float mass;
+layout(location = 3) in uint species;
 
 layout(location = 0) out vec4 fragColor;
 
+// Species with zero alpha are coloured by their speed
+layout(push_constant) uniform PushConstants {
+    vec4 speciesColors[8];
+} pcs;
+
 vec3 hsv2rgb(vec3 c)
 {
     vec4 K = vec4(1.0, 2.0 / 3.0, 1.0 / 3.0, 3.0);
@@ -15,6 +22,13 @@ vec3 hsv2rgb(vec3 c)
 void main() {
     gl_PointSize = 2.0;
     gl_Position = vec4(inPosition, 0.0, 1.0);
+
+    vec4 speciesColor = species < 8 ? pcs.speciesColors[species] : vec4(0);
+    if(speciesColor.a > 0) {
+        fragColor = speciesColor;
+        return;
+    }
+
     float flatVelocity = length(velocity);
     float factor = 0.02;
     float hue = -factor / (flatVelocity + factor) + 1;
@@ -0,0 +1,72 @@
+// Softening kernels, the same as `src/cpu/softening.rs`
+
+const uint CUTOFF = 0;
+const uint PLUMMER = 1;
+const uint SPLINE = 2;
+
+// Support of the spline kernel in softening lengths
+const float SPLINE_SUPPORT = 2.8;
+
+// Factor g of the acceleration G m g (other - pos) at distance d, 1 / d^3
+// without softening
+float softenedForce(uint kernel, float d, float softening) {
+    if(kernel == PLUMMER) {
+        float s = d * d + softening * softening;
+        return 1.0 / (s * sqrt(s));
+    }
+
+    if(kernel == SPLINE) {
+        float h = SPLINE_SUPPORT * softening;
+        if(d < h) {
+            float u = d / h;
+            float h3 = h * h * h;
+            if(u < 0.5) {
+                return (32.0 / 3.0 + u * u * (32.0 * u - 38.4)) / h3;
+            }
+
+            float u3 = u * u * u;
+            return (64.0 / 3.0 - 48.0 * u + 38.4 * u * u - 32.0 / 3.0 * u3 - 1.0 / (15.0 * u3)) / h3;
+        }
+    } else if(d < softening) {
+        return 0;
+    }
+
+    return 1.0 / (d * d * d);
+}
+
+// Derivative of the force factor by d divided by d and the derivative of
+// that divided by d again, for the quadrupole moments of the mass field
+vec2 softenedForceDerivatives(uint kernel, float d, float softening) {
+    if(kernel == PLUMMER) {
+        float s = d * d + softening * softening;
+        float h = -3.0 / (s * s * sqrt(s));
+        return vec2(h, -5.0 * h / s);
+    }
+
+    if(kernel == SPLINE) {
+        float h = SPLINE_SUPPORT * softening;
+        if(d < h) {
+            float u = d / h;
+            float h5 = h * h * h * h * h;
+            float h7 = h5 * h * h;
+            if(u < 0.5) {
+                return vec2((96.0 * u - 76.8) / h5, 96.0 / (u * h7));
+            }
+
+            float u2 = u * u;
+            return vec2((-48.0 + 76.8 * u - 32.0 * u2 + 0.2 / (u2 * u2)) / (u * h5), (48.0 / u2 - 32.0 - 1.0 / (u2 * u2 * u2)) / (u * h7));
+        }
+    } else if(d < softening) {
+        return vec2(0);
+    }
+
+    float d2 = d * d;
+    float d5 = d2 * d2 * d;
+    return vec2(-3.0 / d5, 15.0 / (d5 * d2));
+}
+
+// Softening length of a particle of `mass`, scaled with the square root of
+// the mass relative to `particleMass` with adaptive softening
+float particleSoftening(float mass, float softening, float particleMass, uint adaptive) {
+    return adaptive != 0 ? softening * sqrt(mass / particleMass) : softening;
+}
@@ -1,28 +1,31 @@
 use anyhow::{anyhow, Ok, Result};
+use log::info;
 
-use std::mem::size_of;
-use std::ptr::copy_nonoverlapping as memcpy;
+use std::path::PathBuf;
 use std::time::Instant;
 use vulkanalia::loader::{LibloadingLoader, LIBRARY};
 use vulkanalia::prelude::v1_0::*;
 use vulkanalia::window as vk_window;
 use winit::window::Window;
 
+use vulkanalia::vk::ExtDebugUtilsExtension;
 use vulkanalia::vk::KhrSurfaceExtension;
 use vulkanalia::vk::KhrSwapchainExtension;
-use vulkanalia::vk::{ExtDebugUtilsExtension, Fence};
 
+use crate::config::units::{self, Units};
+use crate::config::{Integrator, Physics, RunConfig};
 use crate::data::buffers_data::BuffersData;
 use crate::data::commands_data::CommandsData;
-use crate::data::descriptors_data::DescriptorsData;
 use crate::data::globals;
 use crate::data::pipeline_data::PipelineData;
+use crate::data::push_constants::RenderPushConstants;
 use crate::data::swapchain_data::SwapchainData;
 use crate::data::sync_data::SyncData;
-use crate::data::uniform_buffer_object::UniformBufferObject;
-use crate::data::vertex::Vertex;
-use crate::generators::random_generator;
-use crate::init::{buffers, commands, descriptors, framebuffers, pipeline, swapchain, sync};
+use crate::init::{buffers, commands, framebuffers, pipeline, swapchain, sync};
+use crate::integrators::Update;
+use crate::snapshot::{self, Snapshot, SnapshotHeader};
+use crate::solvers::{self, GravitySolver};
+use crate::utils::resources::{self, memory_barrier};
 use crate::{
     data::common_data::CommonData,
     init::{device, instance},
@@ -32,27 +35,43 @@ use crate::{
 pub struct App {
     instance: Instance,
     frame: usize,
+    pub step: u64,
     pub resized: bool,
-    prev_duration: f32,
-    start: Instant,
+    delta_t: f32,
+    time_scale: f32,
+    paused: bool,
+    /// Scaled wall time not yet simulated.
+    accumulator: f64,
+    /// Single steps requested while paused.
+    requested_steps: u64,
+    last_step: Option<u64>,
+    physics: Physics,
+    integrator: Integrator,
+    seed: Option<u64>,
+    particle_count: usize,
+    render_push_constants: RenderPushConstants,
+    report_every: Option<u64>,
+    units: Option<Units>,
+    snapshot_every: Option<u64>,
+    snapshot_dir: PathBuf,
+    last_snapshot_step: Option<u64>,
+    sim_time: f64,
+    last_frame: Instant,
 
     _entry: Entry,
     buffers: BuffersData,
     common: CommonData,
     commands: CommandsData,
     render_pipeline: PipelineData,
-    mass_pipeline: PipelineData,
-    gravity_pipeline: PipelineData,
     swapchain: SwapchainData,
-    gravity_descriptors: DescriptorsData,
-    mass_descriptors: DescriptorsData,
     sync: SyncData,
-
-    vertices: Vec<Vertex>,
+    solver: Box<dyn GravitySolver>,
 }
 
 impl App {
-    pub unsafe fn create(window: &Window) -> Result<Self> {
+    pub unsafe fn create(window: &Window, initial: Snapshot, config: &RunConfig) -> Result<Self> {
+        let Snapshot { header, vertices } = initial;
+
         let loader = LibloadingLoader::new(LIBRARY)?;
         let entry = Entry::new(loader).map_err(|b| anyhow!("{}", b))?;
 
@@ -60,56 +79,33 @@ impl App {
         let mut common = CommonData::default();
         let mut commands = CommandsData::default();
         let mut render_pipeline = PipelineData::default();
-        let mut mass_pipeline = PipelineData::default();
-        let mut gravity_pipeline = PipelineData::default();
         let mut swapchain = SwapchainData::default();
         let mut sync = SyncData::default();
-        let mut gravity_descriptors = DescriptorsData::default();
-        let mut mass_descriptors = DescriptorsData::default();
 
-        let instance = instance::create_instance(window, &entry, &mut common)?;
+        let instance = instance::create_instance(Some(window), &entry, &mut common)?;
         common.surface = vk_window::create_surface(&instance, &window, &window)?;
 
         device::pick_physical_device(&instance, &mut common)?;
         let device = device::create_logical_device(&instance, &mut common)?;
         globals::set_device(&device);
 
-        swapchain::create_swapchain(&common, &instance, &window, &mut swapchain)?;
+        swapchain::create_swapchain(&common, &instance, window, &mut swapchain)?;
         swapchain.swapchain_image_views = swapchain::create_swapchain_image_views(&swapchain)?;
 
         commands.main_command_pool = commands::create_command_pool(&instance, &common)?;
-        buffers.offscreen_images = buffers::create_offscreen_images(&instance, &common, &commands)?;
 
         // Render passes
         swapchain.render_pass = swapchain::create_render_pass(swapchain.swapchain_format)?;
 
-        // Descriptor layouts
-        gravity_descriptors.descriptor_set_layout =
-            descriptors::create_gravity_descriptor_set_layout()?;
-        mass_descriptors.descriptor_set_layout = descriptors::create_mass_descriptor_set_layout()?;
-
         // Pipelines
-        pipeline::create_mass_compute_pipeline(&mass_descriptors, &mut mass_pipeline)?;
-        pipeline::create_gravity_compute_pipeline(&gravity_descriptors, &mut gravity_pipeline)?;
         pipeline::create_pipeline(&swapchain, &mut render_pipeline)?;
 
-        gravity_descriptors.descriptor_pool =
-            descriptors::create_gravity_descriptor_pool(&swapchain)?;
-        mass_descriptors.descriptor_pool = descriptors::create_mass_descriptor_pool(&swapchain)?;
-
         swapchain.present_framebuffers = framebuffers::create_framebuffers(
             swapchain.render_pass,
             &swapchain.swapchain_extent,
             &swapchain.swapchain_image_views,
         )?;
 
-        //let vertices = random_generator::generate_random_vertices(500000);
-        //let vertices = random_generator::generate_two_clusters(2000000);
-        //let vertices = random_generator::generate_circular_cluster(1000000, 0.5, 0.001);
-        let vertices = random_generator::generate_2_circular_clusters(1000000, 0.2, 0.0001);
-
-        buffers::create_uniform_buffers(&instance, &common, &swapchain, &mut buffers)?;
-
         buffers::create_shader_storage_buffers(
             &instance,
             &vertices,
@@ -118,51 +114,75 @@ impl App {
             &mut buffers,
         )?;
 
-        descriptors::create_gravity_descriptor_sets(&buffers, &vertices, &mut gravity_descriptors)?;
+        let mut solver = solvers::create_solver(
+            &instance,
+            &common,
+            &commands,
+            &buffers.storage_buffers,
+            &vertices,
+            config,
+        )?;
 
-        descriptors::create_mass_descriptor_sets(&buffers, &vertices, &mut mass_descriptors)?;
+        if solver.steps_on_device() {
+            let delta_t = config.dt.unwrap_or(globals::DEFAULT_DELTA_T);
+            for frame in 0..globals::MAX_FRAMES_IN_FLIGHT {
+                solver.prepare(frame, delta_t)?;
+            }
+        }
 
         commands.command_buffers = commands::create_command_buffers(
             swapchain.swapchain_images.len(),
             commands.main_command_pool,
         )?;
 
-        commands.gravity_compute_command_buffers = commands::create_command_buffers(
-            globals::MAX_FRAMES_IN_FLIGHT,
-            commands.main_command_pool,
-        )?;
-
-        commands.mass_compute_command_buffers = commands::create_command_buffers(
-            globals::MAX_FRAMES_IN_FLIGHT,
-            commands.main_command_pool,
-        )?;
-
-        commands.image_clear_command_buffers = commands::create_command_buffers(
+        commands.compute_step_command_buffers = commands::create_command_buffers(
             globals::MAX_FRAMES_IN_FLIGHT,
             commands.main_command_pool,
         )?;
 
         sync::create_sync_objects(&swapchain, &mut sync)?;
-        let _self = Self {
-            gravity_descriptors,
-            mass_descriptors,
+        let mut _self = Self {
             _entry: entry,
             instance,
             frame: 0,
+            step: header.step,
             resized: false,
-            prev_duration: 0.0,
-            start: Instant::now(),
+            delta_t: config.dt.unwrap_or(globals::DEFAULT_DELTA_T),
+            time_scale: 1.0,
+            paused: false,
+            accumulator: 0.0,
+            requested_steps: 0,
+            last_step: config.steps.map(|steps| header.step + steps),
+            physics: config.physics,
+            integrator: config.integrator,
+            seed: config.seed,
+            particle_count: vertices.len(),
+            render_push_constants: RenderPushConstants {
+                species_colors: config.species_colors(),
+            },
+            report_every: config.report_every,
+            units: config.units.clone(),
+            snapshot_every: config.snapshot_every,
+            snapshot_dir: config.snapshot_dir.clone(),
+            last_snapshot_step: None,
+            sim_time: header.sim_time,
+            last_frame: Instant::now(),
             buffers,
             common,
             commands,
             render_pipeline,
-            mass_pipeline,
-            gravity_pipeline,
             swapchain,
             sync,
-            vertices,
+            solver,
         };
 
+        // The first step reads storage buffer `(step + 1) % 2`, which gets
+        // the velocities half a step back.
+        if config.integrator.staggered() {
+            let frame = ((_self.step + 1) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+            _self.kick(frame, -Update::lag(_self.integrator))?;
+        }
+
         Ok(_self)
     }
 
@@ -170,13 +190,21 @@ impl App {
         globals::get_device().wait_for_fences(
             &[self.sync.in_flight_fences[self.frame]],
             true,
-            u64::max_value(),
+            u64::MAX,
         )?;
 
+        let steps = self.steps_this_frame();
+
+        // The host writes the upload buffers of both parities, which the
+        // other frame in flight may still copy from.
+        if steps > 0 && !self.solver.steps_on_device() {
+            globals::get_device().wait_for_fences(&self.sync.in_flight_fences, true, u64::MAX)?;
+        }
+
         let result: Result<(u32, vk::SuccessCode), vk::ErrorCode> = globals::get_device()
             .acquire_next_image_khr(
                 self.swapchain.swapchain,
-                u64::max_value(),
+                u64::MAX,
                 self.sync.image_available_semaphores[self.frame],
                 vk::Fence::null(),
             );
@@ -191,112 +219,128 @@ impl App {
             globals::get_device().wait_for_fences(
                 &[self.sync.images_in_flight[image_index as usize]],
                 true,
-                u64::max_value(),
+                u64::MAX,
             )?;
         }
 
         self.sync.images_in_flight[image_index as usize] = self.sync.in_flight_fences[self.frame];
         globals::get_device().reset_fences(&[self.sync.in_flight_fences[self.frame]])?;
 
-        self.update_mass_command_buffers()?;
+        let first_step = self.step;
+        self.update_compute_step_command_buffer(steps)?;
         self.update_command_buffer(image_index)?;
-        self.update_uniform_buffer(image_index)?;
-        self.update_gravity_compute_command_buffers()?;
-        self.update_clear_command_buffer()?;
 
-        self.submit_image_clear()?;
-        self.submit_mass_compute()?;
-        self.submit_gravity_compute()?;
+        self.submit_compute_step()?;
 
         self.submit_render(image_index)?;
         self.submit_present(window, image_index)?;
 
         self.frame = (self.frame + 1) % globals::MAX_FRAMES_IN_FLIGHT;
 
+        if self
+            .report_every
+            .is_some_and(|report_every| self.step / report_every > first_step / report_every)
+        {
+            info!(
+                "step {}, sim time {}",
+                self.step,
+                units::format_time(self.sim_time, self.units.as_ref())
+            );
+        }
+
+        if self
+            .snapshot_every
+            .is_some_and(|snapshot_every| self.step.is_multiple_of(snapshot_every))
+        {
+            self.write_snapshot()?;
+        }
+
         Ok(())
     }
 
-    unsafe fn submit_image_clear(&mut self) -> Result<()> {
-        let command_buffers = &[self.commands.image_clear_command_buffers[self.frame]];
-        let wait_semaphores = &[self.sync.image_available_semaphores[self.frame]];
-        let wait_stages = &[vk::PipelineStageFlags::TOP_OF_PIPE];
-        let signal_semaphores = &[self.sync.image_clear_finished_semaphores[self.frame]];
-
-        let submit_info = vk::SubmitInfo::builder()
-            .wait_semaphores(wait_semaphores)
-            .wait_dst_stage_mask(wait_stages)
-            .command_buffers(command_buffers)
-            .signal_semaphores(signal_semaphores);
+    /// Waits for the frames in flight and reads the particles of the latest
+    /// step back from the device. Staggered velocities are kicked forward
+    /// into the buffer the next step overwrites and read from there.
+    pub unsafe fn snapshot(&mut self) -> Result<Snapshot> {
+        globals::get_device().device_wait_idle()?;
 
-        globals::get_device().queue_submit(
-            self.common.graphics_queue,
-            &[submit_info],
-            Fence::null(),
+        let latest = if self.integrator.staggered() {
+            let frame = (self.step % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+            self.kick(frame, Update::lag(self.integrator))?;
+            frame
+        } else {
+            self.latest_storage_buffer()
+        };
+        let vertices = buffers::read_shader_storage_buffer(
+            &self.instance,
+            &self.common,
+            &self.commands,
+            self.buffers.storage_buffers[latest],
+            self.particle_count,
         )?;
-        Ok(())
+
+        Ok(Snapshot {
+            header: SnapshotHeader {
+                particle_count: vertices.len() as u64,
+                step: self.step,
+                sim_time: self.sim_time,
+                seed: self.seed,
+                dt: self.delta_t,
+                physics: self.physics,
+            },
+            vertices,
+        })
     }
 
-    unsafe fn submit_mass_compute(&mut self) -> Result<()> {
-        let command_buffers = &[self.commands.mass_compute_command_buffers[self.frame]];
-        let mut wait_semaphores = vec![self.sync.image_clear_finished_semaphores[self.frame]];
-        let mut wait_stages = vec![vk::PipelineStageFlags::COMPUTE_SHADER];
-        if !self.sync.first_gravity_compute {
-            wait_semaphores.push(
-                self.sync.gravity_read_finished_semaphores
-                    [(self.frame + 1) % globals::MAX_FRAMES_IN_FLIGHT],
-            );
+    /// Records a kick of the velocities by `kick` steps into the storage
+    /// buffer of `frame` and waits for it.
+    unsafe fn kick(&mut self, frame: usize, kick: f32) -> Result<()> {
+        let command_buffer = resources::begin_single_time_commands(&self.commands)?;
+        self.solver.record_kick(command_buffer, frame, kick)?;
+        resources::end_single_time_commands(&self.common, &self.commands, command_buffer)
+    }
 
-            wait_stages.push(vk::PipelineStageFlags::COMPUTE_SHADER)
+    /// Writes a snapshot into the snapshot directory if snapshots are enabled
+    /// for this run and the current step has not been written yet.
+    pub unsafe fn write_snapshot(&mut self) -> Result<()> {
+        if self.snapshot_every.is_none() || self.last_snapshot_step == Some(self.step) {
+            return Ok(());
         }
 
-        let signal_semaphores = &[self.sync.mass_compute_finished_semaphores[self.frame]];
-
-        let submit_info = vk::SubmitInfo::builder()
-            .wait_semaphores(&wait_semaphores)
-            .wait_dst_stage_mask(&wait_stages)
-            .command_buffers(command_buffers)
-            .signal_semaphores(signal_semaphores);
-
-        globals::get_device().queue_submit(
-            self.common.graphics_queue,
-            &[submit_info],
-            Fence::null(),
-        )?;
+        let path = self.snapshot()?.save_to_dir(&self.snapshot_dir)?;
+        info!("wrote {}", path.display());
+        self.last_snapshot_step = Some(self.step);
 
         Ok(())
     }
 
-    unsafe fn submit_gravity_compute(&mut self) -> Result<()> {
-        let command_buffers = &[self.commands.gravity_compute_command_buffers[self.frame]];
-
-        let wait_semaphores = &[self.sync.mass_compute_finished_semaphores[self.frame]];
-        let wait_stages = &[vk::PipelineStageFlags::COMPUTE_SHADER];
-
-        let signal_semaphores = &[
-            self.sync.gravity_compute_finished_semaphores[self.frame],
-            self.sync.gravity_read_finished_semaphores[self.frame],
-        ];
+    unsafe fn submit_compute_step(&mut self) -> Result<()> {
+        let command_buffers = &[self.commands.compute_step_command_buffers[self.frame]];
+        let signal_semaphores = &[self.sync.compute_step_finished_semaphores[self.frame]];
 
-        let compute_submit_info = vk::SubmitInfo::builder()
-            .wait_semaphores(wait_semaphores)
-            .wait_dst_stage_mask(wait_stages)
+        let submit_info = vk::SubmitInfo::builder()
             .command_buffers(command_buffers)
             .signal_semaphores(signal_semaphores);
 
         globals::get_device().queue_submit(
             self.common.compute_queue,
-            &[compute_submit_info],
-            Fence::null(),
+            &[submit_info],
+            vk::Fence::null(),
         )?;
 
-        self.sync.first_gravity_compute = false;
         Ok(())
     }
 
     unsafe fn submit_render(&mut self, image_index: usize) -> Result<()> {
-        let command_buffers = &[self.commands.command_buffers[image_index as usize]];
-        let wait_semaphores = &[self.sync.gravity_compute_finished_semaphores[self.frame]];
-        let wait_stages = &[vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+        let command_buffers = &[self.commands.command_buffers[image_index]];
+        let wait_semaphores = &[
+            self.sync.compute_step_finished_semaphores[self.frame],
+            self.sync.image_available_semaphores[self.frame],
+        ];
+        let wait_stages = &[
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        ];
         let signal_semaphores = &[self.sync.render_finished_semaphores[self.frame]];
 
         let submit_info = vk::SubmitInfo::builder()
@@ -333,7 +377,6 @@ impl App {
         if self.resized || changed {
             self.resized = false;
             self.recreate_swapchain(window)?;
-            self.update_gravity_compute_command_buffers()?;
         } else if let Err(e) = result {
             return Err(anyhow!(e));
         }
@@ -348,7 +391,7 @@ impl App {
         swapchain::create_swapchain(&self.common, &self.instance, window, &mut self.swapchain)?;
 
         self.swapchain.swapchain_image_views =
-            swapchain::create_swapchain_image_views(&mut self.swapchain)?;
+            swapchain::create_swapchain_image_views(&self.swapchain)?;
 
         self.swapchain.render_pass =
             swapchain::create_render_pass(self.swapchain.swapchain_format)?;
@@ -413,11 +456,19 @@ impl App {
         globals::get_device().cmd_bind_vertex_buffers(
             command_buffer,
             0,
-            &[self.buffers.storage_buffers[self.frame]],
+            &[self.buffers.storage_buffers[self.latest_storage_buffer()]],
             &[0],
         );
 
-        globals::get_device().cmd_draw(command_buffer, self.vertices.len() as u32, 1, 0, 0);
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.render_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::VERTEX,
+            0,
+            self.render_push_constants.as_bytes(),
+        );
+
+        globals::get_device().cmd_draw(command_buffer, self.particle_count as u32, 1, 0, 0);
 
         globals::get_device().cmd_end_render_pass(command_buffer);
         globals::get_device().end_command_buffer(command_buffer)?;
@@ -425,156 +476,115 @@ impl App {
         Ok(())
     }
 
-    unsafe fn update_clear_command_buffer(&mut self) -> Result<()> {
-        let command_buffer = self.commands.image_clear_command_buffers[self.frame];
-
-        let inheritance = vk::CommandBufferInheritanceInfo::builder();
-        let info = vk::CommandBufferBeginInfo::builder()
-            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
-            .inheritance_info(&inheritance);
-
-        globals::get_device().begin_command_buffer(command_buffer, &info)?;
-
-        let clear_color = vk::ClearColorValue {
-            float32: [0.0, 0.0, 0.0, 0.0],
-        };
-
-        let subresource = vk::ImageSubresourceRange::builder()
-            .aspect_mask(vk::ImageAspectFlags::COLOR)
-            .base_array_layer(0)
-            .layer_count(1)
-            .level_count(1);
-
-        let subresources = &[subresource];
-
-        self.buffers.offscreen_images[self.frame]
-            .iter()
-            .for_each(|i| {
-                globals::get_device().cmd_clear_color_image(
-                    command_buffer,
-                    i.image,
-                    vk::ImageLayout::GENERAL,
-                    &clear_color,
-                    subresources,
-                );
-            });
-
-        globals::get_device().end_command_buffer(command_buffer)?;
-        Ok(())
+    /// Whether the step count of the run has been reached.
+    pub fn finished(&self) -> bool {
+        self.last_step
+            .is_some_and(|last_step| self.step >= last_step)
     }
 
-    unsafe fn update_uniform_buffer(&mut self, image_index: usize) -> Result<()> {
-        let curr_duration = self.start.elapsed().as_secs_f32();
-        let delta = curr_duration - self.prev_duration;
-        self.prev_duration = curr_duration;
-
-        let ubo = UniformBufferObject { delta_t: delta };
-        let memory = globals::get_device().map_memory(
-            self.buffers.uniform_buffers_memory[image_index],
-            0,
-            size_of::<UniformBufferObject>() as u64,
-            vk::MemoryMapFlags::empty(),
-        )?;
-
-        memcpy(&ubo, memory.cast(), 1);
-
-        globals::get_device().unmap_memory(self.buffers.uniform_buffers_memory[image_index]);
-        Ok(())
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.accumulator = 0.0;
+        info!(
+            "{} at step {}, sim time {}",
+            if self.paused { "paused" } else { "resumed" },
+            self.step,
+            units::format_time(self.sim_time, self.units.as_ref())
+        );
     }
 
-    unsafe fn update_mass_command_buffers(&self) -> Result<()> {
-        let command_buffer = self.commands.mass_compute_command_buffers[self.frame];
-
-        let inheritance = vk::CommandBufferInheritanceInfo::builder();
-        let info = vk::CommandBufferBeginInfo::builder()
-            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
-            .inheritance_info(&inheritance);
-
-        globals::get_device().begin_command_buffer(command_buffer, &info)?;
-
-        globals::get_device().cmd_bind_pipeline(
-            command_buffer,
-            vk::PipelineBindPoint::COMPUTE,
-            self.mass_pipeline.pipeline,
-        );
+    /// Advances a paused simulation by one step on the next frame.
+    pub fn request_step(&mut self) {
+        if self.paused {
+            self.requested_steps += 1;
+        }
+    }
 
-        let descriptor_sets = &[self.mass_descriptors.descriptor_sets[self.frame]];
-        globals::get_device().cmd_bind_descriptor_sets(
-            command_buffer,
-            vk::PipelineBindPoint::COMPUTE,
-            self.mass_pipeline.pipeline_layout,
-            0,
-            descriptor_sets,
-            &[],
-        );
+    /// Multiplies the simulated time per wall clock second by `factor`.
+    pub fn scale_time(&mut self, factor: f32) {
+        self.time_scale =
+            (self.time_scale * factor).clamp(globals::MIN_TIME_SCALE, globals::MAX_TIME_SCALE);
+        info!("time scale {}", self.time_scale);
+    }
 
-        let detail_levels = self.buffers.offscreen_images[0].len() as u32;
-        let mip_levels_bytes = &detail_levels.to_ne_bytes();
+    /// Step `s` writes storage buffer `s % 2`, so the latest particles are
+    /// in the other one.
+    fn latest_storage_buffer(&self) -> usize {
+        ((self.step + 1) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize
+    }
 
-        globals::get_device().cmd_push_constants(
-            command_buffer,
-            self.mass_pipeline.pipeline_layout,
-            vk::ShaderStageFlags::COMPUTE,
-            0,
-            mip_levels_bytes,
-        );
+    /// Number of fixed steps covering the scaled wall time since the last
+    /// frame. Frames stop at snapshot steps and at the end of the run, and
+    /// when the simulation falls behind by more than `MAX_STEPS_PER_FRAME`
+    /// steps the rest of the time is dropped.
+    fn steps_this_frame(&mut self) -> u64 {
+        let now = Instant::now();
+        let elapsed = (now - self.last_frame)
+            .as_secs_f64()
+            .min(globals::MAX_FRAME_TIME);
+        self.last_frame = now;
+
+        let delta_t = self.delta_t as f64;
+        let available = if self.paused {
+            self.requested_steps
+        } else {
+            self.accumulator += elapsed * self.time_scale as f64;
+            (self.accumulator / delta_t) as u64
+        };
 
-        globals::get_device().cmd_dispatch(
-            command_buffer,
-            (self.vertices.len() as f32 / 256.0).ceil() as u32,
-            1,
-            1,
-        );
+        let until_snapshot = snapshot::steps_until_snapshot(self.step, self.snapshot_every);
+        let until_last = self
+            .last_step
+            .map_or(u64::MAX, |last_step| last_step.saturating_sub(self.step));
+        let steps = available
+            .min(globals::MAX_STEPS_PER_FRAME)
+            .min(until_snapshot)
+            .min(until_last);
+
+        if self.paused {
+            self.requested_steps -= steps;
+        } else if available > globals::MAX_STEPS_PER_FRAME {
+            self.accumulator %= delta_t;
+        } else {
+            self.accumulator -= steps as f64 * delta_t;
+        }
 
-        globals::get_device().end_command_buffer(command_buffer)?;
-        Ok(())
+        steps
     }
 
-    unsafe fn update_gravity_compute_command_buffers(&mut self) -> Result<()> {
-        let command_buffer = self.commands.gravity_compute_command_buffers[self.frame];
+    /// Records `steps` solver steps for this frame. The first one waits for
+    /// the frames before it to stop drawing the storage buffers.
+    unsafe fn update_compute_step_command_buffer(&mut self, steps: u64) -> Result<()> {
+        let command_buffer = self.commands.compute_step_command_buffers[self.frame];
 
-        let inheritance = vk::CommandBufferInheritanceInfo::builder();
         let info = vk::CommandBufferBeginInfo::builder()
-            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT)
-            .inheritance_info(&inheritance);
+            .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
         globals::get_device().begin_command_buffer(command_buffer, &info)?;
 
-        globals::get_device().cmd_bind_pipeline(
-            command_buffer,
-            vk::PipelineBindPoint::COMPUTE,
-            self.gravity_pipeline.pipeline,
-        );
-
-        let descriptor_sets = &[self.gravity_descriptors.descriptor_sets[self.frame]];
-        globals::get_device().cmd_bind_descriptor_sets(
-            command_buffer,
-            vk::PipelineBindPoint::COMPUTE,
-            self.gravity_pipeline.pipeline_layout,
-            0,
-            descriptor_sets,
-            &[],
-        );
-
-        let detail_levels = self.buffers.offscreen_images[0].len() as u32;
-        let detail_levels_bytes = &detail_levels.to_ne_bytes();
-
-        globals::get_device().cmd_push_constants(
+        memory_barrier(
             command_buffer,
-            self.mass_pipeline.pipeline_layout,
-            vk::ShaderStageFlags::COMPUTE,
-            0,
-            detail_levels_bytes,
+            vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::empty(),
+            vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::empty(),
         );
 
-        globals::get_device().cmd_dispatch(
-            command_buffer,
-            (self.vertices.len() as f32 / 256.0).ceil() as u32,
-            1,
-            1,
-        );
+        for _ in 0..steps {
+            let parity = (self.step % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+            let delta_t = if self.solver.steps_on_device() {
+                self.delta_t
+            } else {
+                self.solver.prepare(parity, self.delta_t)?
+            };
+            self.solver.record(command_buffer, parity)?;
+
+            self.step += 1;
+            self.sim_time += delta_t as f64;
+        }
 
         globals::get_device().end_command_buffer(command_buffer)?;
+
         Ok(())
     }
 
@@ -582,12 +592,9 @@ impl App {
         self.destroy_swapchain();
 
         self.commands = CommandsData::default();
-        self.mass_pipeline = PipelineData::default();
-        self.gravity_pipeline = PipelineData::default();
+        self.solver.destroy();
         self.buffers = BuffersData::default();
         self.sync = SyncData::default();
-        self.gravity_descriptors = DescriptorsData::default();
-        self.mass_descriptors = DescriptorsData::default();
 
         globals::destroy_device();
         self.instance.destroy_surface_khr(self.common.surface, None);
@@ -0,0 +1,465 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use cgmath::vec2;
+use clap::{Parser, Subcommand};
+
+use crate::config::scenario::Scenario;
+use crate::config::{
+    Boundary, DomainMode, Integrator, MassFieldConfig, Multipoles, Physics, Population, RunConfig,
+    SofteningKernel, SolverKind, TimestepCriterion, DEFAULT_BLOCK_LEVELS, DEFAULT_ETA,
+    DEFAULT_GRID_SIZE, DEFAULT_THETA,
+};
+use crate::generators::GeneratorKind;
+use crate::snapshot::DEFAULT_SNAPSHOT_DIR;
+
+/// 2D gravity simulator of particles.
+#[derive(Debug, Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Scenario file describing the populations and physics of the run.
+    #[arg(long, conflicts_with_all = ["generator", "count", "radius", "thickness", "angular_velocity"])]
+    pub scenario: Option<PathBuf>,
+
+    /// Snapshot to continue a previous run from, with its physics and seed.
+    /// The rest of the run comes from the scenario given with it, whose
+    /// populations are not generated.
+    #[arg(long, conflicts_with_all = ["generator", "count", "radius", "thickness", "angular_velocity", "seed", "softening_kernel", "adaptive_softening", "boundary"])]
+    pub resume: Option<PathBuf>,
+
+    /// Initial particle distribution.
+    #[arg(long, value_enum, default_value_t = GeneratorKind::TwoCircularClusters)]
+    pub generator: GeneratorKind,
+
+    /// Number of particles to generate.
+    #[arg(long, default_value_t = 1_000_000)]
+    pub count: u32,
+
+    /// Ring radius of the circular cluster generators.
+    #[arg(long, default_value_t = 0.2)]
+    pub radius: f32,
+
+    /// Ring thickness of the circular cluster generators.
+    #[arg(long, default_value_t = 0.0001)]
+    pub thickness: f32,
+
+    /// Angular velocity of the disk generator.
+    #[arg(long, default_value_t = 0.0)]
+    pub angular_velocity: f32,
+
+    /// Method computing the gravitational forces [default: mass-field]
+    #[arg(long, value_enum)]
+    pub solver: Option<SolverKind>,
+
+    /// Scheme advancing the particles by one step [default: euler]
+    #[arg(long, value_enum)]
+    pub integrator: Option<Integrator>,
+
+    /// Kernel softening the forces below the softening length [default: cutoff]
+    #[arg(long, value_enum)]
+    pub softening_kernel: Option<SofteningKernel>,
+
+    /// Scale the softening length of every particle with the square root
+    /// of its mass.
+    #[arg(long)]
+    pub adaptive_softening: bool,
+
+    /// What happens to particles leaving the world square from -1 to 1
+    /// [default: open, periodic for the particle-mesh solver]
+    #[arg(long, value_enum)]
+    pub boundary: Option<Boundary>,
+
+    /// Opening angle of the Barnes–Hut solver [default: 0.5]
+    #[arg(long)]
+    pub theta: Option<f32>,
+
+    /// Cells per side of the particle-mesh grid [default: 256]
+    #[arg(long)]
+    pub grid_size: Option<u32>,
+
+    /// Square covered by the mass field every step [default: bounding-box]
+    #[arg(long, value_enum)]
+    pub domain: Option<DomainMode>,
+
+    /// Moments of the mass field pixels, `monopole` for speed [default: quadrupole]
+    #[arg(long, value_enum)]
+    pub multipoles: Option<Multipoles>,
+
+    #[command(flatten)]
+    pub mass_field: MassFieldArgs,
+
+    /// Seed of the initial conditions. A random one is picked and logged if omitted.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Number of steps to simulate. Windowed runs keep going until closed if omitted.
+    #[arg(long)]
+    pub steps: Option<u64>,
+
+    /// Simulation timestep, the largest one of adaptive runs [default: 1/60]
+    #[arg(long)]
+    pub dt: Option<f32>,
+
+    /// Adapt the timestep of every step to the accelerations or jerks.
+    #[arg(long, value_enum)]
+    pub adaptive: Option<TimestepCriterion>,
+
+    /// Accuracy parameter of the adaptive and block timesteps [default: 0.02]
+    #[arg(long)]
+    pub eta: Option<f32>,
+
+    /// Timestep levels of the block integrator, the smallest timestep being
+    /// dt / 2^(levels - 1) [default: 8]
+    #[arg(long)]
+    pub block_levels: Option<u32>,
+
+    /// Write a snapshot every this many steps.
+    #[arg(long)]
+    pub snapshot_every: Option<u64>,
+
+    /// Directory the snapshots are written to.
+    #[arg(long)]
+    pub snapshot_dir: Option<PathBuf>,
+
+    /// Window width in logical pixels.
+    #[arg(long, default_value_t = 1024)]
+    pub width: u32,
+
+    /// Window height in logical pixels.
+    #[arg(long, default_value_t = 768)]
+    pub height: u32,
+
+    /// Run the compute passes only, without a window or swapchain.
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Run the headless simulation on the CPU reference passes instead of
+    /// a Vulkan device.
+    #[arg(long, requires = "headless")]
+    pub cpu: bool,
+}
+
+/// Resolution of the mass field, shared by the runs and the tools.
+#[derive(Debug, clap::Args)]
+pub struct MassFieldArgs {
+    /// Pixels per side of the finest mass field level [default: 2187]
+    #[arg(long)]
+    pub mass_field_size: Option<u32>,
+
+    /// Factor between the sides of neighbouring mass field levels [default: 3]
+    #[arg(long)]
+    pub mass_field_downsampling: Option<u32>,
+
+    /// Number of mass field levels, the coarsest one being summed whole
+    /// [default: down to the first level within reach]
+    #[arg(long)]
+    pub mass_field_levels: Option<u32>,
+
+    /// Pixels on each side of a particle summed on every mass field level
+    /// [default: 2]
+    #[arg(long)]
+    pub mass_field_reach: Option<u32>,
+
+    /// Particles per finest mass field pixel above which a tile gets a
+    /// finer patch [default: no patches]
+    #[arg(long)]
+    pub refine_density: Option<f32>,
+
+    /// Finest mass field pixels per side of the tiles the patches refine,
+    /// the pixels of one of the coarser levels [default: 27]
+    #[arg(long)]
+    pub patch_size: Option<u32>,
+
+    /// Patch pixels per side of a finest mass field pixel [default: 3]
+    #[arg(long)]
+    pub refine_factor: Option<u32>,
+
+    /// Most tiles refined at once [default: 64]
+    #[arg(long)]
+    pub max_patches: Option<u32>,
+}
+
+impl MassFieldArgs {
+    /// The resolution on the command line, then in the scenario, then the
+    /// default one.
+    pub fn config(&self, scenario: Option<&Scenario>) -> MassFieldConfig {
+        let defaults = MassFieldConfig::default();
+
+        MassFieldConfig {
+            size: self
+                .mass_field_size
+                .or(scenario.and_then(|s| s.mass_field_size))
+                .unwrap_or(defaults.size),
+            downsampling: self
+                .mass_field_downsampling
+                .or(scenario.and_then(|s| s.mass_field_downsampling))
+                .unwrap_or(defaults.downsampling),
+            levels: self
+                .mass_field_levels
+                .or(scenario.and_then(|s| s.mass_field_levels)),
+            reach: self
+                .mass_field_reach
+                .or(scenario.and_then(|s| s.mass_field_reach))
+                .unwrap_or(defaults.reach),
+            refine_density: self
+                .refine_density
+                .or(scenario.and_then(|s| s.refine_density)),
+            patch_size: self
+                .patch_size
+                .or(scenario.and_then(|s| s.patch_size))
+                .unwrap_or(defaults.patch_size),
+            refine_factor: self
+                .refine_factor
+                .or(scenario.and_then(|s| s.refine_factor))
+                .unwrap_or(defaults.refine_factor),
+            max_patches: self
+                .max_patches
+                .or(scenario.and_then(|s| s.max_patches))
+                .unwrap_or(defaults.max_patches),
+        }
+    }
+}
+
+/// Tools working on snapshots instead of running a simulation.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Compare the particles of two snapshots, e.g. a device run against the
+    /// CPU reference started from the same state.
+    Compare { a: PathBuf, b: PathBuf },
+
+    /// Report the relative force error of a solver against direct
+    /// summation over all particles of a snapshot.
+    ForceError {
+        snapshot: PathBuf,
+
+        /// Solver to evaluate.
+        #[arg(long, value_enum, default_value_t = SolverKind::MassField)]
+        solver: SolverKind,
+
+        /// Opening angle of the Barnes–Hut solver.
+        #[arg(long, default_value_t = DEFAULT_THETA)]
+        theta: f32,
+
+        /// Cells per side of the particle-mesh grid.
+        #[arg(long, default_value_t = DEFAULT_GRID_SIZE)]
+        grid_size: u32,
+
+        /// Square covered by the mass field.
+        #[arg(long, value_enum, default_value_t = DomainMode::default())]
+        domain: DomainMode,
+
+        /// Moments of the mass field pixels.
+        #[arg(long, value_enum, default_value_t = Multipoles::default())]
+        multipoles: Multipoles,
+
+        #[command(flatten)]
+        mass_field: MassFieldArgs,
+
+        /// Number of randomly picked particles to evaluate.
+        #[arg(long, default_value_t = 4096)]
+        sample: u32,
+
+        /// Seed picking the evaluated particles.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// Run the direct summation on the Vulkan device instead of the CPU.
+        #[arg(long)]
+        gpu: bool,
+    },
+
+    /// Check that depositing the particles of a snapshot into the mass field
+    /// keeps their whole mass on every level.
+    MassCheck {
+        snapshot: PathBuf,
+
+        /// Square covered by the mass field.
+        #[arg(long, value_enum, default_value_t = DomainMode::default())]
+        domain: DomainMode,
+
+        #[command(flatten)]
+        mass_field: MassFieldArgs,
+
+        /// Also deposit on the Vulkan device.
+        #[arg(long)]
+        gpu: bool,
+    },
+
+    /// Time depositing the particles of a snapshot into every level of the
+    /// mass field against depositing into the finest level and reducing it
+    /// into the coarser ones.
+    DepositTiming {
+        snapshot: PathBuf,
+
+        /// Square covered by the mass field.
+        #[arg(long, value_enum, default_value_t = DomainMode::default())]
+        domain: DomainMode,
+
+        /// Terms of the multipole expansion kept per pixel.
+        #[arg(long, value_enum, default_value_t = Multipoles::default())]
+        multipoles: Multipoles,
+
+        #[command(flatten)]
+        mass_field: MassFieldArgs,
+
+        /// Number of deposits timed.
+        #[arg(long, default_value_t = 10)]
+        repeat: u32,
+
+        /// Also time the deposits on the Vulkan device.
+        #[arg(long)]
+        gpu: bool,
+    },
+
+    /// Integrate a two-body Kepler orbit with direct summation on the CPU
+    /// and report the energy error and the distance to the analytic orbit.
+    Kepler {
+        /// Integrator to check, all of them if not given.
+        #[arg(long, value_enum)]
+        integrator: Option<Integrator>,
+
+        /// Number of full orbits.
+        #[arg(long, default_value_t = 10)]
+        orbits: u32,
+
+        /// Eccentricity of the orbit.
+        #[arg(long, default_value_t = 0.5)]
+        eccentricity: f32,
+
+        /// Semi-major axis of the relative orbit in world units.
+        #[arg(long, default_value_t = 0.6)]
+        semi_major_axis: f32,
+
+        /// Simulation timestep, the largest one of adaptive runs [default: 1/60].
+        #[arg(long)]
+        dt: Option<f32>,
+
+        /// Adapt the timestep of every step to the accelerations or jerks.
+        #[arg(long, value_enum)]
+        adaptive: Option<TimestepCriterion>,
+
+        /// Accuracy parameter of the adaptive timestep.
+        #[arg(long, default_value_t = DEFAULT_ETA)]
+        eta: f32,
+
+        /// Also run the orbit on the Vulkan device and compare it with the
+        /// CPU path.
+        #[arg(long)]
+        gpu: bool,
+    },
+}
+
+impl Args {
+    /// Builds the run from the scenario file if one was given, with the run
+    /// length, timestep and seed on the command line taking precedence.
+    pub fn into_run_config(self) -> Result<RunConfig> {
+        let scenario = self.scenario.as_deref().map(Scenario::load).transpose()?;
+        if let (Some(path), Some(scenario)) = (&self.scenario, &scenario) {
+            if scenario.populations.is_empty() && self.resume.is_none() {
+                return Err(anyhow!(
+                    "{}: populations: at least one population is required unless resuming",
+                    path.display()
+                ));
+            }
+        }
+        let (populations, mut physics, seed, dt, steps, report_every) = match &scenario {
+            Some(scenario) => (
+                scenario.populations.clone(),
+                scenario.physics,
+                scenario.seed,
+                scenario.dt,
+                scenario.steps,
+                scenario.report_every,
+            ),
+            None => {
+                let population = Population {
+                    generator: self.generator,
+                    count: self.count,
+                    radius: self.radius,
+                    thickness: self.thickness,
+                    angular_velocity: self.angular_velocity,
+                    offset: vec2(0.0, 0.0),
+                    velocity: vec2(0.0, 0.0),
+                    species: 0,
+                };
+
+                (vec![population], Physics::default(), None, None, None, None)
+            }
+        };
+
+        physics.kernel = self.softening_kernel.unwrap_or(physics.kernel);
+        physics.adaptive_softening |= self.adaptive_softening;
+
+        let solver = self
+            .solver
+            .or(scenario.as_ref().and_then(|s| s.solver))
+            .unwrap_or_default();
+        physics.boundary = self
+            .boundary
+            .or(scenario.as_ref().and_then(|s| s.boundary))
+            .unwrap_or(match solver {
+                SolverKind::ParticleMesh => Boundary::Periodic,
+                _ => Boundary::Open,
+            });
+
+        Ok(RunConfig {
+            populations,
+            species: scenario
+                .as_ref()
+                .map(|s| s.species.clone())
+                .unwrap_or_default(),
+            physics,
+            units: scenario.as_ref().and_then(|s| s.units.clone()),
+            seed: self.seed.or(seed),
+            solver,
+            integrator: self
+                .integrator
+                .or(scenario.as_ref().and_then(|s| s.integrator))
+                .unwrap_or_default(),
+            theta: self
+                .theta
+                .or(scenario.as_ref().and_then(|s| s.theta))
+                .unwrap_or(DEFAULT_THETA),
+            grid_size: self
+                .grid_size
+                .or(scenario.as_ref().and_then(|s| s.grid_size))
+                .unwrap_or(DEFAULT_GRID_SIZE),
+            domain: self
+                .domain
+                .or(scenario.as_ref().and_then(|s| s.domain))
+                .unwrap_or_default(),
+            multipoles: self
+                .multipoles
+                .or(scenario.as_ref().and_then(|s| s.multipoles))
+                .unwrap_or_default(),
+            mass_field: self.mass_field.config(scenario.as_ref()),
+            dt: self.dt.or(dt),
+            adaptive: self.adaptive.or(scenario.as_ref().and_then(|s| s.adaptive)),
+            eta: self
+                .eta
+                .or(scenario.as_ref().and_then(|s| s.eta))
+                .unwrap_or(DEFAULT_ETA),
+            block_levels: self
+                .block_levels
+                .or(scenario.as_ref().and_then(|s| s.block_levels))
+                .unwrap_or(DEFAULT_BLOCK_LEVELS),
+            steps: self.steps.or(steps),
+            report_every,
+            resume: self.resume,
+            snapshot_every: self
+                .snapshot_every
+                .or(scenario.as_ref().and_then(|s| s.snapshot_every)),
+            snapshot_dir: self
+                .snapshot_dir
+                .or(scenario.and_then(|s| s.snapshot_dir))
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_SNAPSHOT_DIR)),
+            headless: self.headless,
+            cpu: self.cpu,
+            width: self.width,
+            height: self.height,
+        })
+    }
+}
@@ -0,0 +1,218 @@
+//! Run metadata written next to the snapshots. It uses the scenario format,
+//! so the initial conditions of a run can be reproduced exactly with
+//! `--scenario`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::config::scenario::SCENARIO_VERSION;
+use crate::config::{
+    Boundary, DomainMode, Integrator, Multipoles, RunConfig, SofteningKernel, SolverKind,
+    TimestepCriterion,
+};
+use crate::generators::GeneratorKind;
+
+#[derive(Serialize)]
+struct MetadataFile<'a> {
+    version: u32,
+    seed: Option<u64>,
+    units: Option<UnitsSection<'a>>,
+    physics: PhysicsSection,
+    solver: SolverSection,
+    integrator: IntegratorSection,
+    mass_field: MassFieldSection,
+    output: OutputSection<'a>,
+    species: Vec<SpeciesSection<'a>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    populations: Vec<PopulationSection<'a>>,
+}
+
+#[derive(Serialize)]
+struct UnitsSection<'a> {
+    length: &'a str,
+    mass: &'a str,
+    time: &'a str,
+}
+
+#[derive(Serialize)]
+struct PhysicsSection {
+    /// Follows from the units if the run has them.
+    gravitational_constant: Option<f64>,
+    particle_mass: f64,
+    softening: f64,
+    softening_kernel: SofteningKernel,
+    adaptive_softening: bool,
+    boundary: Boundary,
+    dt: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct SolverSection {
+    kind: SolverKind,
+    theta: f64,
+    grid_size: u32,
+}
+
+#[derive(Serialize)]
+struct IntegratorSection {
+    kind: Integrator,
+    adaptive: Option<TimestepCriterion>,
+    eta: f64,
+    block_levels: u32,
+}
+
+#[derive(Serialize)]
+struct MassFieldSection {
+    size: u32,
+    downsampling: u32,
+    levels: Option<u32>,
+    reach: u32,
+    refine_density: Option<f64>,
+    patch_size: u32,
+    refine_factor: u32,
+    max_patches: u32,
+    domain: DomainMode,
+    multipoles: Multipoles,
+}
+
+#[derive(Serialize)]
+struct OutputSection<'a> {
+    steps: Option<u64>,
+    report_every: Option<u64>,
+    snapshot_every: Option<u64>,
+    snapshot_dir: &'a Path,
+}
+
+#[derive(Serialize)]
+struct SpeciesSection<'a> {
+    name: &'a str,
+    mass: f64,
+    color: Option<[f64; 4]>,
+}
+
+#[derive(Serialize)]
+struct PopulationSection<'a> {
+    generator: GeneratorKind,
+    count: u32,
+    radius: f64,
+    thickness: f64,
+    angular_velocity: f64,
+    offset: [f64; 2],
+    velocity: [f64; 2],
+    species: Option<&'a str>,
+}
+
+/// Writes the settings of a run starting at `start_step` into the snapshot
+/// directory as `run_<step>.toml`.
+pub fn write_run_metadata(config: &RunConfig, start_step: u64) -> Result<PathBuf> {
+    let file = MetadataFile {
+        version: SCENARIO_VERSION,
+        seed: config.seed,
+        units: config.units.as_ref().map(|units| UnitsSection {
+            length: &units.length.name,
+            mass: &units.mass.name,
+            time: &units.time.name,
+        }),
+        physics: PhysicsSection {
+            gravitational_constant: config
+                .units
+                .is_none()
+                .then(|| shortest(config.physics.gravitational_constant)),
+            particle_mass: shortest(config.physics.particle_mass),
+            softening: shortest(config.physics.softening),
+            softening_kernel: config.physics.kernel,
+            adaptive_softening: config.physics.adaptive_softening,
+            boundary: config.physics.boundary,
+            dt: config.dt.map(shortest),
+        },
+        solver: SolverSection {
+            kind: config.solver,
+            theta: shortest(config.theta),
+            grid_size: config.grid_size,
+        },
+        integrator: IntegratorSection {
+            kind: config.integrator,
+            adaptive: config.adaptive,
+            eta: shortest(config.eta),
+            block_levels: config.block_levels,
+        },
+        mass_field: MassFieldSection {
+            size: config.mass_field.size,
+            downsampling: config.mass_field.downsampling,
+            levels: config.mass_field.levels,
+            reach: config.mass_field.reach,
+            refine_density: config.mass_field.refine_density.map(shortest),
+            patch_size: config.mass_field.patch_size,
+            refine_factor: config.mass_field.refine_factor,
+            max_patches: config.mass_field.max_patches,
+            domain: config.domain,
+            multipoles: config.multipoles,
+        },
+        output: OutputSection {
+            steps: config.steps,
+            report_every: config.report_every,
+            snapshot_every: config.snapshot_every,
+            snapshot_dir: &config.snapshot_dir,
+        },
+        species: config
+            .species
+            .iter()
+            .map(|s| SpeciesSection {
+                name: &s.name,
+                mass: shortest(s.mass),
+                color: s.color.map(|c| c.map(shortest)),
+            })
+            .collect(),
+        populations: config
+            .populations
+            .iter()
+            .filter(|_| config.resume.is_none())
+            .map(|p| PopulationSection {
+                generator: p.generator,
+                count: p.count,
+                radius: shortest(p.radius),
+                thickness: shortest(p.thickness),
+                angular_velocity: shortest(p.angular_velocity),
+                offset: [shortest(p.offset.x), shortest(p.offset.y)],
+                velocity: [shortest(p.velocity.x), shortest(p.velocity.y)],
+                species: p
+                    .species
+                    .checked_sub(1)
+                    .map(|i| config.species[i as usize].name.as_str()),
+            })
+            .collect(),
+    };
+
+    let mut contents = format!(
+        "# Written by {} {}\n",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+
+    if let Some(resume) = &config.resume {
+        contents += &format!(
+            "# Resumed from {} at step {}, populations are not recorded, use with --resume\n",
+            resume.display(),
+            start_step
+        );
+    }
+
+    contents += "\n";
+    contents += &toml::to_string(&file)?;
+
+    let dir = &config.snapshot_dir;
+    std::fs::create_dir_all(dir).map_err(|e| anyhow!("{}: {}", dir.display(), e))?;
+
+    let path = dir.join(format!("run_{:010}.toml", start_step));
+    std::fs::write(&path, contents).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+    Ok(path)
+}
+
+/// Widens `value` to the shortest `f64` that reads back as the same `f32`,
+/// so the file shows `0.2` instead of `0.20000000298023224`.
+fn shortest(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
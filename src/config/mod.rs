@@ -0,0 +1,753 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use cgmath::vec2;
+use clap::ValueEnum;
+use log::info;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::config::units::Units;
+use crate::cpu::boundary;
+use crate::data::globals;
+use crate::data::vertex::Vertex;
+use crate::generators::{random_generator, GeneratorKind};
+use crate::snapshot::{Snapshot, SnapshotHeader};
+use crate::utils::mass_field;
+
+pub mod cli;
+pub mod metadata;
+pub mod scenario;
+pub mod units;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Constants of the force law `a = G m / r^2`, in the units of the run with
+/// world lengths.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Physics {
+    pub gravitational_constant: f32,
+    /// Mass of the particles without a species.
+    pub particle_mass: f32,
+    /// Length below which the softening kernel weakens the force.
+    pub softening: f32,
+    pub kernel: SofteningKernel,
+    /// Scales the softening length of every particle with the square root
+    /// of its mass relative to `particle_mass`, so heavier species are
+    /// softened over a larger region.
+    pub adaptive_softening: bool,
+    /// What happens to particles leaving the world square from -1 to 1.
+    pub boundary: Boundary,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            gravitational_constant: 1e-4,
+            particle_mass: 0.03,
+            softening: 0.24,
+            kernel: SofteningKernel::default(),
+            adaptive_softening: false,
+            boundary: Boundary::default(),
+        }
+    }
+}
+
+impl Physics {
+    /// Physics given in normalized field units, half the world size, with
+    /// the particle mass folded into the constant. Version 1 scenarios and
+    /// snapshots before version 3 state them like that.
+    pub fn from_field_units(
+        gravitational_constant: f32,
+        particle_mass: f32,
+        softening: f32,
+    ) -> Self {
+        Self {
+            gravitational_constant: FIELD_SCALE
+                * FIELD_SCALE
+                * gravitational_constant
+                * particle_mass,
+            particle_mass,
+            softening: FIELD_SCALE * softening,
+            ..Default::default()
+        }
+    }
+
+    /// The gravitational constant for distances in normalized field units,
+    /// which the solvers measure.
+    pub fn field_gravitational_constant(&self) -> f32 {
+        self.gravitational_constant / (FIELD_SCALE * FIELD_SCALE)
+    }
+
+    /// The softening in normalized field units.
+    pub fn field_softening(&self) -> f32 {
+        self.softening / FIELD_SCALE
+    }
+
+    /// The softening length of a particle of `mass`.
+    pub fn softening_of(&self, mass: f32) -> f32 {
+        if self.adaptive_softening {
+            self.softening * (mass / self.particle_mass).sqrt()
+        } else {
+            self.softening
+        }
+    }
+
+    /// The softening of a particle of `mass` in normalized field units.
+    pub fn field_softening_of(&self, mass: f32) -> f32 {
+        self.softening_of(mass) / FIELD_SCALE
+    }
+}
+
+/// World lengths per normalized field length.
+const FIELD_SCALE: f32 = 2.0;
+
+/// How the force between two masses closer than the softening length is
+/// weakened, see `cpu/softening.rs`. The values are the ones of the
+/// shaders and snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SofteningKernel {
+    /// No force at all below the softening length, as in the first
+    /// versions of the simulator.
+    #[default]
+    Cutoff = 0,
+    /// The force of a Plummer sphere, `G m r / (r^2 + eps^2)^(3/2)`.
+    Plummer = 1,
+    /// The cubic spline kernel of Monaghan and Lattanzio, exactly Newtonian
+    /// beyond 2.8 softening lengths.
+    Spline = 2,
+}
+
+/// Boundary conditions of the world square from -1 to 1, see
+/// `cpu/boundary.rs`. The values are the ones of the shaders and snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Boundary {
+    /// No walls, the particles move on wherever they go.
+    #[default]
+    Open = 0,
+    /// Particles leaving the square enter it on the opposite side and the
+    /// forces come from the nearest periodic image of every particle.
+    Periodic = 1,
+    /// Particles bounce off the walls with the normal velocity mirrored.
+    Reflecting = 2,
+    /// Particles leaving the square are removed from the run.
+    Absorbing = 3,
+}
+
+/// Methods computing the gravitational forces.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SolverKind {
+    /// Mass field pyramid in the `mass.comp` and `gravity.comp` passes.
+    #[default]
+    MassField,
+    /// Pairwise direct summation, quadratic in the particle count.
+    Direct,
+    /// Barnes–Hut quadtree on the CPU, opening nodes wider than `theta`.
+    BarnesHut,
+    /// Particle-mesh FFT solver on the CPU with periodic boundaries.
+    ParticleMesh,
+}
+
+impl SolverKind {
+    /// Whether the forces are computed by the compute shaders.
+    pub fn runs_on_device(self) -> bool {
+        matches!(self, SolverKind::MassField | SolverKind::Direct)
+    }
+}
+
+/// How the square covered by the mass field is chosen every step, see
+/// `cpu/domain.rs`. The values are the ones of `domain.comp`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DomainMode {
+    /// The world square from -1 to 1, as in the first versions of the
+    /// simulator.
+    Fixed = 0,
+    /// The bounding box of all particles.
+    #[default]
+    BoundingBox = 1,
+    /// The box between the percentiles of the particle positions, leaving
+    /// far outliers outside of the field.
+    Percentile = 2,
+}
+
+/// Moments of the mass field pixels the forces are expanded in, see
+/// `cpu/mass_field.rs`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Multipoles {
+    /// The mass at the center of mass only, cheaper to deposit and
+    /// evaluate.
+    Monopole = 0,
+    /// The second moments about the center of mass on top.
+    #[default]
+    Quadrupole = 1,
+}
+
+/// Schemes advancing the particles by one step.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Integrator {
+    /// Explicit Euler, drifting with the velocity before the kick.
+    #[default]
+    Euler,
+    /// Kick-drift-kick leapfrog, second order and symplectic.
+    Leapfrog,
+    /// Yoshida's composition of three leapfrog steps, fourth order and
+    /// symplectic.
+    Yoshida4,
+    /// Classic fourth order Runge–Kutta, on the host.
+    Rk4,
+    /// Fourth order Hermite predictor-corrector with the jerks of direct
+    /// summation, on the host.
+    Hermite,
+    /// Kick-drift-kick leapfrog with a power-of-two timestep per particle,
+    /// only kicking the particles whose timestep ends.
+    Block,
+}
+
+impl Integrator {
+    /// Whether the particle buffers hold velocities behind the positions
+    /// between steps.
+    pub fn staggered(self) -> bool {
+        matches!(self, Integrator::Leapfrog | Integrator::Yoshida4)
+    }
+
+    /// Whether the compute shaders can step, the `Update` passes and the
+    /// block timesteps of the direct solver.
+    pub fn runs_on_device(self) -> bool {
+        matches!(
+            self,
+            Integrator::Euler | Integrator::Leapfrog | Integrator::Yoshida4 | Integrator::Block
+        )
+    }
+}
+
+/// Quantities the adaptive timestep is chosen from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimestepCriterion {
+    /// `sqrt(2 eta softening / max |a|)`, needs a softening length.
+    Acceleration,
+    /// `eta min |a| / |j|` with the jerks of direct summation.
+    Jerk,
+}
+
+/// Default Barnes–Hut opening angle.
+pub const DEFAULT_THETA: f32 = 0.5;
+/// Default number of particle-mesh cells per side.
+pub const DEFAULT_GRID_SIZE: u32 = 256;
+/// Default accuracy parameter of the adaptive and block timesteps.
+pub const DEFAULT_ETA: f32 = 0.02;
+/// Default number of block timestep levels, the smallest timestep being
+/// `dt / 2^(levels - 1)`.
+pub const DEFAULT_BLOCK_LEVELS: u32 = 8;
+/// Default pixels per side of the finest mass field level.
+pub const DEFAULT_MASS_FIELD_SIZE: u32 = 2187;
+/// Default factor between the sides of neighbouring mass field levels.
+pub const DEFAULT_DOWNSAMPLING: u32 = 3;
+/// Default pixels on each side of the particle summed on every mass field
+/// level.
+pub const DEFAULT_REACH: u32 = 2;
+/// Default finest level pixels per side of the tiles refinement patches
+/// cover.
+pub const DEFAULT_PATCH_SIZE: u32 = 27;
+/// Default patch pixels per side of a finest level pixel.
+pub const DEFAULT_REFINE_FACTOR: u32 = 3;
+/// Default number of refinement patches.
+pub const DEFAULT_MAX_PATCHES: u32 = 64;
+
+/// Resolution of the mass field pyramid, see `utils/mass_field.rs`. Finer
+/// levels and a larger reach make the forces more accurate and slower.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MassFieldConfig {
+    /// Pixels per side of the finest level.
+    pub size: u32,
+    /// Factor between the sides of neighbouring levels.
+    pub downsampling: u32,
+    /// Number of levels, down to the first one within reach if not set.
+    /// The coarsest level is summed whole.
+    pub levels: Option<u32>,
+    /// Pixels on each side of the particle summed on every level.
+    pub reach: u32,
+    /// Particles per finest level pixel above which a tile gets a
+    /// refinement patch, no patches if not set.
+    pub refine_density: Option<f32>,
+    /// Finest level pixels per side of a tile, the pixels of one of the
+    /// coarser levels.
+    pub patch_size: u32,
+    /// Patch pixels per side of a finest level pixel.
+    pub refine_factor: u32,
+    /// Most tiles refined at once.
+    pub max_patches: u32,
+}
+
+impl Default for MassFieldConfig {
+    fn default() -> Self {
+        Self {
+            size: DEFAULT_MASS_FIELD_SIZE,
+            downsampling: DEFAULT_DOWNSAMPLING,
+            levels: None,
+            reach: DEFAULT_REACH,
+            refine_density: None,
+            patch_size: DEFAULT_PATCH_SIZE,
+            refine_factor: DEFAULT_REFINE_FACTOR,
+            max_patches: DEFAULT_MAX_PATCHES,
+        }
+    }
+}
+
+impl MassFieldConfig {
+    /// Rejects pyramids the shaders cannot build.
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=globals::MAX_MASS_FIELD_SIZE).contains(&self.size) {
+            return Err(anyhow!(
+                "The mass field size has to be between 1 and {}",
+                globals::MAX_MASS_FIELD_SIZE
+            ));
+        }
+
+        if !(2..=globals::MAX_DOWNSAMPLING).contains(&self.downsampling) {
+            return Err(anyhow!(
+                "The mass field downsampling has to be between 2 and {}",
+                globals::MAX_DOWNSAMPLING
+            ));
+        }
+
+        if !(1..=globals::MAX_REACH).contains(&self.reach) {
+            return Err(anyhow!(
+                "The mass field reach has to be between 1 and {}",
+                globals::MAX_REACH
+            ));
+        }
+
+        // Every level has to cover exactly `downsampling` pixels of the one
+        // below it on each side.
+        if let Some(levels) = self.levels {
+            let most = mass_field::divisible_levels(self.size, self.downsampling);
+            if !(2..=most).contains(&levels) {
+                return Err(anyhow!(
+                    "A mass field of size {} with downsampling {} has between 2 and {} levels",
+                    self.size,
+                    self.downsampling,
+                    most
+                ));
+            }
+        } else if !self.size.is_multiple_of(self.downsampling) {
+            return Err(anyhow!(
+                "The mass field size {} has to be divisible by the downsampling {}",
+                self.size,
+                self.downsampling
+            ));
+        }
+
+        if self.refine_density.is_some() {
+            self.validate_patches()?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_patches(&self) -> Result<()> {
+        if !self
+            .refine_density
+            .is_some_and(|d| d.is_finite() && d > 0.0)
+        {
+            return Err(anyhow!("The refinement density must be greater than zero"));
+        }
+
+        if mass_field::tile_level(self).is_none() {
+            return Err(anyhow!(
+                "The patch size has to be the finest pixels per side of a pixel of one of the \
+                 coarser levels, {}",
+                mass_field::level_sizes(self)[1..]
+                    .iter()
+                    .map(|size| (self.size / size).to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        // A particle only uses the patches when the pixels within reach of
+        // it fit into two tiles per side.
+        if self.patch_size < 2 * self.reach + 1 {
+            return Err(anyhow!(
+                "The patch size has to be at least {} to hold the reach",
+                2 * self.reach + 1
+            ));
+        }
+
+        if !(2..=globals::MAX_DOWNSAMPLING).contains(&self.refine_factor) {
+            return Err(anyhow!(
+                "The refinement factor has to be between 2 and {}",
+                globals::MAX_DOWNSAMPLING
+            ));
+        }
+
+        if !(1..=globals::MAX_PATCHES).contains(&self.max_patches) {
+            return Err(anyhow!(
+                "The number of patches has to be between 1 and {}",
+                globals::MAX_PATCHES
+            ));
+        }
+
+        // The patches get no more pixels than the largest finest level.
+        let side = (self.patch_size * self.refine_factor) as u64;
+        let limit = (globals::MAX_MASS_FIELD_SIZE as u64).pow(2);
+        if self.max_patches as u64 * side * side > limit {
+            return Err(anyhow!(
+                "The patches can have at most {} pixels together, not {}",
+                limit,
+                self.max_patches as u64 * side * side
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// A kind of particle with its own mass and colour, like stars, dark matter
+/// or black holes. Species are numbered from 1 in the order they are
+/// defined, 0 being the particles without one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Species {
+    pub name: String,
+    pub mass: f32,
+    /// RGBA colour of the particles, colored by their speed if not set.
+    pub color: Option<[f32; 4]>,
+}
+
+/// A group of particles created by one generator and then moved by
+/// `offset` and `velocity`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Population {
+    pub generator: GeneratorKind,
+    pub count: u32,
+    pub radius: f32,
+    pub thickness: f32,
+    pub angular_velocity: f32,
+    pub offset: Vec2,
+    pub velocity: Vec2,
+    /// Species of the particles, 0 for none.
+    pub species: u32,
+}
+
+impl Default for Population {
+    fn default() -> Self {
+        Self {
+            generator: GeneratorKind::TwoCircularClusters,
+            count: 1_000_000,
+            radius: 0.2,
+            thickness: 0.0001,
+            angular_velocity: 0.0,
+            offset: vec2(0.0, 0.0),
+            velocity: vec2(0.0, 0.0),
+            species: 0,
+        }
+    }
+}
+
+impl Population {
+    /// Generates the particles with the `mass` of their species.
+    pub fn generate(&self, rng: &mut ChaCha8Rng, mass: f32) -> Vec<Vertex> {
+        let mut vertices = random_generator::generate(
+            self.generator,
+            self.count,
+            self.radius,
+            self.thickness,
+            self.angular_velocity,
+            rng,
+        );
+
+        vertices.iter_mut().for_each(|v| {
+            v.pos += self.offset;
+            v.velocity += self.velocity;
+            *v = v.with_species(self.species, mass);
+        });
+
+        vertices
+    }
+}
+
+/// Everything needed to start a run, assembled from the command line and an
+/// optional scenario file.
+#[derive(Clone, Debug, Default)]
+pub struct RunConfig {
+    pub populations: Vec<Population>,
+    pub species: Vec<Species>,
+    pub physics: Physics,
+    /// Units of the lengths, masses and times, simulation units if unset.
+    pub units: Option<Units>,
+    pub seed: Option<u64>,
+    pub solver: SolverKind,
+    pub integrator: Integrator,
+    pub theta: f32,
+    pub grid_size: u32,
+    /// Square covered by the mass field.
+    pub domain: DomainMode,
+    /// Moments of the mass field pixels.
+    pub multipoles: Multipoles,
+    /// Resolution of the mass field.
+    pub mass_field: MassFieldConfig,
+
+    /// Maximum timestep of adaptive runs.
+    pub dt: Option<f32>,
+    /// Adapts the timestep of every step when set.
+    pub adaptive: Option<TimestepCriterion>,
+    pub eta: f32,
+    /// Timestep levels of the block integrator.
+    pub block_levels: u32,
+    pub steps: Option<u64>,
+    pub report_every: Option<u64>,
+
+    /// Snapshot to continue from instead of generating the populations.
+    pub resume: Option<PathBuf>,
+    pub snapshot_every: Option<u64>,
+    pub snapshot_dir: PathBuf,
+
+    pub headless: bool,
+    pub cpu: bool,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RunConfig {
+    /// Rejects combinations of solver, integrator and timestep that cannot
+    /// run.
+    pub fn validate(&self) -> Result<()> {
+        if self.dt.is_some_and(|dt| !(dt.is_finite() && dt > 0.0)) {
+            return Err(anyhow!("dt must be greater than zero"));
+        }
+
+        if self.populations.iter().any(|p| p.count == 0) {
+            return Err(anyhow!("The particle count must be greater than zero"));
+        }
+
+        if self.snapshot_every == Some(0) {
+            return Err(anyhow!("The snapshot interval must be greater than zero"));
+        }
+
+        self.mass_field.validate()?;
+
+        let theta = self.theta;
+        if self.solver == SolverKind::BarnesHut && !(theta.is_finite() && theta > 0.0) {
+            return Err(anyhow!("theta must be greater than zero"));
+        }
+
+        if self.solver == SolverKind::ParticleMesh && self.grid_size == 0 {
+            return Err(anyhow!(
+                "The particle-mesh grid size must be greater than zero"
+            ));
+        }
+
+        if self.species.len() >= globals::MAX_SPECIES {
+            return Err(anyhow!(
+                "At most {} species can be defined",
+                globals::MAX_SPECIES - 1
+            ));
+        }
+
+        let boundary = self.physics.boundary;
+        if self.solver == SolverKind::ParticleMesh && boundary != Boundary::Periodic {
+            return Err(anyhow!(
+                "The particle-mesh solver only has periodic boundaries"
+            ));
+        }
+
+        if self.solver == SolverKind::BarnesHut && boundary == Boundary::Periodic {
+            return Err(anyhow!(
+                "The Barnes–Hut solver does not sum the periodic images"
+            ));
+        }
+
+        if boundary != Boundary::Open && !self.integrator.runs_on_device() {
+            return Err(anyhow!(
+                "The {:?} integrator does not apply the {:?} boundary",
+                self.integrator,
+                boundary
+            ));
+        }
+
+        let jerks = self.integrator == Integrator::Hermite
+            || self.adaptive == Some(TimestepCriterion::Jerk);
+        if jerks && self.solver != SolverKind::Direct {
+            return Err(anyhow!("Jerks are only computed by the direct solver"));
+        }
+
+        if self.adaptive == Some(TimestepCriterion::Acceleration) && self.physics.softening <= 0.0 {
+            return Err(anyhow!(
+                "The acceleration timestep criterion needs a softening length"
+            ));
+        }
+
+        let block = self.integrator == Integrator::Block;
+        if block && self.adaptive.is_some() {
+            return Err(anyhow!(
+                "Block timesteps already adapt to every particle, drop --adaptive"
+            ));
+        }
+
+        if block && self.physics.softening <= 0.0 {
+            return Err(anyhow!("Block timesteps need a softening length"));
+        }
+
+        if block && !(1..=globals::MAX_BLOCK_LEVELS).contains(&self.block_levels) {
+            return Err(anyhow!(
+                "The block levels have to be between 1 and {}",
+                globals::MAX_BLOCK_LEVELS
+            ));
+        }
+
+        if (self.adaptive.is_some() || block) && !(self.eta.is_finite() && self.eta > 0.0) {
+            return Err(anyhow!("eta must be greater than zero"));
+        }
+
+        Ok(())
+    }
+
+    /// Square covered by the mass field. Walls keep it on the world square
+    /// they enclose.
+    pub fn mass_field_domain(&self) -> DomainMode {
+        match self.physics.boundary {
+            Boundary::Open => self.domain,
+            _ => DomainMode::Fixed,
+        }
+    }
+
+    /// Whether the steps run in the compute shaders. The rest are computed
+    /// by the CPU versions of the solvers. Block timesteps need the forces
+    /// of a subset of the particles, which only the direct pass computes.
+    pub fn steps_on_device(&self) -> bool {
+        let solver = match self.integrator {
+            Integrator::Block => self.solver == SolverKind::Direct,
+            _ => self.solver.runs_on_device(),
+        };
+
+        solver && self.integrator.runs_on_device() && self.adaptive.is_none()
+    }
+
+    /// Generates the populations from `seed`. Every population draws from
+    /// its own ChaCha stream, so editing one population leaves the particles
+    /// of the others unchanged, and the output does not depend on the `rand`
+    /// version like `StdRng` would.
+    pub fn generate_vertices(&self, seed: u64) -> Vec<Vertex> {
+        self.populations
+            .iter()
+            .enumerate()
+            .flat_map(|(i, p)| {
+                let mut rng = ChaCha8Rng::seed_from_u64(seed);
+                rng.set_stream(i as u64);
+                p.generate(&mut rng, self.species_mass(p.species))
+            })
+            .collect()
+    }
+
+    /// Mass of the particles of `species`.
+    pub fn species_mass(&self, species: u32) -> f32 {
+        match species {
+            0 => self.physics.particle_mass,
+            _ => self.species[species as usize - 1].mass,
+        }
+    }
+
+    /// RGBA colour of every species index the shaders can draw, zero for
+    /// the ones colored by their speed.
+    pub fn species_colors(&self) -> [[f32; 4]; globals::MAX_SPECIES] {
+        let mut colors = [[0.0; 4]; globals::MAX_SPECIES];
+        for (color, species) in colors[1..].iter_mut().zip(&self.species) {
+            *color = species.color.unwrap_or_default();
+        }
+        colors
+    }
+
+    /// Takes the physics, seed and timestep recorded in the snapshot a run
+    /// resumes from in place of the configured ones, except for a timestep
+    /// given explicitly on the command line. The snapshot must have been
+    /// written in the units of the run, if it has any. Only reads the
+    /// header, so the run can be validated before the particles are loaded.
+    pub fn resume_header(&mut self) -> Result<()> {
+        let Some(path) = &self.resume else {
+            return Ok(());
+        };
+
+        let header = SnapshotHeader::load(path)?;
+        if header.particle_count == 0 {
+            return Err(anyhow!("{}: snapshot has no particles", path.display()));
+        }
+
+        if let Some(units) = &self.units {
+            let constant = units.gravitational_constant() as f32;
+            let recorded = header.physics.gravitational_constant;
+            if (recorded - constant).abs() > 1e-4 * constant.abs() {
+                return Err(anyhow!(
+                    "{}: the snapshot has G = {:e}, not the {:e} of {}",
+                    path.display(),
+                    recorded,
+                    constant,
+                    units
+                ));
+            }
+        }
+
+        self.physics = header.physics;
+        // Snapshots before version 5 do not record that the particle-mesh
+        // runs were periodic.
+        if self.solver == SolverKind::ParticleMesh {
+            self.physics.boundary = Boundary::Periodic;
+        }
+        self.seed = header.seed;
+        let dt = self.dt.or((header.dt > 0.0).then_some(header.dt));
+        self.dt = Some(dt.unwrap_or(globals::DEFAULT_DELTA_T));
+
+        Ok(())
+    }
+
+    /// Builds the state the run starts from, after `resume_header`. A random
+    /// seed is picked and logged if none was given. Resumed particles must
+    /// belong to the species of the run.
+    pub fn initial_state(&mut self) -> Result<Snapshot> {
+        let Some(path) = &self.resume else {
+            let seed = *self.seed.get_or_insert_with(rand::random);
+            info!("seed {}", seed);
+            if let Some(units) = &self.units {
+                info!(
+                    "units {}, G = {:e}",
+                    units, self.physics.gravitational_constant
+                );
+            }
+
+            let dt = *self.dt.get_or_insert(globals::DEFAULT_DELTA_T);
+            let mut vertices = self.generate_vertices(seed);
+            boundary::apply_all(self.physics.boundary, &mut vertices);
+            return Ok(Snapshot {
+                header: SnapshotHeader {
+                    particle_count: vertices.len() as u64,
+                    step: 0,
+                    sim_time: 0.0,
+                    seed: self.seed,
+                    dt,
+                    physics: self.physics,
+                },
+                vertices,
+            });
+        };
+
+        let snapshot = Snapshot::load(path)?;
+        if let Some(v) = snapshot
+            .vertices
+            .iter()
+            .find(|v| v.species as usize > self.species.len())
+        {
+            return Err(anyhow!(
+                "{}: species {} is not defined, resume with the scenario of the run",
+                path.display(),
+                v.species
+            ));
+        }
+        Ok(snapshot)
+    }
+}
@@ -0,0 +1,757 @@
+//! Scenario files describe a run in TOML so it can be versioned and reviewed
+//! instead of being edited into the code:
+//!
+//! ```toml
+//! version = 3
+//! seed = 42
+//!
+//! [units]
+//! length = "10 kpc"
+//! mass = "1e10 Msun"
+//! time = "Gyr"
+//!
+//! [physics]
+//! particle_mass = 0.03
+//! softening = 0.02
+//! softening_kernel = "spline"
+//! adaptive_softening = true
+//! boundary = "periodic"
+//! dt = 0.001
+//!
+//! [solver]
+//! kind = "mass-field"
+//! theta = 0.5
+//! grid_size = 256
+//!
+//! [integrator]
+//! kind = "leapfrog"
+//! adaptive = "acceleration"
+//! eta = 0.02
+//! block_levels = 8
+//!
+//! [mass_field]
+//! size = 2187
+//! downsampling = 3
+//! levels = 7
+//! reach = 2
+//! refine_density = 4.0
+//! patch_size = 27
+//! refine_factor = 3
+//! max_patches = 64
+//! domain = "percentile"
+//! multipoles = "quadrupole"
+//!
+//! [output]
+//! steps = 5000
+//! report_every = 100
+//! snapshot_every = 1000
+//! snapshot_dir = "snapshots"
+//!
+//! [[species]]
+//! name = "stars"
+//! mass = 0.03
+//! color = [1.0, 0.85, 0.6, 0.05]
+//!
+//! [[species]]
+//! name = "black-holes"
+//! mass = 300.0
+//! color = [1.0, 0.0, 0.0, 1.0]
+//!
+//! [[populations]]
+//! generator = "circular-cluster"
+//! count = 500000
+//! radius = 0.2
+//! thickness = 0.0001
+//! offset = [-0.5, 0.0]
+//! velocity = [0.0, 0.01]
+//! species = "stars"
+//! ```
+//!
+//! Every section is optional and falls back to the built in defaults,
+//! except that a run needs `populations` unless it resumes from a
+//! snapshot. Populations without a species have the particle mass of the
+//! physics section and are colored by their speed.
+//!
+//! Lengths are in world units, in which the particles are generated
+//! between -1 and 1. With `[units]` the world unit is the length unit and
+//! the gravitational constant follows from the units, see `units.rs`.
+//! Without them `physics.gravitational_constant` can be set instead.
+//! Version 1 files gave the constants in normalized field units and are
+//! converted. Files before version 3 keep the mass field on the world
+//! square and without quadrupoles unless they set `mass_field.domain` and
+//! `mass_field.multipoles`.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use cgmath::vec2;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::config::units::{Dimension, Unit, Units};
+use crate::config::{
+    Boundary, DomainMode, Integrator, Multipoles, Physics, Population, SofteningKernel, SolverKind,
+    Species, TimestepCriterion,
+};
+use crate::data::globals;
+use crate::generators::GeneratorKind;
+
+pub const SCENARIO_VERSION: u32 = 3;
+
+/// Defaults of version 1 files, in normalized field units.
+const V1_GRAVITATIONAL_CONSTANT: f32 = 30000.0 / (6000.0 * 6000.0);
+const V1_SOFTENING: f32 = 0.12;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    version: Spanned<u32>,
+    seed: Option<u64>,
+    units: Option<Spanned<UnitsSection>>,
+    #[serde(default)]
+    physics: PhysicsSection,
+    #[serde(default)]
+    solver: SolverSection,
+    #[serde(default)]
+    integrator: IntegratorSection,
+    #[serde(default)]
+    mass_field: MassFieldSection,
+    #[serde(default)]
+    output: OutputSection,
+    #[serde(default)]
+    species: Vec<SpeciesSection>,
+    populations: Option<Spanned<Vec<PopulationSection>>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UnitsSection {
+    length: Spanned<String>,
+    mass: Spanned<String>,
+    time: Spanned<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PhysicsSection {
+    gravitational_constant: Option<Spanned<f32>>,
+    particle_mass: Option<Spanned<f32>>,
+    softening: Option<Spanned<f32>>,
+    softening_kernel: Option<SofteningKernel>,
+    adaptive_softening: Option<bool>,
+    boundary: Option<Boundary>,
+    dt: Option<Spanned<f32>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SolverSection {
+    kind: Option<SolverKind>,
+    theta: Option<Spanned<f32>>,
+    grid_size: Option<Spanned<u32>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct IntegratorSection {
+    kind: Option<Integrator>,
+    adaptive: Option<TimestepCriterion>,
+    eta: Option<Spanned<f32>>,
+    block_levels: Option<Spanned<u32>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MassFieldSection {
+    size: Option<Spanned<u32>>,
+    downsampling: Option<Spanned<u32>>,
+    levels: Option<Spanned<u32>>,
+    reach: Option<Spanned<u32>>,
+    refine_density: Option<Spanned<f32>>,
+    patch_size: Option<Spanned<u32>>,
+    refine_factor: Option<Spanned<u32>>,
+    max_patches: Option<Spanned<u32>>,
+    domain: Option<DomainMode>,
+    multipoles: Option<Multipoles>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OutputSection {
+    steps: Option<Spanned<u64>>,
+    report_every: Option<Spanned<u64>>,
+    snapshot_every: Option<Spanned<u64>>,
+    snapshot_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpeciesSection {
+    name: Spanned<String>,
+    mass: Spanned<f32>,
+    color: Option<Spanned<[f32; 4]>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PopulationSection {
+    generator: GeneratorKind,
+    count: Spanned<u32>,
+    radius: Option<Spanned<f32>>,
+    thickness: Option<Spanned<f32>>,
+    angular_velocity: Option<Spanned<f32>>,
+    offset: Option<Spanned<[f32; 2]>>,
+    velocity: Option<Spanned<[f32; 2]>>,
+    species: Option<Spanned<String>>,
+}
+
+/// A validated scenario file.
+#[derive(Clone, Debug)]
+pub struct Scenario {
+    pub populations: Vec<Population>,
+    pub species: Vec<Species>,
+    pub physics: Physics,
+    /// Boundary of the physics, the default of the solver if not given.
+    pub boundary: Option<Boundary>,
+    pub units: Option<Units>,
+    pub seed: Option<u64>,
+    pub solver: Option<SolverKind>,
+    pub integrator: Option<Integrator>,
+    pub adaptive: Option<TimestepCriterion>,
+    pub eta: Option<f32>,
+    pub block_levels: Option<u32>,
+    pub theta: Option<f32>,
+    pub grid_size: Option<u32>,
+    pub domain: Option<DomainMode>,
+    pub multipoles: Option<Multipoles>,
+    pub mass_field_size: Option<u32>,
+    pub mass_field_downsampling: Option<u32>,
+    pub mass_field_levels: Option<u32>,
+    pub mass_field_reach: Option<u32>,
+    pub refine_density: Option<f32>,
+    pub patch_size: Option<u32>,
+    pub refine_factor: Option<u32>,
+    pub max_patches: Option<u32>,
+    pub dt: Option<f32>,
+    pub steps: Option<u64>,
+    pub report_every: Option<u64>,
+    pub snapshot_every: Option<u64>,
+    pub snapshot_dir: Option<PathBuf>,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let source =
+            std::fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+        Self::parse(path, &source)
+    }
+
+    pub fn parse(path: &Path, source: &str) -> Result<Self> {
+        let checker = Checker { path, source };
+
+        let file: ScenarioFile = toml::from_str(source).map_err(|e| {
+            let span = e.span().unwrap_or(0..0);
+            checker.error(span, e.message())
+        })?;
+
+        let version = checker.check(&file.version, "version", |v| {
+            (1..=SCENARIO_VERSION)
+                .contains(v)
+                .then_some(*v)
+                .ok_or_else(|| format!("must be between 1 and {}", SCENARIO_VERSION))
+        })?;
+
+        let units = file
+            .units
+            .as_ref()
+            .map(|units| checker.units(units, version))
+            .transpose()?;
+
+        let gravitational_constant = checker.positive_opt(
+            &file.physics.gravitational_constant,
+            "physics.gravitational_constant",
+        )?;
+        if let (Some(_), Some(constant)) = (&units, &file.physics.gravitational_constant) {
+            return Err(checker.error(
+                constant.span(),
+                "physics.gravitational_constant follows from the units",
+            ));
+        }
+
+        let particle_mass =
+            checker.positive_opt(&file.physics.particle_mass, "physics.particle_mass")?;
+        let softening = file
+            .physics
+            .softening
+            .as_ref()
+            .map(|softening| {
+                checker.check(softening, "physics.softening", |v| {
+                    (v.is_finite() && *v >= 0.0)
+                        .then_some(*v)
+                        .ok_or_else(|| "must not be negative".to_string())
+                })
+            })
+            .transpose()?;
+
+        let defaults = Physics::default();
+        let particle_mass = particle_mass.unwrap_or(defaults.particle_mass);
+        let mut physics = if version == 1 {
+            Physics::from_field_units(
+                gravitational_constant.unwrap_or(V1_GRAVITATIONAL_CONSTANT),
+                particle_mass,
+                softening.unwrap_or(V1_SOFTENING),
+            )
+        } else {
+            Physics {
+                gravitational_constant: match &units {
+                    Some(units) => units.gravitational_constant() as f32,
+                    None => gravitational_constant.unwrap_or(defaults.gravitational_constant),
+                },
+                particle_mass,
+                softening: softening.unwrap_or(defaults.softening),
+                ..defaults
+            }
+        };
+        physics.kernel = file.physics.softening_kernel.unwrap_or(defaults.kernel);
+        physics.adaptive_softening = file
+            .physics
+            .adaptive_softening
+            .unwrap_or(defaults.adaptive_softening);
+
+        let dt = checker.positive_opt(&file.physics.dt, "physics.dt")?;
+        let eta = checker.positive_opt(&file.integrator.eta, "integrator.eta")?;
+        let block_levels = file
+            .integrator
+            .block_levels
+            .as_ref()
+            .map(|levels| {
+                checker.check(levels, "integrator.block_levels", |v| {
+                    (1..=globals::MAX_BLOCK_LEVELS)
+                        .contains(v)
+                        .then_some(*v)
+                        .ok_or_else(|| {
+                            format!("must be between 1 and {}", globals::MAX_BLOCK_LEVELS)
+                        })
+                })
+            })
+            .transpose()?;
+        let theta = checker.positive_opt(&file.solver.theta, "solver.theta")?;
+        let grid_size = checker.positive_opt(&file.solver.grid_size, "solver.grid_size")?;
+
+        // Whether the resolution fits together is checked with the rest of
+        // the run, once the command line options are merged in.
+        let mass_field_size = checker.positive_opt(&file.mass_field.size, "mass_field.size")?;
+        let mass_field_downsampling =
+            checker.positive_opt(&file.mass_field.downsampling, "mass_field.downsampling")?;
+        let mass_field_levels =
+            checker.positive_opt(&file.mass_field.levels, "mass_field.levels")?;
+        let mass_field_reach = checker.positive_opt(&file.mass_field.reach, "mass_field.reach")?;
+        let refine_density =
+            checker.positive_opt(&file.mass_field.refine_density, "mass_field.refine_density")?;
+        let patch_size =
+            checker.positive_opt(&file.mass_field.patch_size, "mass_field.patch_size")?;
+        let refine_factor =
+            checker.positive_opt(&file.mass_field.refine_factor, "mass_field.refine_factor")?;
+        let max_patches =
+            checker.positive_opt(&file.mass_field.max_patches, "mass_field.max_patches")?;
+
+        let steps = checker.positive_opt(&file.output.steps, "output.steps")?;
+        let report_every =
+            checker.positive_opt(&file.output.report_every, "output.report_every")?;
+        let snapshot_every =
+            checker.positive_opt(&file.output.snapshot_every, "output.snapshot_every")?;
+
+        if let Some(populations) = file.populations.as_ref().filter(|p| p.get_ref().is_empty()) {
+            return Err(checker.error(
+                populations.span(),
+                "populations: at least one population is required",
+            ));
+        }
+
+        let species = file
+            .species
+            .iter()
+            .enumerate()
+            .map(|(i, s)| checker.species(s, &file.species[..i]))
+            .collect::<Result<Vec<_>>>()?;
+
+        let populations = file
+            .populations
+            .iter()
+            .flat_map(|p| p.get_ref())
+            .map(|p| checker.population(p, &species))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            populations,
+            species,
+            physics,
+            boundary: file.physics.boundary,
+            units,
+            seed: file.seed,
+            solver: file.solver.kind,
+            integrator: file.integrator.kind,
+            adaptive: file.integrator.adaptive,
+            eta,
+            block_levels,
+            theta,
+            grid_size,
+            domain: file
+                .mass_field
+                .domain
+                .or((version < 3).then_some(DomainMode::Fixed)),
+            multipoles: file
+                .mass_field
+                .multipoles
+                .or((version < 3).then_some(Multipoles::Monopole)),
+            mass_field_size,
+            mass_field_downsampling,
+            mass_field_levels,
+            mass_field_reach,
+            refine_density,
+            patch_size,
+            refine_factor,
+            max_patches,
+            dt,
+            steps,
+            report_every,
+            snapshot_every,
+            snapshot_dir: file.output.snapshot_dir,
+        })
+    }
+}
+
+struct Checker<'a> {
+    path: &'a Path,
+    source: &'a str,
+}
+
+impl Checker<'_> {
+    fn error(&self, span: Range<usize>, message: &str) -> anyhow::Error {
+        let before = &self.source[..span.start.min(self.source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+
+        anyhow!("{}:{}:{}: {}", self.path.display(), line, column, message)
+    }
+
+    fn check<T, R>(
+        &self,
+        value: &Spanned<T>,
+        name: &str,
+        f: impl FnOnce(&T) -> std::result::Result<R, String>,
+    ) -> Result<R> {
+        f(value.get_ref())
+            .map_err(|message| self.error(value.span(), &format!("{} {}", name, message)))
+    }
+
+    fn positive<T: PartialOrd + Default + Copy + IsFinite>(
+        &self,
+        value: &Spanned<T>,
+        name: &str,
+    ) -> Result<T> {
+        self.check(value, name, |v| {
+            (v.is_finite() && *v > T::default())
+                .then_some(*v)
+                .ok_or_else(|| "must be greater than zero".to_string())
+        })
+    }
+
+    fn positive_opt<T: PartialOrd + Default + Copy + IsFinite>(
+        &self,
+        value: &Option<Spanned<T>>,
+        name: &str,
+    ) -> Result<Option<T>> {
+        value.as_ref().map(|v| self.positive(v, name)).transpose()
+    }
+
+    fn units(&self, section: &Spanned<UnitsSection>, version: u32) -> Result<Units> {
+        if version == 1 {
+            return Err(self.error(section.span(), "units: need version 2"));
+        }
+
+        let section = section.get_ref();
+        let unit = |value: &Spanned<String>, name: &str, dimension: Dimension| {
+            self.check(value, name, |v| Unit::parse(v, dimension))
+        };
+
+        let units = Units {
+            length: unit(&section.length, "units.length", Dimension::Length)?,
+            mass: unit(&section.mass, "units.mass", Dimension::Mass)?,
+            time: unit(&section.time, "units.time", Dimension::Time)?,
+        };
+
+        let constant = units.gravitational_constant() as f32;
+        if !(constant.is_normal() && constant.is_finite()) {
+            return Err(self.error(
+                section.length.span(),
+                &format!(
+                    "units: the gravitational constant of {:e} does not fit a f32",
+                    units.gravitational_constant()
+                ),
+            ));
+        }
+
+        Ok(units)
+    }
+
+    /// Checks a species against the ones defined before it.
+    fn species(&self, section: &SpeciesSection, before: &[SpeciesSection]) -> Result<Species> {
+        if before.len() + 1 >= globals::MAX_SPECIES {
+            return Err(self.error(
+                section.name.span(),
+                &format!(
+                    "species: at most {} species can be defined",
+                    globals::MAX_SPECIES - 1
+                ),
+            ));
+        }
+
+        let name = self.check(&section.name, "species.name", |v| {
+            if v.is_empty() {
+                Err("must not be empty".to_string())
+            } else if before.iter().any(|s| s.name.get_ref() == v) {
+                Err(format!("{:?} is defined twice", v))
+            } else {
+                Ok(v.clone())
+            }
+        })?;
+
+        let color = section
+            .color
+            .as_ref()
+            .map(|color| {
+                self.check(color, "species.color", |v| {
+                    v.iter()
+                        .all(|c| (0.0..=1.0).contains(c))
+                        .then_some(*v)
+                        .ok_or_else(|| "components must be between 0 and 1".to_string())
+                })
+            })
+            .transpose()?;
+
+        Ok(Species {
+            name,
+            mass: self.positive(&section.mass, "species.mass")?,
+            color,
+        })
+    }
+
+    fn population(&self, section: &PopulationSection, species: &[Species]) -> Result<Population> {
+        let defaults = Population::default();
+
+        let count = self.positive(&section.count, "populations.count")?;
+        let radius = self
+            .positive_opt(&section.radius, "populations.radius")?
+            .unwrap_or(defaults.radius);
+        let thickness = self
+            .positive_opt(&section.thickness, "populations.thickness")?
+            .unwrap_or(defaults.thickness);
+
+        let angular_velocity = match &section.angular_velocity {
+            Some(value) => self.check(value, "populations.angular_velocity", |v| {
+                v.is_finite()
+                    .then_some(*v)
+                    .ok_or_else(|| "must be finite".to_string())
+            })?,
+            None => defaults.angular_velocity,
+        };
+
+        let vector = |value: &Option<Spanned<[f32; 2]>>, name: &str| match value {
+            Some(value) => self.check(value, name, |v| {
+                v.iter()
+                    .all(|c| c.is_finite())
+                    .then_some(vec2(v[0], v[1]))
+                    .ok_or_else(|| "must be finite".to_string())
+            }),
+            None => Ok(vec2(0.0, 0.0)),
+        };
+
+        Ok(Population {
+            generator: section.generator,
+            count,
+            radius,
+            thickness,
+            angular_velocity,
+            offset: vector(&section.offset, "populations.offset")?,
+            velocity: vector(&section.velocity, "populations.velocity")?,
+            species: match &section.species {
+                Some(name) => self.check(name, "populations.species", |v| {
+                    species
+                        .iter()
+                        .position(|s| &s.name == v)
+                        .map(|i| i as u32 + 1)
+                        .ok_or_else(|| format!("{:?} is not defined", v))
+                })?,
+                None => 0,
+            },
+        })
+    }
+}
+
+trait IsFinite {
+    fn is_finite(&self) -> bool;
+}
+
+impl IsFinite for f32 {
+    fn is_finite(&self) -> bool {
+        f32::is_finite(*self)
+    }
+}
+
+impl IsFinite for u32 {
+    fn is_finite(&self) -> bool {
+        true
+    }
+}
+
+impl IsFinite for u64 {
+    fn is_finite(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Scenario> {
+        Scenario::parse(Path::new("test.toml"), source)
+    }
+
+    fn error(source: &str) -> String {
+        parse(source).unwrap_err().to_string()
+    }
+
+    const POPULATION: &str = "[[populations]]\ngenerator = \"disk\"\ncount = 10\n";
+
+    #[test]
+    fn errors_point_at_the_value() {
+        let source = format!(
+            "version = 3\n\n[physics]\nsoftening = -1.0\n\n{}",
+            POPULATION
+        );
+        assert_eq!(
+            error(&source),
+            "test.toml:4:13: physics.softening must not be negative"
+        );
+
+        let source = format!("version = 3\n\n[solver]\nkind = \"fast\"\n\n{}", POPULATION);
+        assert!(error(&source).starts_with("test.toml:4:8: "));
+
+        let source = format!("version = 3\ncolour = 1\n{}", POPULATION);
+        assert!(error(&source).starts_with("test.toml:2:1: "));
+    }
+
+    #[test]
+    fn checks_the_ranges() {
+        for (source, message) in [
+            (
+                "version = 0",
+                "test.toml:1:11: version must be between 1 and 3",
+            ),
+            (
+                "version = 4",
+                "test.toml:1:11: version must be between 1 and 3",
+            ),
+            (
+                "version = 3\n[integrator]\nblock_levels = 0",
+                "test.toml:3:16: integrator.block_levels must be between 1 and 12",
+            ),
+            (
+                "version = 3\n[solver]\ntheta = 0.0",
+                "test.toml:3:9: solver.theta must be greater than zero",
+            ),
+            (
+                "version = 3\n[output]\nsnapshot_every = 0",
+                "test.toml:3:18: output.snapshot_every must be greater than zero",
+            ),
+            (
+                "version = 3\n[[species]]\nname = \"a\"\nmass = 1.0\ncolor = [2.0, 0.0, 0.0, 1.0]",
+                "test.toml:5:9: species.color components must be between 0 and 1",
+            ),
+        ] {
+            assert_eq!(error(&format!("{}\n{}", source, POPULATION)), message);
+        }
+
+        assert_eq!(
+            error("version = 3\npopulations = []"),
+            "test.toml:2:15: populations: at least one population is required"
+        );
+        assert_eq!(
+            error(&format!("version = 3\n{}species = \"stars\"", POPULATION)),
+            "test.toml:5:11: populations.species \"stars\" is not defined"
+        );
+    }
+
+    #[test]
+    fn populations_are_optional() {
+        let scenario = parse("version = 3").unwrap();
+        assert!(scenario.populations.is_empty());
+    }
+
+    #[test]
+    fn reads_the_current_version() {
+        let source = format!(
+            "version = 3\n\n[units]\nlength = \"10 kpc\"\nmass = \"1e10 Msun\"\ntime = \"Gyr\"\n\n\
+             [physics]\nsoftening = 0.02\n\n[[species]]\nname = \"stars\"\nmass = 0.5\n\n\
+             {}species = \"stars\"\n",
+            POPULATION
+        );
+        let scenario = parse(&source).unwrap();
+
+        let units = scenario.units.unwrap();
+        assert_eq!(
+            scenario.physics.gravitational_constant,
+            units.gravitational_constant() as f32
+        );
+        assert_eq!(scenario.physics.softening, 0.02);
+        assert_eq!(scenario.domain, None);
+        assert_eq!(scenario.multipoles, None);
+        assert_eq!(scenario.species[0].mass, 0.5);
+        assert_eq!(scenario.populations[0].count, 10);
+        assert_eq!(scenario.populations[0].species, 1);
+    }
+
+    #[test]
+    fn older_versions_keep_their_defaults() {
+        let scenario = parse(&format!("version = 2\n{}", POPULATION)).unwrap();
+        assert_eq!(scenario.physics, Physics::default());
+        assert_eq!(scenario.domain, Some(DomainMode::Fixed));
+        assert_eq!(scenario.multipoles, Some(Multipoles::Monopole));
+
+        let source = format!(
+            "version = 2\n[mass_field]\ndomain = \"percentile\"\nmultipoles = \"quadrupole\"\n{}",
+            POPULATION
+        );
+        let scenario = parse(&source).unwrap();
+        assert_eq!(scenario.domain, Some(DomainMode::Percentile));
+        assert_eq!(scenario.multipoles, Some(Multipoles::Quadrupole));
+    }
+
+    #[test]
+    fn converts_version_1_field_units() {
+        let scenario = parse(&format!("version = 1\n{}", POPULATION)).unwrap();
+        let defaults = Physics::default();
+        assert_eq!(
+            scenario.physics,
+            Physics::from_field_units(
+                V1_GRAVITATIONAL_CONSTANT,
+                defaults.particle_mass,
+                V1_SOFTENING
+            )
+        );
+
+        let source = format!(
+            "version = 1\n[physics]\ngravitational_constant = 0.002\nparticle_mass = 0.5\nsoftening = 0.1\n{}",
+            POPULATION
+        );
+        let scenario = parse(&source).unwrap();
+        assert_eq!(scenario.physics, Physics::from_field_units(0.002, 0.5, 0.1));
+
+        let source = "version = 1\n[units]\nlength = \"kpc\"\nmass = \"Msun\"\ntime = \"Myr\"";
+        assert_eq!(error(source), "test.toml:2:1: units: need version 2");
+    }
+}
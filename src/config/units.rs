@@ -0,0 +1,128 @@
+//! Unit systems of scenarios. A length unit is one world unit, so the
+//! particles start within -1 to 1 of them, and masses and times are given in
+//! the mass and time units. The gravitational constant follows from the
+//! three units.
+
+use std::fmt;
+
+/// Newtonian constant of gravitation in m^3 / (kg s^2).
+pub const GRAVITATIONAL_CONSTANT_SI: f64 = 6.674_30e-11;
+
+const LENGTHS: &[(&str, f64)] = &[
+    ("m", 1.0),
+    ("km", 1e3),
+    ("AU", 1.495_978_707e11),
+    ("ly", 9.460_730_472_580_8e15),
+    ("pc", 3.085_677_581_491_367e16),
+    ("kpc", 3.085_677_581_491_367e19),
+    ("Mpc", 3.085_677_581_491_367e22),
+];
+
+const MASSES: &[(&str, f64)] = &[
+    ("kg", 1.0),
+    ("Mearth", 5.972_2e24),
+    ("Mjup", 1.898_13e27),
+    ("Msun", 1.988_47e30),
+];
+
+const TIMES: &[(&str, f64)] = &[
+    ("s", 1.0),
+    ("day", 86_400.0),
+    ("yr", 3.155_76e7),
+    ("kyr", 3.155_76e10),
+    ("Myr", 3.155_76e13),
+    ("Gyr", 3.155_76e16),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dimension {
+    Length,
+    Mass,
+    Time,
+}
+
+impl Dimension {
+    fn units(self) -> &'static [(&'static str, f64)] {
+        match self {
+            Dimension::Length => LENGTHS,
+            Dimension::Mass => MASSES,
+            Dimension::Time => TIMES,
+        }
+    }
+}
+
+/// A unit as written in the scenario, e.g. `kpc` or `1e10 Msun`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Unit {
+    pub name: String,
+    /// Size of the unit in SI units.
+    pub si: f64,
+}
+
+impl Unit {
+    /// Parses a unit symbol of `dimension`, optionally preceded by a factor.
+    pub fn parse(text: &str, dimension: Dimension) -> Result<Self, String> {
+        let mut parts = text.split_whitespace();
+        let (factor, symbol) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(symbol), None, None) => (1.0, symbol),
+            (Some(factor), Some(symbol), None) => (
+                factor
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|f| f.is_finite() && *f > 0.0)
+                    .ok_or_else(|| format!("{:?} is not a positive factor", factor))?,
+                symbol,
+            ),
+            _ => return Err("must be a unit with an optional factor, e.g. \"1e10 Msun\"".into()),
+        };
+
+        let units = dimension.units();
+        let si = units
+            .iter()
+            .find(|(name, _)| *name == symbol)
+            .map(|(_, si)| factor * si)
+            .ok_or_else(|| {
+                let names = units.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                format!("{:?} is not one of {}", symbol, names.join(", "))
+            })?;
+
+        Ok(Self {
+            name: text.split_whitespace().collect::<Vec<_>>().join(" "),
+            si,
+        })
+    }
+}
+
+/// The units of a run. Runs without them use simulation units that only
+/// have a meaning relative to each other.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Units {
+    pub length: Unit,
+    pub mass: Unit,
+    pub time: Unit,
+}
+
+impl Units {
+    /// The gravitational constant in these units.
+    pub fn gravitational_constant(&self) -> f64 {
+        GRAVITATIONAL_CONSTANT_SI * self.mass.si * self.time.si.powi(2) / self.length.si.powi(3)
+    }
+}
+
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} / {} / {}",
+            self.length.name, self.mass.name, self.time.name
+        )
+    }
+}
+
+/// `time` for the log, followed by the time unit if the run has units.
+pub fn format_time(time: f64, units: Option<&Units>) -> String {
+    match units {
+        Some(units) => format!("{:.3} {}", time, units.time.name),
+        None => format!("{:.3}", time),
+    }
+}
@@ -0,0 +1,343 @@
+//! Barnes–Hut quadtree solver. The tree covers the bounding square of all
+//! particles, so unlike the mass field no mass is ignored, and a node is
+//! treated as a point mass once its size seen from the particle is below
+//! the opening angle `theta`.
+
+use cgmath::{vec2, InnerSpace};
+use rayon::prelude::*;
+
+use crate::config::Physics;
+use crate::cpu::mass_field::normalize_position;
+use crate::cpu::softening;
+use crate::cpu::CpuSolver;
+use crate::data::vertex::Vertex;
+use crate::integrators::Update;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Nodes with at most this many particles are not split further.
+const LEAF_SIZE: usize = 8;
+/// Limits the depth for particles sitting on top of each other.
+const MAX_DEPTH: u32 = 32;
+
+#[derive(Clone, Debug)]
+struct Node {
+    size: f32,
+    mass: f32,
+    center_of_mass: Vec2,
+    /// Index of the first of four consecutive children, 0 for leaves.
+    children: u32,
+    /// Range of the particles of a leaf in `QuadTree::indices`.
+    start: u32,
+    end: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct QuadTree {
+    nodes: Vec<Node>,
+    indices: Vec<u32>,
+    positions: Vec<Vec2>,
+    masses: Vec<f32>,
+}
+
+impl QuadTree {
+    /// Builds the tree over the particles with finite positions, in the
+    /// normalized field units used by the other solvers.
+    pub fn build(particles: &[Vertex]) -> Self {
+        let positions = particles
+            .iter()
+            .map(|p| normalize_position(p.pos))
+            .collect::<Vec<_>>();
+
+        let mut indices = (0..positions.len() as u32)
+            .filter(|&i| is_finite(positions[i as usize]))
+            .collect::<Vec<_>>();
+
+        let (min, max) = indices.iter().fold(
+            (vec2(f32::MAX, f32::MAX), vec2(f32::MIN, f32::MIN)),
+            |(min, max), &i| {
+                let p = positions[i as usize];
+                (
+                    vec2(min.x.min(p.x), min.y.min(p.y)),
+                    vec2(max.x.max(p.x), max.y.max(p.y)),
+                )
+            },
+        );
+
+        let size = (max.x - min.x).max(max.y - min.y).max(f32::EPSILON);
+        let center = (min + max) * 0.5;
+
+        let mut tree = Self {
+            nodes: vec![],
+            indices: vec![],
+            positions,
+            masses: particles.iter().map(|p| p.mass).collect(),
+        };
+
+        let count = indices.len();
+        tree.nodes.push(Node {
+            size,
+            mass: 0.0,
+            center_of_mass: vec2(0.0, 0.0),
+            children: 0,
+            start: 0,
+            end: 0,
+        });
+        tree.build_node(0, &mut indices, 0, count, center, size, 0);
+        tree.indices = indices;
+
+        tree
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_node(
+        &mut self,
+        node: usize,
+        indices: &mut [u32],
+        start: usize,
+        end: usize,
+        center: Vec2,
+        size: f32,
+        depth: u32,
+    ) {
+        let particles = &indices[start..end];
+        let (mass, moment) = particles
+            .iter()
+            .fold((0.0, vec2(0.0, 0.0)), |(mass, moment), &i| {
+                let m = self.masses[i as usize];
+                (mass + m, moment + self.positions[i as usize] * m)
+            });
+
+        let count = particles.len();
+        self.nodes[node].mass = mass;
+        self.nodes[node].center_of_mass = if mass > 0.0 { moment / mass } else { center };
+        self.nodes[node].start = start as u32;
+        self.nodes[node].end = end as u32;
+
+        if count <= LEAF_SIZE || depth >= MAX_DEPTH {
+            return;
+        }
+
+        // Orders the particles by quadrant: bit 0 is right, bit 1 is top.
+        let positions = &self.positions;
+        let quadrant = |i: &u32| {
+            let p = positions[*i as usize];
+            (p.x >= center.x) as usize | (((p.y >= center.y) as usize) << 1)
+        };
+        indices[start..end].sort_unstable_by_key(quadrant);
+
+        let mut bounds = [start; 5];
+        for q in 0..4 {
+            bounds[q + 1] = start + indices[start..end].partition_point(|i| quadrant(i) <= q);
+        }
+
+        let first_child = self.nodes.len();
+        self.nodes[node].children = first_child as u32;
+
+        for _ in 0..4 {
+            self.nodes.push(Node {
+                size: size * 0.5,
+                mass: 0.0,
+                center_of_mass: vec2(0.0, 0.0),
+                children: 0,
+                start: 0,
+                end: 0,
+            });
+        }
+
+        for q in 0..4 {
+            let offset = vec2(
+                if q & 1 == 1 { 0.25 } else { -0.25 },
+                if q & 2 == 2 { 0.25 } else { -0.25 },
+            ) * size;
+
+            self.build_node(
+                first_child + q,
+                indices,
+                bounds[q],
+                bounds[q + 1],
+                center + offset,
+                size * 0.5,
+                depth + 1,
+            );
+        }
+    }
+
+    /// Acceleration of particle `index` with the force law of
+    /// `gravity.comp`. `None` for particles that are not in the tree.
+    pub fn acceleration(&self, index: usize, physics: &Physics, theta: f32) -> Option<Vec2> {
+        let pos = self.positions[index];
+        if !is_finite(pos) {
+            return None;
+        }
+
+        let strength = physics.field_gravitational_constant();
+        let own_softening = physics.field_softening_of(self.masses[index]);
+        let mut force = vec2(0.0, 0.0);
+        let mut stack = vec![0u32];
+
+        // Nodes are softened like the particle, particles with the larger
+        // softening length of the pair.
+        let mut attract = |mass: f32, center: Vec2, softening: f32| {
+            let d = (center - pos).magnitude();
+            if d > 0.0 {
+                let g = softening::force(physics.kernel, d as f64, softening as f64) as f32;
+                force += (center - pos) * (strength * mass * g);
+            }
+        };
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node as usize];
+            if node.mass == 0.0 {
+                continue;
+            }
+
+            let d = (node.center_of_mass - pos).magnitude();
+
+            if node.children == 0 {
+                for &i in &self.indices[node.start as usize..node.end as usize] {
+                    if i as usize != index {
+                        let mass = self.masses[i as usize];
+                        attract(
+                            mass,
+                            self.positions[i as usize],
+                            own_softening.max(physics.field_softening_of(mass)),
+                        );
+                    }
+                }
+            } else if node.size < theta * d {
+                attract(node.mass, node.center_of_mass, own_softening);
+            } else {
+                stack.extend(node.children..node.children + 4);
+            }
+        }
+
+        Some(force)
+    }
+
+    /// Applies `update` from `read` into `write`. Particles outside the
+    /// tree are copied unchanged.
+    pub fn integrate(
+        &self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        physics: &Physics,
+        theta: f32,
+        update: &Update,
+        delta_t: f32,
+    ) {
+        write
+            .par_iter_mut()
+            .zip(read.par_iter())
+            .enumerate()
+            .for_each(|(i, (out, particle))| {
+                *out = *particle;
+
+                if let Some(force) = self.acceleration(i, physics, theta) {
+                    *out = update.apply(particle, force, delta_t);
+                }
+            });
+    }
+}
+
+/// Rebuilds the tree for every step.
+#[derive(Clone, Debug)]
+pub struct BarnesHut {
+    theta: f32,
+}
+
+impl BarnesHut {
+    pub fn new(theta: f32) -> Self {
+        Self { theta }
+    }
+}
+
+impl CpuSolver for BarnesHut {
+    fn step(
+        &mut self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        physics: &Physics,
+        update: &Update,
+        delta_t: f32,
+    ) {
+        QuadTree::build(read).integrate(read, write, physics, self.theta, update, delta_t);
+    }
+
+    fn accelerations(
+        &mut self,
+        particles: &[Vertex],
+        targets: &[u32],
+        physics: &Physics,
+    ) -> Vec<Option<Vec2>> {
+        let tree = QuadTree::build(particles);
+        targets
+            .iter()
+            .map(|&i| tree.acceleration(i as usize, physics, self.theta))
+            .collect()
+    }
+}
+
+fn is_finite(v: Vec2) -> bool {
+    v.x.is_finite() && v.y.is_finite()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::cpu::direct;
+
+    fn random_particles(count: usize, seed: u64) -> Vec<Vertex> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| {
+                let pos = vec2(rng.gen_range(-0.8..0.8), rng.gen_range(-0.8..0.8));
+                let mass = 0.03 * rng.gen_range(1..4) as f32;
+                Vertex::new(pos, vec2(0.0, 0.0)).with_species(0, mass)
+            })
+            .collect()
+    }
+
+    /// Largest error relative to the largest direct acceleration.
+    fn max_error(particles: &[Vertex], physics: &Physics, theta: f32) -> f32 {
+        let targets = (0..particles.len() as u32).collect::<Vec<_>>();
+        let expected = direct::accelerations(particles, &targets, physics);
+        let scale = expected.iter().map(|a| a.magnitude()).fold(0.0, f32::max);
+
+        let tree = QuadTree::build(particles);
+        (0..particles.len())
+            .map(|i| {
+                let a = tree.acceleration(i, physics, theta).unwrap();
+                (a - expected[i]).magnitude() / scale
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn matches_direct_summation_at_small_theta() {
+        let physics = Physics::default();
+        let particles = random_particles(2000, 1);
+
+        let small = max_error(&particles, &physics, 0.05);
+        assert!(small < 1e-4, "theta 0.05: error {}", small);
+
+        // Opening fewer nodes only loses accuracy.
+        let medium = max_error(&particles, &physics, 0.3);
+        let large = max_error(&particles, &physics, 0.7);
+        assert!(small < medium && medium < large, "{} {}", medium, large);
+    }
+
+    #[test]
+    fn skips_particles_outside_of_the_tree() {
+        let physics = Physics::default();
+        let mut particles = random_particles(100, 2);
+        particles[3].pos = vec2(f32::NAN, 0.0);
+
+        let tree = QuadTree::build(&particles);
+        assert!(tree.acceleration(3, &physics, 0.5).is_none());
+        assert!(tree.acceleration(4, &physics, 0.5).unwrap().x.is_finite());
+    }
+}
@@ -0,0 +1,144 @@
+//! Boundary conditions of the world square from -1 to 1, applied to every
+//! particle after it moved like `boundary.glsl` does in the compute passes.
+//!
+//! Absorbed particles get non-finite positions, which every pass skips, so
+//! the buffers keep their size and the removed particles can be counted.
+
+use cgmath::vec2;
+use rayon::prelude::*;
+
+use crate::config::Boundary;
+use crate::data::vertex::Vertex;
+
+/// Puts a particle that left the square back into it, or removes it.
+pub fn apply(boundary: Boundary, particle: &mut Vertex) {
+    match boundary {
+        Boundary::Open => {}
+        Boundary::Periodic => {
+            particle.pos = vec2(wrap(particle.pos.x), wrap(particle.pos.y));
+        }
+        Boundary::Reflecting => {
+            for axis in 0..2 {
+                let (pos, mirrored) = reflect(particle.pos[axis]);
+                particle.pos[axis] = pos;
+                if mirrored {
+                    particle.velocity[axis] = -particle.velocity[axis];
+                }
+            }
+        }
+        Boundary::Absorbing => {
+            if particle.pos.x.abs() > 1.0 || particle.pos.y.abs() > 1.0 {
+                particle.pos = vec2(f32::NAN, f32::NAN);
+            }
+        }
+    }
+}
+
+/// Applies the boundary to all `particles`.
+pub fn apply_all(boundary: Boundary, particles: &mut [Vertex]) {
+    if boundary != Boundary::Open {
+        particles
+            .par_iter_mut()
+            .for_each(|particle| apply(boundary, particle));
+    }
+}
+
+/// A displacement along one axis in normalized units, the square being one
+/// long, to the nearest periodic image.
+pub fn minimum_image(boundary: Boundary, d: f64) -> f64 {
+    match boundary {
+        Boundary::Periodic => d - (d + 0.5).floor(),
+        _ => d,
+    }
+}
+
+/// Particles removed by the absorbing boundary, or lost otherwise.
+pub fn removed(particles: &[Vertex]) -> usize {
+    particles
+        .iter()
+        .filter(|p| !p.pos.x.is_finite() || !p.pos.y.is_finite())
+        .count()
+}
+
+fn wrap(x: f32) -> f32 {
+    (x + 1.0).rem_euclid(2.0) - 1.0
+}
+
+/// Folds a coordinate back into the square, with whether it ends up
+/// mirrored after crossing the walls an odd number of times.
+fn reflect(x: f32) -> (f32, bool) {
+    let folded = (x + 1.0).rem_euclid(4.0);
+    if folded > 2.0 {
+        (3.0 - folded, true)
+    } else {
+        (folded - 1.0, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moved(boundary: Boundary, x: f32, y: f32) -> Vertex {
+        let mut particle = Vertex::new(vec2(x, y), vec2(1.0, -2.0));
+        apply(boundary, &mut particle);
+        particle
+    }
+
+    #[test]
+    fn open_keeps_the_particles() {
+        let particle = moved(Boundary::Open, 3.5, -1.25);
+        assert_eq!(particle.pos, vec2(3.5, -1.25));
+        assert_eq!(particle.velocity, vec2(1.0, -2.0));
+    }
+
+    #[test]
+    fn periodic_wraps_to_the_other_side() {
+        for (x, wrapped) in [(0.5, 0.5), (1.25, -0.75), (-1.25, 0.75), (3.5, -0.5)] {
+            let particle = moved(Boundary::Periodic, x, -x);
+            assert_eq!(particle.pos, vec2(wrapped, -wrapped), "{}", x);
+            assert_eq!(particle.velocity, vec2(1.0, -2.0));
+        }
+
+        // Both edges are the same line, kept at -1.
+        let particle = moved(Boundary::Periodic, 1.0, -1.0);
+        assert_eq!(particle.pos, vec2(-1.0, -1.0));
+
+        assert_eq!(minimum_image(Boundary::Periodic, 0.75), -0.25);
+        assert_eq!(minimum_image(Boundary::Periodic, -0.75), 0.25);
+        assert_eq!(minimum_image(Boundary::Periodic, 0.25), 0.25);
+        assert_eq!(minimum_image(Boundary::Open, 0.75), 0.75);
+    }
+
+    #[test]
+    fn reflecting_mirrors_the_position_and_velocity() {
+        let particle = moved(Boundary::Reflecting, 1.25, -0.5);
+        assert_eq!(particle.pos, vec2(0.75, -0.5));
+        assert_eq!(particle.velocity, vec2(-1.0, -2.0));
+
+        let particle = moved(Boundary::Reflecting, 0.5, -1.5);
+        assert_eq!(particle.pos, vec2(0.5, -0.5));
+        assert_eq!(particle.velocity, vec2(1.0, 2.0));
+
+        // Crossing both walls of an axis mirrors it twice.
+        let particle = moved(Boundary::Reflecting, 3.5, 0.0);
+        assert_eq!(particle.pos, vec2(-0.5, 0.0));
+        assert_eq!(particle.velocity, vec2(1.0, -2.0));
+    }
+
+    #[test]
+    fn absorbing_removes_the_particles_outside() {
+        let mut particles = [
+            vec2(0.5, 1.0),
+            vec2(1.25, 0.0),
+            vec2(0.0, -1.5),
+            vec2(-1.0, -1.0),
+        ]
+        .map(|pos| Vertex::new(pos, vec2(0.0, 0.0)));
+        apply_all(Boundary::Absorbing, &mut particles);
+
+        assert_eq!(particles[0].pos, vec2(0.5, 1.0));
+        assert!(particles[1].pos.x.is_nan() && particles[2].pos.y.is_nan());
+        assert_eq!(removed(&particles), 2);
+    }
+}
@@ -0,0 +1,175 @@
+//! Pairwise summation over all particles with the force law of
+//! `gravity.comp`, as the ground truth for the approximate solvers.
+
+use cgmath::vec2;
+use rayon::prelude::*;
+
+use crate::config::Physics;
+use crate::cpu::boundary::minimum_image;
+use crate::cpu::mass_field::normalize_position;
+use crate::cpu::softening;
+use crate::cpu::CpuSolver;
+use crate::data::vertex::Vertex;
+use crate::integrators::Update;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Accelerations of the `targets` particles from every other particle, in
+/// the normalized field units used by the shaders. Sums are accumulated in
+/// double precision. Particles with non-finite positions neither attract
+/// nor get a force. A pair is softened with the larger softening length of
+/// the two particles, and periodic boundaries take the nearest image.
+pub fn accelerations(particles: &[Vertex], targets: &[u32], physics: &Physics) -> Vec<Vec2> {
+    let positions = particles
+        .iter()
+        .map(|p| normalize_position(p.pos))
+        .collect::<Vec<_>>();
+
+    let softenings = softenings(particles, physics);
+    let strength = physics.field_gravitational_constant() as f64;
+
+    targets
+        .par_iter()
+        .map(|&target| {
+            let target = target as usize;
+            let pos = positions[target];
+            if !is_finite(pos) {
+                return vec2(0.0, 0.0);
+            }
+
+            let (x, y) = (pos.x as f64, pos.y as f64);
+            let (mut ax, mut ay) = (0.0, 0.0);
+
+            for (i, other) in positions.iter().enumerate() {
+                if i == target || !is_finite(*other) {
+                    continue;
+                }
+
+                let dx = minimum_image(physics.boundary, other.x as f64 - x);
+                let dy = minimum_image(physics.boundary, other.y as f64 - y);
+                let d = (dx * dx + dy * dy).sqrt();
+                if d == 0.0 {
+                    continue;
+                }
+
+                let softening = softenings[target].max(softenings[i]);
+                let f = strength
+                    * particles[i].mass as f64
+                    * softening::force(physics.kernel, d, softening);
+                ax += dx * f;
+                ay += dy * f;
+            }
+
+            vec2(ax as f32, ay as f32)
+        })
+        .collect()
+}
+
+/// Accelerations and jerks, their time derivatives, of every particle like
+/// `accelerations`. The velocities are halved into the normalized units of
+/// the positions.
+pub fn accelerations_and_jerks(particles: &[Vertex], physics: &Physics) -> Vec<(Vec2, Vec2)> {
+    let softenings = softenings(particles, physics);
+    let strength = physics.field_gravitational_constant() as f64;
+
+    let states = particles
+        .iter()
+        .map(|p| {
+            let pos = normalize_position(p.pos);
+            let vel = p.velocity * 0.5;
+            [pos.x as f64, pos.y as f64, vel.x as f64, vel.y as f64]
+        })
+        .collect::<Vec<_>>();
+
+    states
+        .par_iter()
+        .enumerate()
+        .map(|(target, [x, y, vx, vy])| {
+            let (mut a, mut j) = ([0.0, 0.0], [0.0, 0.0]);
+            if !x.is_finite() || !y.is_finite() {
+                return (vec2(0.0, 0.0), vec2(0.0, 0.0));
+            }
+
+            for (i, [ox, oy, ovx, ovy]) in states.iter().enumerate() {
+                if i == target || !ox.is_finite() || !oy.is_finite() {
+                    continue;
+                }
+
+                let dx = minimum_image(physics.boundary, ox - x);
+                let dy = minimum_image(physics.boundary, oy - y);
+                let (dvx, dvy) = (ovx - vx, ovy - vy);
+                let d = (dx * dx + dy * dy).sqrt();
+                if d == 0.0 {
+                    continue;
+                }
+
+                let softening = softenings[target].max(softenings[i]);
+                let (g, dg) = softening::force_and_derivative(physics.kernel, d, softening);
+                let m = strength * particles[i].mass as f64;
+                let rv = (dx * dvx + dy * dvy) * dg;
+                a[0] += dx * g * m;
+                a[1] += dy * g * m;
+                j[0] += (dvx * g + rv * dx) * m;
+                j[1] += (dvy * g + rv * dy) * m;
+            }
+
+            (
+                vec2(a[0] as f32, a[1] as f32),
+                vec2(j[0] as f32, j[1] as f32),
+            )
+        })
+        .collect()
+}
+
+/// Direct summation as a solver, quadratic in the particle count.
+#[derive(Clone, Copy, Debug)]
+pub struct DirectSummation;
+
+impl CpuSolver for DirectSummation {
+    fn step(
+        &mut self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        physics: &Physics,
+        update: &Update,
+        delta_t: f32,
+    ) {
+        let targets = (0..read.len() as u32).collect::<Vec<_>>();
+        let forces = accelerations(read, &targets, physics);
+
+        write
+            .par_iter_mut()
+            .zip(read.par_iter().zip(forces))
+            .for_each(|(out, (particle, force))| {
+                *out = *particle;
+
+                if is_finite(particle.pos) {
+                    *out = update.apply(particle, force, delta_t);
+                }
+            });
+    }
+
+    fn accelerations(
+        &mut self,
+        particles: &[Vertex],
+        targets: &[u32],
+        physics: &Physics,
+    ) -> Vec<Option<Vec2>> {
+        accelerations(particles, targets, physics)
+            .into_iter()
+            .map(Some)
+            .collect()
+    }
+}
+
+fn is_finite(v: Vec2) -> bool {
+    v.x.is_finite() && v.y.is_finite()
+}
+
+/// Softening length of every particle in normalized field units.
+fn softenings(particles: &[Vertex], physics: &Physics) -> Vec<f64> {
+    particles
+        .iter()
+        .map(|p| physics.field_softening_of(p.mass) as f64)
+        .collect()
+}
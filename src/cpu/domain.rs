@@ -0,0 +1,121 @@
+//! The square covered by the mass field, chosen every step like
+//! `domain.comp` does on the device. It is the bounding box of the live
+//! particles or the box between their percentiles, made square and widened
+//! by `DOMAIN_MARGIN` so the particles on its edge are inside of the field.
+//!
+//! Percentiles are taken from histograms of `DOMAIN_HISTOGRAM_BINS` bins
+//! per axis over the bounding box, so they are only as exact as one bin.
+//! Particles outside of the percentile box are not deposited and feel the
+//! whole mass of the field at its center of mass.
+
+use cgmath::vec2;
+use rayon::prelude::*;
+
+use crate::config::DomainMode;
+use crate::data::globals;
+use crate::data::vertex::Vertex;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Domain {
+    /// Lower corner in world units.
+    pub origin: Vec2,
+    pub size: f32,
+}
+
+impl Domain {
+    /// The world square from -1 to 1.
+    pub const FIXED: Self = Self {
+        origin: vec2(-1.0, -1.0),
+        size: 2.0,
+    };
+
+    /// The domain of `particles` in `mode`.
+    pub fn of(particles: &[Vertex], mode: DomainMode) -> Self {
+        let (count, mut lower, mut upper) = particles
+            .par_iter()
+            .filter(|p| is_live(p))
+            .map(|p| (1_u32, p.pos, p.pos))
+            .reduce(
+                || (0, vec2(f32::MAX, f32::MAX), vec2(f32::MIN, f32::MIN)),
+                |(n, lo, hi), (m, a, b)| {
+                    (
+                        n + m,
+                        vec2(lo.x.min(a.x), lo.y.min(a.y)),
+                        vec2(hi.x.max(b.x), hi.y.max(b.y)),
+                    )
+                },
+            );
+
+        if mode == DomainMode::Fixed || count == 0 {
+            return Self::FIXED;
+        }
+
+        if mode == DomainMode::Percentile {
+            let bins = globals::DOMAIN_HISTOGRAM_BINS;
+            let mut histogram = vec![0_u32; 2 * bins];
+            for particle in particles.iter().filter(|p| is_live(p)) {
+                histogram[bin(particle.pos.x, lower.x, upper.x)] += 1;
+                histogram[bins + bin(particle.pos.y, lower.y, upper.y)] += 1;
+            }
+
+            let tail = (globals::DOMAIN_PERCENTILE * count as f32) as u32;
+            let width = (upper - lower) / bins as f32;
+            for (axis, histogram) in histogram.chunks(bins).enumerate() {
+                let first = percentile_bin(histogram.iter().enumerate(), tail);
+                let last = percentile_bin(histogram.iter().enumerate().rev(), tail);
+                let (lo, w) = (lower[axis], width[axis]);
+                lower[axis] = lo + first as f32 * w;
+                upper[axis] = lo + (last + 1) as f32 * w;
+            }
+        }
+
+        let extent = upper - lower;
+        let size = (extent.x.max(extent.y) * (1.0 + 2.0 * globals::DOMAIN_MARGIN))
+            .max(globals::MIN_DOMAIN_SIZE);
+
+        Self {
+            origin: (lower + upper) * 0.5 - vec2(size, size) * 0.5,
+            size,
+        }
+    }
+
+    /// Maps world coordinates to the [0, 1] range of the mass field.
+    pub fn normalize(&self, pos: Vec2) -> Vec2 {
+        (pos - self.origin) / self.size
+    }
+
+    /// Whether a normalized position is inside of the field.
+    pub fn contains(normalized: Vec2) -> bool {
+        normalized.x > 0.0 && normalized.y > 0.0 && normalized.y < 1.0 && normalized.x < 1.0
+    }
+}
+
+/// Whether a particle takes part in the mass field. Particles at the origin
+/// are skipped like in the first versions of the shaders.
+pub fn is_live(particle: &Vertex) -> bool {
+    particle.pos.x.is_finite() && particle.pos.y.is_finite() && particle.pos != vec2(0.0, 0.0)
+}
+
+/// Histogram bin of `x` between `lower` and `upper`.
+fn bin(x: f32, lower: f32, upper: f32) -> usize {
+    if upper > lower {
+        (((x - lower) / (upper - lower) * globals::DOMAIN_HISTOGRAM_BINS as f32) as usize)
+            .min(globals::DOMAIN_HISTOGRAM_BINS - 1)
+    } else {
+        0
+    }
+}
+
+/// First bin of `bins` in their order after more than `tail` particles.
+fn percentile_bin<'a>(bins: impl Iterator<Item = (usize, &'a u32)>, tail: u32) -> usize {
+    let mut seen = 0;
+    for (i, &count) in bins {
+        if seen + count > tail {
+            return i;
+        }
+        seen += count;
+    }
+    0
+}
@@ -0,0 +1,886 @@
+//! CPU version of the mass and gravity compute passes. It follows
+//! `mass.comp` and `gravity.comp` line by line, including which particles
+//! are skipped, so its results can be compared with a device readback.
+//!
+//! The field covers the `Domain` of the particles, which is found again on
+//! every deposit.
+//!
+//! Mass is deposited in 64 bit fixed point like the atomic additions of
+//! `mass.comp`, so the order of the particles does not change the sums and
+//! the total mass of every level is exactly the mass of the deposited
+//! particles, up to the rounding of each particle to the fixed point unit.
+//! Only the finest level is deposited, the coarser ones add up the sums of
+//! the level below them like `reduce.comp`.
+//!
+//! With quadrupoles the pixels also keep the second moments of their mass
+//! about the center of mass, and the forces add the second order of the
+//! expansion of the softened force around it.
+//!
+//! Dense tiles of the finest level can get finer patches, see
+//! `cpu/patches.rs`.
+
+use cgmath::{vec2, vec3, InnerSpace};
+use rayon::prelude::*;
+
+use crate::config::{Boundary, DomainMode, MassFieldConfig, Multipoles, Physics};
+use crate::cpu::boundary::minimum_image;
+use crate::cpu::domain::{self, Domain};
+use crate::cpu::patches::Patches;
+use crate::cpu::softening;
+use crate::cpu::CpuSolver;
+use crate::data::globals;
+use crate::data::vertex::Vertex;
+use crate::integrators::Update;
+use crate::utils::mass_field;
+
+type Vec2 = cgmath::Vector2<f32>;
+type Vec3 = cgmath::Vector3<f32>;
+
+/// Share of the mass of a pixel left over by rounding when the particle in
+/// it is taken out, like in `gravity.comp`.
+const SELF_MASS_TOLERANCE: f32 = 1e-5;
+
+/// Accumulated mass of one pixel and its center of mass relative to the
+/// pixel corner, in pixels. The second moments about the center of mass
+/// per mass are `xx`, `xy` and `yy` in pixels squared, zero without
+/// quadrupoles.
+#[derive(Clone, Copy, Debug)]
+pub struct MassCell {
+    pub mass: f32,
+    pub center: Vec2,
+    pub moments: Vec3,
+}
+
+impl MassCell {
+    pub const EMPTY: Self = Self {
+        mass: 0.0,
+        center: vec2(0.0, 0.0),
+        moments: vec3(0.0, 0.0, 0.0),
+    };
+}
+
+/// Fixed point sums of one pixel during the deposit, its mass, mass
+/// weighted position in the pixel and with quadrupoles its second moments,
+/// like the words of `mass.comp`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FixedCell {
+    mass: u64,
+    x: u64,
+    y: u64,
+    xx: u64,
+    xy: u64,
+    yy: u64,
+}
+
+impl FixedCell {
+    /// Adds `mass` fixed point units at `center` in the pixel.
+    pub fn add(&mut self, mass: f32, center: Vec2, quadrupole: bool) {
+        self.mass = self.mass.wrapping_add(fixed_point(mass));
+        let x = (mass * center.x).round_ties_even();
+        self.x = self.x.wrapping_add(fixed_point(x));
+        let y = (mass * center.y).round_ties_even();
+        self.y = self.y.wrapping_add(fixed_point(y));
+
+        if quadrupole {
+            let (x, y) = (center.x, center.y);
+            let xx = (mass * x * x).round_ties_even();
+            self.xx = self.xx.wrapping_add(fixed_point(xx));
+            let xy = (mass * x * y).round_ties_even();
+            self.xy = self.xy.wrapping_add(fixed_point(xy));
+            let yy = (mass * y * y).round_ties_even();
+            self.yy = self.yy.wrapping_add(fixed_point(yy));
+        }
+    }
+
+    /// The mass and center of mass of the sums with units of `quantum`.
+    pub fn resolve(&self, quantum: f32, quadrupole: bool) -> MassCell {
+        let mass = to_f32(self.mass);
+        if mass <= 0.0 {
+            return MassCell::EMPTY;
+        }
+
+        let center = vec2(to_f32(self.x), to_f32(self.y)) / mass;
+        let moments = if !quadrupole {
+            MassCell::EMPTY.moments
+        } else {
+            // Only the rounding of the deposits can leave a variance below
+            // zero once the subtraction is exact
+            vec3(
+                central_moment(self.xx, self.x, self.x, self.mass, mass).max(0.0),
+                central_moment(self.xy, self.x, self.y, self.mass, mass),
+                central_moment(self.yy, self.y, self.y, self.mass, mass).max(0.0),
+            )
+        };
+        MassCell {
+            mass: mass * quantum,
+            center,
+            moments,
+        }
+    }
+
+    pub fn mass(&self) -> u64 {
+        self.mass
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MassLevel {
+    pub size: u32,
+    pub cells: Vec<MassCell>,
+    sums: Vec<FixedCell>,
+}
+
+impl MassLevel {
+    /// Fixed point mass accumulated over all pixels since the last resolve.
+    pub fn accumulated_mass(&self) -> u64 {
+        self.sums
+            .iter()
+            .fold(0, |total, sum| total.wrapping_add(sum.mass))
+    }
+
+    /// Fixed point sums of every pixel since the last resolve.
+    pub fn sums(&self) -> &[FixedCell] {
+        &self.sums
+    }
+
+    fn cell(&self, x: i32, y: i32) -> &MassCell {
+        &self.cells[y as usize * self.size as usize + x as usize]
+    }
+
+    fn sum_mut(&mut self, x: i32, y: i32) -> &mut FixedCell {
+        &mut self.sums[y as usize * self.size as usize + x as usize]
+    }
+}
+
+/// The pyramid of mass images, finest level first.
+#[derive(Clone, Debug)]
+pub struct MassField {
+    pub levels: Vec<MassLevel>,
+    mode: DomainMode,
+    multipoles: Multipoles,
+    /// Pixels on each side of the particle summed on every level.
+    reach: i32,
+    /// Finer pixels in dense tiles of the finest level, if refinement is on.
+    pub patches: Option<Patches>,
+    /// Domain of the latest deposit.
+    pub domain: Domain,
+}
+
+impl MassField {
+    pub fn new(mode: DomainMode, multipoles: Multipoles, config: &MassFieldConfig) -> Self {
+        let levels = mass_field::level_sizes(config)
+            .into_iter()
+            .map(|size| MassLevel {
+                size,
+                cells: vec![MassCell::EMPTY; (size * size) as usize],
+                sums: vec![FixedCell::default(); (size * size) as usize],
+            })
+            .collect();
+
+        Self {
+            levels,
+            mode,
+            multipoles,
+            reach: config.reach as i32,
+            patches: Patches::new(config),
+            domain: Domain::FIXED,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.levels
+            .iter_mut()
+            .for_each(|l| l.cells.fill(MassCell::EMPTY));
+    }
+
+    /// Mass pass: finds the domain of the particles, adds every particle
+    /// inside of it to its pixel on the finest level, builds the coarser
+    /// levels from it and resolves the sums into the mass and center of mass
+    /// of the pixels.
+    pub fn deposit(&mut self, particles: &[Vertex], physics: &Physics) {
+        self.accumulate(particles, physics);
+        self.resolve(physics);
+    }
+
+    /// Finds the domain, adds the particles inside of it to the fixed point
+    /// sums of the finest level and builds the coarser levels from them,
+    /// the deposit pass of `mass.comp` and the passes of `reduce.comp`.
+    /// With refinement the dense tiles get their patches and the particles
+    /// in them are added to the patches, the passes of `refine.comp`.
+    pub fn accumulate(&mut self, particles: &[Vertex], physics: &Physics) {
+        self.accumulate_levels(particles, physics, 1);
+        self.reduce(1);
+
+        if let Some(patches) = &mut self.patches {
+            patches.select(&self.levels[patches.tile_level]);
+
+            let (scale, _) = fixed_point_scale(physics);
+            let quadrupole = self.multipoles == Multipoles::Quadrupole;
+            let size = self.levels[0].size;
+            for particle in particles {
+                let pos = self.domain.normalize(particle.pos);
+                if domain::is_live(particle) && Domain::contains(pos) {
+                    let mass = (particle.mass * scale).round_ties_even();
+                    patches.add(pos, size, mass, quadrupole);
+                }
+            }
+        }
+    }
+
+    /// Finds the domain and adds the particles inside of it to the fixed
+    /// point sums of their pixels on the first `levels` levels, the deposit
+    /// pass of `mass.comp`. Depositing into all of them leaves nothing to
+    /// reduce, as in earlier versions.
+    pub fn accumulate_levels(&mut self, particles: &[Vertex], physics: &Physics, levels: usize) {
+        self.domain = Domain::of(particles, self.mode);
+        let (scale, _) = fixed_point_scale(physics);
+        let quadrupole = self.multipoles == Multipoles::Quadrupole;
+
+        for particle in particles {
+            if !domain::is_live(particle) {
+                continue;
+            }
+
+            let pos = self.domain.normalize(particle.pos);
+            if !Domain::contains(pos) {
+                continue;
+            }
+
+            let mass = (particle.mass * scale).round_ties_even();
+            for level in self.levels.iter_mut().take(levels) {
+                let dims = level.size as f32;
+                let pixel = vec2((pos.x * dims).floor(), (pos.y * dims).floor());
+                let particle_center = pos * dims - pixel;
+
+                level.sum_mut(pixel.x as i32, pixel.y as i32).add(
+                    mass,
+                    particle_center,
+                    quadrupole,
+                );
+            }
+        }
+    }
+
+    /// Builds the sums of the levels from `first` on out of the pixels they
+    /// cover on the level below, moving the mass weighted positions and
+    /// second moments into their own pixels like `reduce.comp`.
+    pub fn reduce(&mut self, first: usize) {
+        let quadrupole = self.multipoles == Multipoles::Quadrupole;
+
+        for i in first.max(1)..self.levels.len() {
+            let (finer, coarser) = self.levels.split_at_mut(i);
+            let child = &finer[i - 1];
+            let parent = &mut coarser[0];
+            let factor = child.size / parent.size;
+            let size = parent.size;
+
+            parent
+                .sums
+                .par_iter_mut()
+                .enumerate()
+                .for_each(|(cell, sum)| {
+                    let (parent_x, parent_y) = (cell as u32 % size, cell as u32 / size);
+                    let mut total = FixedCell::default();
+
+                    for y in 0..factor {
+                        for x in 0..factor {
+                            let pixel =
+                                (parent_y * factor + y) * child.size + parent_x * factor + x;
+                            let c = child.sums[pixel as usize];
+                            let (x, y) = (x as u64, y as u64);
+
+                            // Positions in the child pixel move by its
+                            // offset, in child pixels until the division
+                            total.mass = total.mass.wrapping_add(c.mass);
+                            total.x = total
+                                .x
+                                .wrapping_add(c.x.wrapping_add(c.mass.wrapping_mul(x)));
+                            total.y = total
+                                .y
+                                .wrapping_add(c.y.wrapping_add(c.mass.wrapping_mul(y)));
+
+                            if quadrupole {
+                                let xx =
+                                    c.xx.wrapping_add(c.x.wrapping_mul(2 * x))
+                                        .wrapping_add(c.mass.wrapping_mul(x * x));
+                                let xy =
+                                    c.xy.wrapping_add(c.y.wrapping_mul(x))
+                                        .wrapping_add(c.x.wrapping_mul(y))
+                                        .wrapping_add(c.mass.wrapping_mul(x * y));
+                                let yy =
+                                    c.yy.wrapping_add(c.y.wrapping_mul(2 * y))
+                                        .wrapping_add(c.mass.wrapping_mul(y * y));
+                                total.xx = total.xx.wrapping_add(xx);
+                                total.xy = total.xy.wrapping_add(xy);
+                                total.yy = total.yy.wrapping_add(yy);
+                            }
+                        }
+                    }
+
+                    let factor = factor as u64;
+                    *sum = FixedCell {
+                        mass: total.mass,
+                        x: divide(total.x, factor),
+                        y: divide(total.y, factor),
+                        xx: divide(total.xx, factor * factor),
+                        xy: divide(total.xy, factor * factor),
+                        yy: divide(total.yy, factor * factor),
+                    };
+                });
+        }
+    }
+
+    /// Turns the sums into the mass and center of mass of every pixel and
+    /// clears them, the resolve pass of `mass.comp`.
+    pub fn resolve(&mut self, physics: &Physics) {
+        let (_, quantum) = fixed_point_scale(physics);
+        let quadrupole = self.multipoles == Multipoles::Quadrupole;
+
+        for level in self.levels.iter_mut() {
+            level
+                .cells
+                .par_iter_mut()
+                .zip(level.sums.par_iter_mut())
+                .for_each(|(cell, sum)| {
+                    *cell = sum.resolve(quantum, quadrupole);
+                    *sum = FixedCell::default();
+                });
+        }
+
+        if let Some(patches) = &mut self.patches {
+            patches.resolve(quantum, quadrupole);
+        }
+    }
+
+    /// Gravity pass for a single particle: the acceleration from the
+    /// interaction lists of all levels in world units, or from the whole
+    /// field for particles outside of it. `None` when the shader leaves the
+    /// particle untouched.
+    pub fn acceleration(&self, particle: &Vertex, physics: &Physics) -> Option<Vec2> {
+        if !domain::is_live(particle) {
+            return None;
+        }
+
+        // Distances are measured in the normalized field, the domain size
+        // being its unit length.
+        let size = self.domain.size;
+        let gravitational_constant = physics.gravitational_constant / (size * size);
+        let softening = (physics.softening_of(particle.mass) / size) as f64;
+        let pos = self.domain.normalize(particle.pos);
+        let quadrupole = self.multipoles == Multipoles::Quadrupole;
+        let attract = |mass: f32, mass_center: Vec2, moments: Vec3| {
+            let delta = mass_center - pos;
+            let delta = vec2(
+                minimum_image(physics.boundary, delta.x as f64) as f32,
+                minimum_image(physics.boundary, delta.y as f64) as f32,
+            );
+            let d = delta.magnitude();
+            if d == 0.0 {
+                return vec2(0.0, 0.0);
+            }
+
+            let g = softening::force(physics.kernel, d as f64, softening) as f32;
+            let mut force = delta * (gravitational_constant * mass * g);
+
+            // Second order of the force expanded around the center of mass,
+            // with the moments in normalized units
+            if quadrupole {
+                let (h, k) = softening::derivatives(physics.kernel, d as f64, softening);
+                let (h, k) = (h as f32, k as f32);
+                let spread = vec2(
+                    moments.x * delta.x + moments.y * delta.y,
+                    moments.y * delta.x + moments.z * delta.y,
+                );
+                let radial = 0.5 * (h * (moments.x + moments.z) + k * delta.dot(spread));
+                force += (spread * h + delta * radial) * (gravitational_constant * mass);
+            }
+
+            force
+        };
+
+        if !Domain::contains(pos) {
+            let (mass, center) = self.total_mass();
+            return Some(if mass > 0.0 {
+                attract(mass, center, vec3(0.0, 0.0, 0.0))
+            } else {
+                vec2(0.0, 0.0)
+            });
+        }
+
+        // Every region is counted once, as in the interaction lists of the
+        // fast multipole method. The finest level adds the pixels within
+        // `reach` of the particle, its own pixel without the particle, and
+        // every level adds the children of the pixels around its parent that
+        // are not around the particle. Those around it are covered by the
+        // finer levels. The level below the coarsest one adds all of its
+        // pixels that are not around the particle. Periodic fields wrap
+        // around, with a smaller reach on levels too small to hold the reach
+        // on both sides.
+        let periodic = physics.boundary == Boundary::Periodic;
+        let reach = |dims: i32| {
+            if periodic {
+                self.reach.min((dims - 1) / 2)
+            } else {
+                self.reach
+            }
+        };
+
+        // Force of the pixel at `x`, `y` of a level with `dims` pixels per
+        // side, the pixel of the particle without it.
+        let pixel_force = |cell: MassCell, x: i32, y: i32, dims: i32, own: bool| {
+            let cell = if own {
+                without_particle(
+                    cell,
+                    particle.mass,
+                    pos * dims as f32 - vec2(x as f32, y as f32),
+                )
+            } else {
+                cell
+            };
+
+            if cell.mass == 0.0 {
+                return vec2(0.0, 0.0);
+            }
+
+            let mass_center = vec2(
+                (x as f32 + cell.center.x) / dims as f32,
+                (y as f32 + cell.center.y) / dims as f32,
+            );
+
+            let moments = cell.moments / (dims * dims) as f32;
+            attract(cell.mass, mass_center, moments)
+        };
+
+        // With all pixels within reach of the particle on the finest level
+        // in refined tiles the patch pixels under them take their place.
+        let mut force = vec2(0.0, 0.0);
+        let refined = self.patches.as_ref().is_some_and(|patches| {
+            self.add_patch_forces(patches, pos, periodic, &mut force, &pixel_force)
+        });
+
+        let levels = self.levels.iter().zip(self.levels.iter().skip(1));
+        for (i, (level, parent)) in levels.enumerate() {
+            let dims = level.size as i32;
+            let pixel_x = (pos.x * dims as f32).floor() as i32;
+            let pixel_y = (pos.y * dims as f32).floor() as i32;
+            let near = reach(dims);
+            let offset = |d: i32| {
+                if periodic {
+                    (d + dims / 2).rem_euclid(dims) - dims / 2
+                } else {
+                    d
+                }
+            };
+
+            let parent_dims = parent.size as i32;
+            let factor = dims / parent_dims;
+            let parent_reach = reach(parent_dims);
+            let (start_x, start_y, span) = if i + 2 == self.levels.len() {
+                (0, 0, dims)
+            } else {
+                (
+                    ((pos.x * parent_dims as f32).floor() as i32 - parent_reach) * factor,
+                    ((pos.y * parent_dims as f32).floor() as i32 - parent_reach) * factor,
+                    (2 * parent_reach + 1) * factor,
+                )
+            };
+
+            for x in start_x..start_x + span {
+                for y in start_y..start_y + span {
+                    let is_near =
+                        offset(x - pixel_x).abs() <= near && offset(y - pixel_y).abs() <= near;
+                    if is_near && (i > 0 || refined) {
+                        continue;
+                    }
+
+                    let (mut px, mut py) = (x, y);
+                    if periodic {
+                        (px, py) = ((px + dims) % dims, (py + dims) % dims);
+                    } else if !(px >= 0 && py >= 0 && py < dims && px < dims) {
+                        continue;
+                    }
+
+                    let own = x == pixel_x && y == pixel_y;
+                    force += pixel_force(*level.cell(px, py), px, py, dims, own);
+                }
+            }
+        }
+
+        Some(force)
+    }
+
+    /// Adds the forces of the patch pixels under the pixels within reach of
+    /// the particle on the finest level to `force`, if all of those are in
+    /// refined tiles. The window is at most two tiles wide, so its corners
+    /// are in all of its tiles.
+    fn add_patch_forces(
+        &self,
+        patches: &Patches,
+        pos: Vec2,
+        periodic: bool,
+        force: &mut Vec2,
+        pixel_force: &impl Fn(MassCell, i32, i32, i32, bool) -> Vec2,
+    ) -> bool {
+        let dims = self.levels[0].size as i32;
+        let near = if periodic {
+            self.reach.min((dims - 1) / 2)
+        } else {
+            self.reach
+        };
+        let pixel_x = (pos.x * dims as f32).floor() as i32;
+        let pixel_y = (pos.y * dims as f32).floor() as i32;
+        let wrap = |p: i32| {
+            if periodic {
+                p.rem_euclid(dims)
+            } else {
+                p.clamp(0, dims - 1)
+            }
+        };
+
+        let corners = [
+            (pixel_x - near, pixel_y - near),
+            (pixel_x + near, pixel_y - near),
+            (pixel_x - near, pixel_y + near),
+            (pixel_x + near, pixel_y + near),
+        ];
+        if !corners
+            .iter()
+            .all(|&(x, y)| patches.slot(wrap(x), wrap(y)).is_some())
+        {
+            return false;
+        }
+
+        let factor = patches.factor as i32;
+        let patch_dims = dims * factor;
+        let (own_x, own_y) = patches.pixel(pos, dims as u32);
+        for x in pixel_x - near..=pixel_x + near {
+            for y in pixel_y - near..=pixel_y + near {
+                if !periodic && (x < 0 || y < 0 || x >= dims || y >= dims) {
+                    continue;
+                }
+
+                let (px, py) = (x.rem_euclid(dims) * factor, y.rem_euclid(dims) * factor);
+                for qy in py..py + factor {
+                    for qx in px..px + factor {
+                        let cell = *patches.cell(qx, qy).unwrap();
+                        let own = qx == own_x && qy == own_y;
+                        *force += pixel_force(cell, qx, qy, patch_dims, own);
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Mass of the field and its normalized center of mass, from the
+    /// coarsest level.
+    fn total_mass(&self) -> (f32, Vec2) {
+        let level = self.levels.last().unwrap();
+        let dims = level.size as i32;
+        let mut mass = 0.0;
+        let mut center = vec2(0.0, 0.0);
+
+        for py in 0..dims {
+            for px in 0..dims {
+                let cell = level.cell(px, py);
+                mass += cell.mass;
+                center += vec2(px as f32 + cell.center.x, py as f32 + cell.center.y)
+                    * (cell.mass / dims as f32);
+            }
+        }
+
+        (mass, center / mass)
+    }
+
+    /// Applies `update` from `read` into `write` like `gravity.comp`.
+    /// Particles the shader skips are copied unchanged.
+    pub fn integrate(
+        &self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        physics: &Physics,
+        update: &Update,
+        delta_t: f32,
+    ) {
+        write
+            .par_iter_mut()
+            .zip(read.par_iter())
+            .for_each(|(out, particle)| {
+                *out = match self.acceleration(particle, physics) {
+                    Some(force) => update.apply(particle, force, delta_t),
+                    None => *particle,
+                };
+            });
+    }
+}
+
+impl CpuSolver for MassField {
+    fn step(
+        &mut self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        physics: &Physics,
+        update: &Update,
+        delta_t: f32,
+    ) {
+        self.clear();
+        self.deposit(read, physics);
+        self.integrate(read, write, physics, update, delta_t);
+    }
+
+    fn accelerations(
+        &mut self,
+        particles: &[Vertex],
+        targets: &[u32],
+        physics: &Physics,
+    ) -> Vec<Option<Vec2>> {
+        self.clear();
+        self.deposit(particles, physics);
+        targets
+            .iter()
+            .map(|&i| self.acceleration(&particles[i as usize], physics))
+            .collect()
+    }
+}
+
+/// The pixel the particle is in without the particle, at `center` in the
+/// pixel. Remainders below `SELF_MASS_TOLERANCE` of the pixel mass are
+/// rounding and leave the pixel empty. The quadrupole expansion does not
+/// hold inside of the pixel, so it has no moments.
+fn without_particle(cell: MassCell, mass: f32, center: Vec2) -> MassCell {
+    let rest = cell.mass - mass;
+    if rest <= cell.mass * SELF_MASS_TOLERANCE {
+        return MassCell::EMPTY;
+    }
+
+    MassCell {
+        mass: rest,
+        center: (cell.center * cell.mass - center * mass) / rest,
+        moments: vec3(0.0, 0.0, 0.0),
+    }
+}
+
+/// Fixed point units per mass unit of the deposit and the mass of one
+/// unit, the `massScale` and `massQuantum` of `mass.comp`.
+pub fn fixed_point_scale(physics: &Physics) -> (f32, f32) {
+    (
+        globals::MASS_FIXED_POINT_SCALE / physics.particle_mass,
+        physics.particle_mass / globals::MASS_FIXED_POINT_SCALE,
+    )
+}
+
+/// Splits a whole number below 2^64 into its words like `mass.comp`.
+fn fixed_point(value: f32) -> u64 {
+    let high = (value * (1.0 / 4294967296.0)).floor();
+    ((high as u64) << 32) | (value - high * 4294967296.0) as u32 as u64
+}
+
+/// Division rounding to the nearest number like `reduce.comp`.
+fn divide(value: u64, d: u64) -> u64 {
+    value.wrapping_add(d / 2) / d
+}
+
+fn to_f32(value: u64) -> f32 {
+    ((value >> 32) as u32 as f32) * 4294967296.0 + (value as u32) as f32
+}
+
+/// Second moment about the center of mass of the sums `square` of a times
+/// b and `a` and `b` over `mass`, `(square * mass - a * b) / mass^2`. The
+/// numerator is exact in 128 bits, as `centralMoment` in `fixed_point.glsl`
+/// computes it, since subtracting the squared center in floats cancels on
+/// pixels whose mass lies close together.
+fn central_moment(square: u64, a: u64, b: u64, mass: u64, mass_f32: f32) -> f32 {
+    let difference = square as i128 * mass as i128 - a as i128 * b as i128;
+    let magnitude = difference.unsigned_abs();
+    let words = [96, 64, 32, 0].map(|shift| (magnitude >> shift) as u32 as f32);
+    let value =
+        ((words[0] * 4294967296.0 + words[1]) * 4294967296.0 + words[2]) * 4294967296.0 + words[3];
+    let moment = value / mass_f32 / mass_f32;
+    if difference < 0 {
+        -moment
+    } else {
+        moment
+    }
+}
+
+/// Maps world coordinates in [-1, 1] to the [0, 1] range the other solvers
+/// measure distances in.
+pub fn normalize_position(pos: Vec2) -> Vec2 {
+    vec2((pos.x + 1.0) * 0.5, (pos.y + 1.0) * 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use super::*;
+    use crate::config::SofteningKernel;
+    use crate::cpu::direct;
+
+    /// Power of two sides keep the pixel borders exact in world units.
+    const CONFIG: MassFieldConfig = MassFieldConfig {
+        size: 32,
+        downsampling: 2,
+        levels: None,
+        reach: 2,
+        refine_density: None,
+        patch_size: 8,
+        refine_factor: 3,
+        max_patches: 64,
+    };
+
+    fn particle(x: f32, y: f32, mass: f32) -> Vertex {
+        Vertex::new(vec2(x, y), vec2(0.0, 0.0)).with_species(0, mass)
+    }
+
+    fn random_particles(count: usize, radius: f32, seed: u64) -> Vec<Vertex> {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        (0..count)
+            .map(|_| {
+                let r = radius * rng.gen::<f32>().sqrt();
+                let angle = rng.gen::<f32>() * std::f32::consts::TAU;
+                let mass = 0.03 * rng.gen_range(1..4) as f32;
+                particle(r * angle.cos() + 0.1, r * angle.sin() - 0.05, mass)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_level_holds_the_mass_inside_of_the_domain() {
+        let physics = Physics::default();
+        let mut particles = random_particles(5000, 0.9, 1);
+        // On the borders of the finest pixels, which are borders of coarser
+        // pixels too, and just below them
+        for k in [-12.0f32, -8.0, 0.5, 4.0, 15.0] {
+            let border = k / 16.0;
+            particles.push(particle(border, border, 0.06));
+            particles.push(particle(border.next_down(), 0.3, 0.03));
+        }
+        // Outside of the field and on its edge
+        particles.push(particle(1.5, 0.2, 0.09));
+        particles.push(particle(-0.4, -1.0, 0.09));
+        particles.push(particle(1.0, 0.7, 0.09));
+
+        let mut field = MassField::new(DomainMode::Fixed, Multipoles::Quadrupole, &CONFIG);
+        field.accumulate(&particles, &physics);
+
+        let (scale, _) = fixed_point_scale(&physics);
+        let inside = particles
+            .iter()
+            .filter(|p| Domain::contains(Domain::FIXED.normalize(p.pos)))
+            .map(|p| (p.mass * scale).round_ties_even() as u64);
+        let expected = inside.clone().fold(0, u64::wrapping_add);
+        assert_eq!(inside.count(), particles.len() - 3);
+
+        assert_eq!(field.levels.len(), 4);
+        for level in &field.levels {
+            assert_eq!(level.accumulated_mass(), expected, "level {}", level.size);
+        }
+    }
+
+    #[test]
+    fn coarser_levels_cover_the_finest_pixel_of_a_particle() {
+        let physics = Physics::default();
+        let mut field = MassField::new(DomainMode::Fixed, Multipoles::Quadrupole, &CONFIG);
+
+        // Normalized at 0.25 and just below it, the first one on the border
+        // of the pixels of every level
+        for x in [-0.5f32, (-0.5f32).next_down()] {
+            field.clear();
+            field.deposit(&[particle(x, 0.1, 0.03)], &physics);
+
+            let finest = (Domain::FIXED.normalize(vec2(x, 0.1)) * 32.0).map(|c| c.floor() as i32);
+            for (i, level) in field.levels.iter().enumerate() {
+                let factor = 1 << i;
+                let cell = level.cell(finest.x / factor, finest.y / factor);
+                assert!(
+                    (cell.mass - 0.03).abs() < 1e-6,
+                    "level {}: mass {}",
+                    level.size,
+                    cell.mass
+                );
+                assert!(cell.center.x >= 0.0 && cell.center.x <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn forces_match_direct_summation() {
+        let physics = Physics {
+            softening: 0.0005,
+            kernel: SofteningKernel::Plummer,
+            ..Default::default()
+        };
+        // Every particle has a companion about a third of a finest pixel
+        // away, which dominates its force and is exact only if the own
+        // pixel loses exactly the particle.
+        let particles = random_particles(1000, 0.8, 2)
+            .into_iter()
+            .flat_map(|p| [p, particle(p.pos.x + 0.002, p.pos.y + 0.001, 0.03)])
+            .collect::<Vec<_>>();
+        let targets = (0..particles.len() as u32).collect::<Vec<_>>();
+
+        let config = MassFieldConfig {
+            size: 243,
+            downsampling: 3,
+            ..CONFIG
+        };
+        let mut field = MassField::new(DomainMode::Fixed, Multipoles::Quadrupole, &config);
+        let approximate = field.accelerations(&particles, &targets, &physics);
+        let exact = direct::accelerations(&particles, &targets, &physics);
+
+        // A few pixels hold a third particle, which their center of mass
+        // does not resolve, so the bounds leave out the largest errors.
+        let mut errors = approximate
+            .iter()
+            .zip(&exact)
+            .map(|(a, e)| (a.unwrap() - e).magnitude() / e.magnitude())
+            .collect::<Vec<_>>();
+        errors.sort_by(f32::total_cmp);
+        let median = errors[errors.len() / 2];
+        let p90 = errors[errors.len() * 9 / 10];
+        assert!(median < 1e-4, "median relative error {}", median);
+        assert!(p90 < 2e-3, "90th percentile of the relative error {}", p90);
+    }
+
+    #[test]
+    fn moments_of_a_tight_cluster_do_not_cancel() {
+        // Far from the corner of the pixel, where the squared center is
+        // much larger than the spread of the cluster
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let points: Vec<Vec2> = (0..10000)
+            .map(|_| {
+                vec2(
+                    0.9 + 0.002 * rng.gen::<f32>(),
+                    0.7 - 0.001 * rng.gen::<f32>(),
+                )
+            })
+            .collect();
+        let mut sum = FixedCell::default();
+        for &point in &points {
+            sum.add(globals::MASS_FIXED_POINT_SCALE, point, true);
+        }
+        let cell = sum.resolve(1.0, true);
+
+        // Rounding the deposits to fixed point units leaves about 1%,
+        // subtracting the squared center in floats left 10%
+
+        let count = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.x as f64).sum::<f64>() / count;
+        let mean_y = points.iter().map(|p| p.y as f64).sum::<f64>() / count;
+        let moment = |f: &dyn Fn(&Vec2) -> f64| points.iter().map(f).sum::<f64>() / count;
+        let expected = [
+            moment(&|p| (p.x as f64 - mean_x).powi(2)),
+            moment(&|p| (p.x as f64 - mean_x) * (p.y as f64 - mean_y)),
+            moment(&|p| (p.y as f64 - mean_y).powi(2)),
+        ];
+        let moments = [cell.moments.x, cell.moments.y, cell.moments.z];
+        let scale = expected[0].max(expected[2]);
+        for (moment, expected) in moments.into_iter().zip(expected) {
+            let error = (moment as f64 - expected).abs() / scale;
+            assert!(error < 3e-2, "moment {moment} instead of {expected}");
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt::Debug;
+
+use rayon::prelude::*;
+
+use crate::config::{Physics, RunConfig, SolverKind};
+use crate::data::vertex::Vertex;
+use crate::integrators::Update;
+
+use self::barnes_hut::BarnesHut;
+use self::direct::DirectSummation;
+use self::mass_field::MassField;
+use self::particle_mesh::ParticleMesh;
+
+pub mod barnes_hut;
+pub mod boundary;
+pub mod direct;
+pub mod domain;
+pub mod mass_field;
+pub mod particle_mesh;
+pub mod patches;
+pub mod simulation;
+pub mod softening;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// A method computing the gravitational forces on the CPU.
+pub trait CpuSolver: Debug {
+    /// Applies `update` to the particles of `read` and writes them into
+    /// `write`, like `gravity.comp`. Particles the solver skips are copied
+    /// unchanged, like on the device.
+    fn step(
+        &mut self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        physics: &Physics,
+        update: &Update,
+        delta_t: f32,
+    );
+
+    /// Accelerations of the `targets` particles, `None` for the ones the
+    /// solver ignores.
+    fn accelerations(
+        &mut self,
+        particles: &[Vertex],
+        targets: &[u32],
+        physics: &Physics,
+    ) -> Vec<Option<Vec2>>;
+
+    /// Applies `update` with the `accelerations` of all particles of `read`,
+    /// for integrators that need them before stepping. Particles without
+    /// one keep their state.
+    fn apply(
+        &self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        accelerations: &[Option<Vec2>],
+        update: &Update,
+        delta_t: f32,
+    ) {
+        write
+            .par_iter_mut()
+            .zip(read.par_iter().zip(accelerations))
+            .for_each(|(out, (particle, acceleration))| {
+                *out = match acceleration {
+                    Some(acceleration) => update.apply(particle, *acceleration, delta_t),
+                    None => *particle,
+                };
+            });
+    }
+}
+
+/// Creates the CPU version of the solver selected by `config`.
+pub fn create_solver(config: &RunConfig) -> Box<dyn CpuSolver> {
+    match config.solver {
+        SolverKind::MassField => Box::new(MassField::new(
+            config.mass_field_domain(),
+            config.multipoles,
+            &config.mass_field,
+        )),
+        SolverKind::Direct => Box::new(DirectSummation),
+        SolverKind::BarnesHut => Box::new(BarnesHut::new(config.theta)),
+        SolverKind::ParticleMesh => Box::new(ParticleMesh::new(
+            config.grid_size as usize,
+            &config.physics,
+        )),
+    }
+}
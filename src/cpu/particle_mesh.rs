@@ -0,0 +1,362 @@
+//! Particle-mesh solver on a periodic grid covering the [-1, 1] world.
+//!
+//! Mass is deposited with cloud-in-cell weights, the potential is the
+//! convolution of the density with the potential of a single particle,
+//! evaluated with FFTs, and the accelerations are the finite difference
+//! gradient of the potential interpolated back with the same weights.
+//!
+//! The kernel is the potential of the force law of `gravity.comp` with its
+//! softening kernel, at least half a cell wide and the same for every
+//! particle, rather than the logarithmic potential of the 2D Poisson
+//! equation, so the results can be compared with the other solvers. Each
+//! cell sees the nearest periodic image of every other cell, and the mean
+//! density is removed, as usual for periodic boundaries.
+
+use std::fmt;
+use std::sync::Arc;
+
+use cgmath::vec2;
+use rayon::prelude::*;
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+
+use crate::config::Physics;
+use crate::cpu::mass_field::normalize_position;
+use crate::cpu::softening;
+use crate::cpu::CpuSolver;
+use crate::data::vertex::Vertex;
+use crate::integrators::Update;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+pub struct ParticleMesh {
+    size: usize,
+    /// Transformed potential of a unit mass.
+    kernel: Vec<Complex<f64>>,
+    forward: Arc<dyn Fft<f64>>,
+    inverse: Arc<dyn Fft<f64>>,
+
+    grid: Vec<Complex<f64>>,
+    acceleration: Vec<[f64; 2]>,
+}
+
+impl fmt::Debug for ParticleMesh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParticleMesh")
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ParticleMesh {
+    /// Creates a mesh with `size` x `size` cells.
+    pub fn new(size: usize, physics: &Physics) -> Self {
+        let mut planner = FftPlanner::new();
+        let forward = planner.plan_fft_forward(size);
+        let inverse = planner.plan_fft_inverse(size);
+
+        let cell = 1.0 / size as f64;
+        let softening = (physics.field_softening() as f64).max(cell * 0.5);
+        let strength = physics.field_gravitational_constant() as f64;
+
+        let mut kernel = (0..size * size)
+            .map(|index| {
+                let x = periodic_offset(index % size, size) as f64 * cell;
+                let y = periodic_offset(index / size, size) as f64 * cell;
+                let d = (x * x + y * y).sqrt();
+
+                Complex::new(
+                    strength * softening::potential(physics.kernel, d, softening),
+                    0.0,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut mesh = Self {
+            size,
+            kernel: vec![],
+            forward,
+            inverse,
+            grid: vec![Complex::new(0.0, 0.0); size * size],
+            acceleration: vec![[0.0; 2]; size * size],
+        };
+
+        mesh.fft2(&mut kernel, true);
+        kernel[0] = Complex::new(0.0, 0.0);
+        mesh.kernel = kernel;
+
+        mesh
+    }
+
+    /// Computes the accelerations on the grid for the current particles.
+    pub fn solve(&mut self, particles: &[Vertex]) {
+        let size = self.size;
+
+        let mut grid = std::mem::take(&mut self.grid);
+        grid.fill(Complex::new(0.0, 0.0));
+
+        for particle in particles {
+            if let Some(weights) = self.weights(particle.pos) {
+                for (index, weight) in weights {
+                    grid[index].re += weight * particle.mass as f64;
+                }
+            }
+        }
+
+        self.fft2(&mut grid, true);
+        grid.iter_mut()
+            .zip(&self.kernel)
+            .for_each(|(density, kernel)| *density *= kernel);
+        self.fft2(&mut grid, false);
+
+        // Fourth order central differences of the potential.
+        let scale = 1.0 / (size * size) as f64;
+        let cell = 1.0 / size as f64;
+        let potential = |x: usize, y: usize| grid[y * size + x].re * scale;
+        let wrap =
+            |i: usize, offset: isize| (i as isize + offset).rem_euclid(size as isize) as usize;
+
+        self.acceleration
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(index, acceleration)| {
+                let (x, y) = (index % size, index / size);
+                let derivative = |f: &dyn Fn(isize) -> f64| {
+                    (8.0 * (f(1) - f(-1)) - (f(2) - f(-2))) / (12.0 * cell)
+                };
+
+                *acceleration = [
+                    -derivative(&|o| potential(wrap(x, o), y)),
+                    -derivative(&|o| potential(x, wrap(y, o))),
+                ];
+            });
+
+        self.grid = grid;
+    }
+
+    /// Acceleration at a world position interpolated from the grid, in the
+    /// normalized field units used by the other solvers.
+    pub fn acceleration(&self, pos: Vec2) -> Option<Vec2> {
+        let weights = self.weights(pos)?;
+
+        let (x, y) = weights.iter().fold((0.0, 0.0), |(x, y), &(index, weight)| {
+            let a = self.acceleration[index];
+            (x + a[0] * weight, y + a[1] * weight)
+        });
+
+        Some(vec2(x as f32, y as f32))
+    }
+
+    /// Applies `update` from `read` into `write`. The periodic boundary
+    /// wraps the positions back into the world afterwards.
+    pub fn integrate(&self, read: &[Vertex], write: &mut [Vertex], update: &Update, delta_t: f32) {
+        write
+            .par_iter_mut()
+            .zip(read.par_iter())
+            .for_each(|(out, particle)| {
+                *out = *particle;
+
+                if let Some(force) = self.acceleration(particle.pos) {
+                    *out = update.apply(particle, force, delta_t);
+                }
+            });
+    }
+
+    /// Cloud-in-cell weights of the four cells around a world position.
+    fn weights(&self, pos: Vec2) -> Option<[(usize, f64); 4]> {
+        if !pos.x.is_finite() || !pos.y.is_finite() {
+            return None;
+        }
+
+        let size = self.size as f64;
+        let pos = normalize_position(pos);
+        let u = pos.x as f64 * size - 0.5;
+        let v = pos.y as f64 * size - 0.5;
+        let (x, y) = (u.floor(), v.floor());
+        let (fx, fy) = (u - x, v - y);
+
+        let wrap = |i: f64| i.rem_euclid(size) as usize;
+        let (x0, x1) = (wrap(x), wrap(x + 1.0));
+        let (y0, y1) = (wrap(y), wrap(y + 1.0));
+        let row = self.size;
+
+        Some([
+            (y0 * row + x0, (1.0 - fx) * (1.0 - fy)),
+            (y0 * row + x1, fx * (1.0 - fy)),
+            (y1 * row + x0, (1.0 - fx) * fy),
+            (y1 * row + x1, fx * fy),
+        ])
+    }
+
+    /// Two dimensional FFT of a row major square grid, unnormalized.
+    fn fft2(&self, data: &mut [Complex<f64>], forward: bool) {
+        let fft = if forward {
+            &self.forward
+        } else {
+            &self.inverse
+        };
+        let size = self.size;
+
+        for _ in 0..2 {
+            data.par_chunks_mut(size).for_each(|row| fft.process(row));
+
+            for y in 0..size {
+                for x in (y + 1)..size {
+                    data.swap(y * size + x, x * size + y);
+                }
+            }
+        }
+    }
+}
+
+/// The physics of the mesh are fixed when it is created.
+impl CpuSolver for ParticleMesh {
+    fn step(
+        &mut self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        _: &Physics,
+        update: &Update,
+        delta_t: f32,
+    ) {
+        self.solve(read);
+        self.integrate(read, write, update, delta_t);
+    }
+
+    fn accelerations(
+        &mut self,
+        particles: &[Vertex],
+        targets: &[u32],
+        _: &Physics,
+    ) -> Vec<Option<Vec2>> {
+        self.solve(particles);
+        targets
+            .iter()
+            .map(|&i| self.acceleration(particles[i as usize].pos))
+            .collect()
+    }
+
+    fn apply(
+        &self,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        accelerations: &[Option<Vec2>],
+        update: &Update,
+        delta_t: f32,
+    ) {
+        write
+            .par_iter_mut()
+            .zip(read.par_iter().zip(accelerations))
+            .for_each(|(out, (particle, acceleration))| {
+                *out = *particle;
+
+                if let Some(acceleration) = acceleration {
+                    *out = update.apply(particle, *acceleration, delta_t);
+                }
+            });
+    }
+}
+
+/// Offset of a grid index from cell 0 towards its nearest periodic image.
+fn periodic_offset(i: usize, size: usize) -> isize {
+    if i <= size / 2 {
+        i as isize
+    } else {
+        i as isize - size as isize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+    use crate::config::{Boundary, SofteningKernel};
+
+    const SIZE: usize = 64;
+
+    /// World position of the center of cell `i` along an axis.
+    fn center(i: usize) -> f32 {
+        -1.0 + (2.0 * i as f32 + 1.0) / SIZE as f32
+    }
+
+    fn particle(x: f32, y: f32) -> Vertex {
+        Vertex::new(vec2(x, y), vec2(0.0, 0.0)).with_species(0, 1.0)
+    }
+
+    /// A smooth kernel two cells wide, which the mesh resolves.
+    fn physics() -> Physics {
+        Physics {
+            softening: 4.0 / SIZE as f32,
+            kernel: SofteningKernel::Plummer,
+            boundary: Boundary::Periodic,
+            ..Default::default()
+        }
+    }
+
+    fn accelerations(particles: &[Vertex], physics: &Physics) -> Vec<Vec2> {
+        let mut mesh = ParticleMesh::new(SIZE, physics);
+        mesh.solve(particles);
+        particles
+            .iter()
+            .map(|p| mesh.acceleration(p.pos).unwrap())
+            .collect()
+    }
+
+    /// The pull of a unit mass `cells` cells away, in normalized units.
+    fn expected(physics: &Physics, cells: usize) -> f32 {
+        let cell = 1.0 / SIZE as f64;
+        let softening = (physics.field_softening() as f64).max(cell * 0.5);
+        let d = cells as f64 * cell;
+        let f = softening::force(physics.kernel, d, softening);
+        (physics.field_gravitational_constant() as f64 * f * d) as f32
+    }
+
+    #[test]
+    fn pair_on_cell_centers_attracts_like_point_masses() {
+        let physics = physics();
+        for cells in [4, 8, 16] {
+            let particles = [
+                particle(center(20), center(30)),
+                particle(center(20 + cells), center(30)),
+            ];
+            let a = accelerations(&particles, &physics);
+
+            let expected = expected(&physics, cells);
+            assert!(
+                (a[0].x - expected).abs() < 0.01 * expected,
+                "{} cells: {} instead of {}",
+                cells,
+                a[0].x,
+                expected
+            );
+            assert!(a[0].y.abs() < 1e-3 * expected);
+            assert!((a[0] + a[1]).magnitude() < 1e-3 * expected);
+        }
+    }
+
+    #[test]
+    fn pulls_through_the_periodic_boundary() {
+        let physics = physics();
+        let inside = accelerations(
+            &[particle(center(28), 0.1), particle(center(36), 0.1)],
+            &physics,
+        );
+        let across = accelerations(
+            &[particle(center(SIZE - 4), 0.1), particle(center(4), 0.1)],
+            &physics,
+        );
+        assert!(across[0].x > 0.0);
+        assert!((across[0] - inside[0]).magnitude() < 1e-4 * inside[0].x);
+
+        // Half a box apart, the two images pull equally in opposite directions.
+        let half = accelerations(
+            &[
+                particle(center(10), 0.1),
+                particle(center(10 + SIZE / 2), 0.1),
+            ],
+            &physics,
+        );
+        assert!(half[0].magnitude() < 1e-3 * inside[0].x);
+    }
+}
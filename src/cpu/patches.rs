@@ -0,0 +1,174 @@
+//! Refinement patches of the CPU mass field, following `refine.comp`.
+//!
+//! The finest level is split into tiles, the pixels of one of the coarser
+//! levels, so the mass of every tile is known once the levels are reduced.
+//! Tiles holding more than the refinement density get a patch with
+//! `refine_factor` times finer pixels, in row order up to the number of
+//! patches, which picks the same tiles on the device. A patch pixel lies
+//! inside of one finest pixel, so the patch of a tile holds exactly the
+//! mass of the tile.
+
+use cgmath::vec2;
+use rayon::prelude::*;
+
+use crate::config::MassFieldConfig;
+use crate::cpu::mass_field::{FixedCell, MassCell, MassLevel};
+use crate::utils::mass_field;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Debug)]
+pub struct Patches {
+    /// Level of the mass field whose pixels are the tiles.
+    pub tile_level: usize,
+    /// Finest pixels per side of a tile.
+    pub tile_size: u32,
+    /// Tiles per side.
+    pub tiles: u32,
+    /// Patch pixels per side of a finest pixel.
+    pub factor: u32,
+    /// Most tiles refined at once.
+    pub max_patches: u32,
+    /// Fixed point mass a tile has to exceed to be refined.
+    threshold: u64,
+    /// Patch of every tile plus one, zero for tiles without one.
+    map: Vec<u32>,
+    /// Patches in use since the last selection.
+    pub count: u32,
+    sums: Vec<FixedCell>,
+    cells: Vec<MassCell>,
+}
+
+impl Patches {
+    /// Patches of a validated config, `None` without refinement.
+    pub fn new(config: &MassFieldConfig) -> Option<Self> {
+        let threshold = mass_field::refine_threshold(config)?;
+        let tile_level = mass_field::tile_level(config)?;
+        let tiles = config.size / config.patch_size;
+        let side = config.patch_size * config.refine_factor;
+        let pixels = (config.max_patches * side * side) as usize;
+
+        Some(Self {
+            tile_level,
+            tile_size: config.patch_size,
+            tiles,
+            factor: config.refine_factor,
+            max_patches: config.max_patches,
+            threshold,
+            map: vec![0; (tiles * tiles) as usize],
+            count: 0,
+            sums: vec![FixedCell::default(); pixels],
+            cells: vec![MassCell::EMPTY; pixels],
+        })
+    }
+
+    /// Patch pixels per side of a patch.
+    pub fn side(&self) -> u32 {
+        self.tile_size * self.factor
+    }
+
+    /// Picks the tiles of `level`, the tile level after the reduction,
+    /// whose mass is above the threshold, the select pass of `refine.comp`.
+    pub fn select(&mut self, level: &MassLevel) {
+        self.count = 0;
+        for (slot, sum) in self.map.iter_mut().zip(level.sums()) {
+            *slot = 0;
+            if sum.mass() > self.threshold && self.count < self.max_patches {
+                self.count += 1;
+                *slot = self.count;
+            }
+        }
+    }
+
+    /// Adds `mass` fixed point units at the normalized position `pos` to
+    /// its patch pixel, if its tile on a finest level of `size` pixels per
+    /// side is refined. The deposit pass of `refine.comp`.
+    pub fn add(&mut self, pos: Vec2, size: u32, mass: f32, quadrupole: bool) {
+        let dims = size as f32;
+        let pixel = vec2((pos.x * dims).floor(), (pos.y * dims).floor());
+        let Some(patch) = self.slot(pixel.x as i32, pixel.y as i32) else {
+            return;
+        };
+
+        let factor = self.factor as f32;
+        let inside = (pos * dims - pixel) * factor;
+        let sub = vec2(
+            inside.x.floor().clamp(0.0, factor - 1.0),
+            inside.y.floor().clamp(0.0, factor - 1.0),
+        );
+
+        let side = self.side();
+        let x = (pixel.x as u32 % self.tile_size) * self.factor + sub.x as u32;
+        let y = (pixel.y as u32 % self.tile_size) * self.factor + sub.y as u32;
+        let index = (patch * side + y) * side + x;
+        self.sums[index as usize].add(mass, inside - sub, quadrupole);
+    }
+
+    /// Turns the sums of the patches in use into their cells and clears
+    /// them, the resolve pass of `refine.comp`.
+    pub fn resolve(&mut self, quantum: f32, quadrupole: bool) {
+        let side = self.side();
+        let used = (self.count * side * side) as usize;
+        self.cells[..used]
+            .par_iter_mut()
+            .zip(self.sums[..used].par_iter_mut())
+            .for_each(|(cell, sum)| {
+                *cell = sum.resolve(quantum, quadrupole);
+                *sum = FixedCell::default();
+            });
+    }
+
+    /// Patch of the tile holding the finest pixel `x`, `y`.
+    pub fn slot(&self, x: i32, y: i32) -> Option<u32> {
+        let tile_x = x as u32 / self.tile_size;
+        let tile_y = y as u32 / self.tile_size;
+        match self.map[(tile_y * self.tiles + tile_x) as usize] {
+            0 => None,
+            slot => Some(slot - 1),
+        }
+    }
+
+    /// Patch pixel of the normalized position `pos` over the whole field,
+    /// for a finest level of `size` pixels per side.
+    pub fn pixel(&self, pos: Vec2, size: u32) -> (i32, i32) {
+        let dims = size as f32;
+        let factor = self.factor as f32;
+        let pixel = vec2((pos.x * dims).floor(), (pos.y * dims).floor());
+        let inside = (pos * dims - pixel) * factor;
+        let sub_x = inside.x.floor().clamp(0.0, factor - 1.0);
+        let sub_y = inside.y.floor().clamp(0.0, factor - 1.0);
+        (
+            pixel.x as i32 * self.factor as i32 + sub_x as i32,
+            pixel.y as i32 * self.factor as i32 + sub_y as i32,
+        )
+    }
+
+    /// Cell of the patch pixel `x`, `y` over the whole field, if its tile
+    /// is refined.
+    pub fn cell(&self, x: i32, y: i32) -> Option<&MassCell> {
+        let factor = self.factor as i32;
+        let patch = self.slot(x / factor, y / factor)?;
+        let side = self.side();
+        let local_x = x as u32 % side;
+        let local_y = y as u32 % side;
+        Some(&self.cells[((patch * side + local_y) * side + local_x) as usize])
+    }
+
+    /// Fixed point mass accumulated in every patch in use and the tile it
+    /// refines, since the last resolve.
+    pub fn accumulated_masses(&self) -> Vec<(u32, u64)> {
+        let side = (self.side() * self.side()) as usize;
+        let mut masses = vec![(0, 0); self.count as usize];
+        for (tile, &slot) in self.map.iter().enumerate() {
+            if slot > 0 {
+                let sums = &self.sums[(slot as usize - 1) * side..][..side];
+                let mass = sums
+                    .iter()
+                    .fold(0, |total: u64, sum| total.wrapping_add(sum.mass()));
+                masses[slot as usize - 1] = (tile as u32, mass);
+            }
+        }
+
+        masses
+    }
+}
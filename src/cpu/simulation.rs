@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use anyhow::Result;
+use log::info;
+
+use crate::config::units::{self, Units};
+use crate::config::{Boundary, Integrator, Physics, RunConfig};
+use crate::cpu::{self, boundary, CpuSolver};
+use crate::data::globals;
+use crate::data::vertex::Vertex;
+use crate::integrators::host::HostIntegrator;
+use crate::integrators::Update;
+use crate::snapshot::{Snapshot, SnapshotHeader};
+
+/// Runs the simulation on the CPU, either with the reference of the mass
+/// field passes, for machines without a Vulkan device, or with the solvers
+/// that only exist on the CPU. Like the device it ping-pongs between two
+/// particle buffers, so skipped particles behave the same way.
+#[derive(Debug)]
+pub struct CpuSimulation {
+    step: u64,
+    sim_time: f64,
+    delta_t: f32,
+    physics: Physics,
+    seed: Option<u64>,
+    integrator: Integrator,
+    report_every: u64,
+    units: Option<Units>,
+    snapshot_every: Option<u64>,
+    snapshot_dir: PathBuf,
+
+    solver: Box<dyn CpuSolver>,
+    host_integrator: HostIntegrator,
+    particles: [Vec<Vertex>; 2],
+}
+
+impl CpuSimulation {
+    pub fn create(initial: Snapshot, config: &RunConfig) -> Self {
+        let Snapshot { header, vertices } = initial;
+
+        let mut simulation = Self {
+            step: header.step,
+            sim_time: header.sim_time,
+            delta_t: config.dt.unwrap_or(globals::DEFAULT_DELTA_T),
+            physics: config.physics,
+            seed: config.seed,
+            integrator: config.integrator,
+            report_every: config
+                .report_every
+                .unwrap_or(globals::HEADLESS_REPORT_EVERY),
+            units: config.units.clone(),
+            snapshot_every: config.snapshot_every,
+            snapshot_dir: config.snapshot_dir.clone(),
+            solver: cpu::create_solver(config),
+            host_integrator: HostIntegrator::new(config),
+            particles: [vertices.clone(), vertices],
+        };
+
+        // Both buffers hold the initial state, so kicking from the one the
+        // first step writes into the one it reads staggers the velocities.
+        if simulation.integrator.staggered() {
+            let lag = Update::lag(simulation.integrator);
+            simulation.kick(((simulation.step + 1) % 2) as usize, -lag);
+        }
+
+        simulation
+    }
+
+    /// Advances the simulation by `steps` steps, reporting and writing
+    /// snapshots on the same schedule as the headless device runs.
+    pub fn run(&mut self, steps: u64) -> Result<()> {
+        let start = Instant::now();
+
+        for done in 1..=steps {
+            self.advance();
+
+            if done.is_multiple_of(self.report_every) || done == steps {
+                info!(
+                    "step {}/{}, sim time {} ({:.1} steps/s)",
+                    done,
+                    steps,
+                    units::format_time(self.sim_time, self.units.as_ref()),
+                    done as f64 / start.elapsed().as_secs_f64()
+                );
+
+                if self.physics.boundary == Boundary::Absorbing {
+                    info!("{} particles absorbed", boundary::removed(self.particles()));
+                }
+            }
+
+            if let Some(every) = self.snapshot_every {
+                if self.step.is_multiple_of(every) || done == steps {
+                    let path = self.snapshot().save_to_dir(&self.snapshot_dir)?;
+                    info!("wrote {}", path.display());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Computes one step. Step `s` reads buffer `(s + 1) % 2` and writes
+    /// buffer `s % 2`, matching the device frames.
+    pub fn advance(&mut self) {
+        let frame = (self.step % 2) as usize;
+        let delta_t = self.host_integrator.step(
+            self.solver.as_mut(),
+            &mut self.particles,
+            frame,
+            &self.physics,
+            self.delta_t,
+        );
+
+        self.step += 1;
+        self.sim_time += delta_t as f64;
+    }
+
+    pub fn sim_time(&self) -> f64 {
+        self.sim_time
+    }
+
+    /// The latest particles, with staggered velocities for leapfrog and
+    /// Yoshida.
+    pub fn particles(&self) -> &[Vertex] {
+        &self.particles[((self.step + 1) % 2) as usize]
+    }
+
+    /// The latest particles with the velocities at the time of the
+    /// positions. Staggered velocities are kicked into the buffer the next
+    /// step overwrites, as on the device.
+    pub fn snapshot(&mut self) -> Snapshot {
+        let vertices = if self.integrator.staggered() {
+            let frame = (self.step % 2) as usize;
+            self.kick(frame, Update::lag(self.integrator));
+            self.particles[frame].clone()
+        } else {
+            self.particles().to_vec()
+        };
+
+        Snapshot {
+            header: SnapshotHeader {
+                particle_count: vertices.len() as u64,
+                step: self.step,
+                sim_time: self.sim_time,
+                seed: self.seed,
+                dt: self.delta_t,
+                physics: self.physics,
+            },
+            vertices,
+        }
+    }
+
+    fn kick(&mut self, frame: usize, kick: f32) {
+        self.host_integrator.kick(
+            self.solver.as_mut(),
+            &mut self.particles,
+            frame,
+            &self.physics,
+            kick,
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Softening kernels shared by the CPU solvers, the same as
+//! `shaders/softening.glsl`. The spline kernel follows Gadget 2: its
+//! support `h` is 2.8 softening lengths, which gives it the central
+//! potential of a Plummer sphere with the same softening length.
+
+use crate::config::SofteningKernel;
+
+/// Support of the spline kernel in softening lengths.
+pub const SPLINE_SUPPORT: f64 = 2.8;
+
+/// Factor `g` of the acceleration `G m g (other - pos)` at distance `d`,
+/// `1 / d^3` without softening.
+pub fn force(kernel: SofteningKernel, d: f64, softening: f64) -> f64 {
+    force_and_derivative(kernel, d, softening).0
+}
+
+/// `force` and its derivative by `d` divided by `d`, which the jerks need.
+pub fn force_and_derivative(kernel: SofteningKernel, d: f64, softening: f64) -> (f64, f64) {
+    let newtonian = || {
+        let d3 = d * d * d;
+        (1.0 / d3, -3.0 / (d3 * d * d))
+    };
+
+    match kernel {
+        SofteningKernel::Cutoff if d < softening => (0.0, 0.0),
+        SofteningKernel::Cutoff => newtonian(),
+        SofteningKernel::Plummer => {
+            let s = d * d + softening * softening;
+            let g = 1.0 / (s * s.sqrt());
+            (g, -3.0 * g / s)
+        }
+        SofteningKernel::Spline => {
+            let h = SPLINE_SUPPORT * softening;
+            if d >= h {
+                return newtonian();
+            }
+
+            let u = d / h;
+            let h3 = h * h * h;
+            let h5 = h3 * h * h;
+            if u < 0.5 {
+                (
+                    (32.0 / 3.0 + u * u * (32.0 * u - 38.4)) / h3,
+                    (96.0 * u - 76.8) / h5,
+                )
+            } else {
+                let u3 = u * u * u;
+                (
+                    (64.0 / 3.0 - 48.0 * u + 38.4 * u * u - 32.0 / 3.0 * u3 - 1.0 / (15.0 * u3))
+                        / h3,
+                    (-48.0 + 76.8 * u - 32.0 * u * u + 0.2 / (u3 * u)) / (u * h5),
+                )
+            }
+        }
+    }
+}
+
+/// The derivative of `force` by `d` divided by `d` and the derivative of
+/// that divided by `d` again, which the quadrupole moments of the mass field
+/// need. Zero inside the cutoff.
+pub fn derivatives(kernel: SofteningKernel, d: f64, softening: f64) -> (f64, f64) {
+    let newtonian = || {
+        let d2 = d * d;
+        let d5 = d2 * d2 * d;
+        (-3.0 / d5, 15.0 / (d5 * d2))
+    };
+
+    match kernel {
+        SofteningKernel::Cutoff if d < softening => (0.0, 0.0),
+        SofteningKernel::Cutoff => newtonian(),
+        SofteningKernel::Plummer => {
+            let s = d * d + softening * softening;
+            let h = -3.0 / (s * s * s.sqrt());
+            (h, -5.0 * h / s)
+        }
+        SofteningKernel::Spline => {
+            let h = SPLINE_SUPPORT * softening;
+            if d >= h {
+                return newtonian();
+            }
+
+            let u = d / h;
+            let h5 = h * h * h * h * h;
+            let h7 = h5 * h * h;
+            if u < 0.5 {
+                ((96.0 * u - 76.8) / h5, 96.0 / (u * h7))
+            } else {
+                let u2 = u * u;
+                let u6 = u2 * u2 * u2;
+                (
+                    (-48.0 + 76.8 * u - 32.0 * u2 + 0.2 / (u2 * u2)) / (u * h5),
+                    (48.0 / u2 - 32.0 - 1.0 / u6) / (u * h7),
+                )
+            }
+        }
+    }
+}
+
+/// Factor `p` of the potential `G m p` at distance `d`, `-1 / d` without
+/// softening. The cutoff keeps the potential at the softening length
+/// constant inside it.
+pub fn potential(kernel: SofteningKernel, d: f64, softening: f64) -> f64 {
+    match kernel {
+        SofteningKernel::Cutoff => -1.0 / d.max(softening),
+        SofteningKernel::Plummer => -1.0 / (d * d + softening * softening).sqrt(),
+        SofteningKernel::Spline => {
+            let h = SPLINE_SUPPORT * softening;
+            if d >= h {
+                return -1.0 / d;
+            }
+
+            let u = d / h;
+            let w = if u < 0.5 {
+                -2.8 + u * u * (16.0 / 3.0 + u * u * (6.4 * u - 9.6))
+            } else {
+                -3.2 + 1.0 / (15.0 * u)
+                    + u * u * (32.0 / 3.0 + u * (-16.0 + u * (9.6 - 32.0 / 15.0 * u)))
+            };
+            w / h
+        }
+    }
+}
@@ -8,10 +8,17 @@ pub struct BuffersData {
     pub storage_buffers: Vec<vk::Buffer>,
     pub storage_buffer_memories: Vec<vk::DeviceMemory>,
 
+    /// Host visible copies of the particles for solvers running on the host.
+    pub upload_buffers: Vec<vk::Buffer>,
+    pub upload_buffer_memories: Vec<vk::DeviceMemory>,
+
     pub uniform_buffers: Vec<vk::Buffer>,
     pub uniform_buffers_memory: Vec<vk::DeviceMemory>,
 
     pub offscreen_images: Vec<Vec<ImageData>>,
+    /// Second moments of the mass field pixels of each frame, empty without
+    /// quadrupoles.
+    pub moment_images: Vec<Vec<ImageData>>,
 }
 
 impl Drop for BuffersData {
@@ -40,6 +47,13 @@ impl Drop for BuffersData {
             self.storage_buffer_memories
                 .iter()
                 .for_each(|s| globals::get_device().free_memory(*s, None));
+
+            self.upload_buffers
+                .iter()
+                .for_each(|s| globals::get_device().destroy_buffer(*s, None));
+            self.upload_buffer_memories
+                .iter()
+                .for_each(|s| globals::get_device().free_memory(*s, None));
         }
     }
 }
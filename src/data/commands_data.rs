@@ -8,9 +8,7 @@ pub struct CommandsData {
     pub main_command_pool: vk::CommandPool,
 
     pub command_buffers: Vec<vk::CommandBuffer>,
-    pub gravity_compute_command_buffers: Vec<vk::CommandBuffer>,
-    pub mass_compute_command_buffers: Vec<vk::CommandBuffer>,
-    pub image_clear_command_buffers: Vec<vk::CommandBuffer>,
+    pub compute_step_command_buffers: Vec<vk::CommandBuffer>,
 }
 
 impl Drop for CommandsData {
@@ -10,5 +10,5 @@ pub struct CommonData {
     pub present_queue: vk::Queue,
     pub compute_queue: vk::Queue,
 
-    pub mip_levels: u32,
+    pub headless: bool,
 }
@@ -1,4 +1,4 @@
-use anyhow::{anyhow, Result};
+use std::ptr::addr_of;
 use vulkanalia::prelude::v1_0::*;
 
 pub const VALIDATION_ENABLED: bool = cfg!(debug_assertions);
@@ -6,26 +6,60 @@ pub const VALIDATION_ENABLED: bool = cfg!(debug_assertions);
 pub const VALIDATION_LAYER: vk::ExtensionName =
     vk::ExtensionName::from_bytes(b"VK_LAYER_KHRONOS_validation");
 
-pub const DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[
-    vk::KHR_SWAPCHAIN_EXTENSION.name,
-    vk::KHR_SHADER_NON_SEMANTIC_INFO_EXTENSION.name,
-];
+pub const DEVICE_EXTENSIONS: &[vk::ExtensionName] =
+    &[vk::KHR_SHADER_NON_SEMANTIC_INFO_EXTENSION.name];
 
-pub const MIP_LEVEL_DOWNSAMLING: u32 = 3;
-pub const MASS_FIELD_SIZE: u32 = MIP_LEVEL_DOWNSAMLING.pow(7);
+pub const WINDOW_DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[vk::KHR_SWAPCHAIN_EXTENSION.name];
 
-pub const MAX_MIP_LEVELS: u32 = 12;
+/// Largest side of the finest mass field level, the image size every
+/// Vulkan device supports.
+pub const MAX_MASS_FIELD_SIZE: u32 = 4096;
+/// Largest factor between neighbouring mass field levels, `reduce.comp`
+/// divides by its square in steps of 16 bits.
+pub const MAX_DOWNSAMPLING: u32 = 16;
+/// Largest reach of the mass field around a particle.
+pub const MAX_REACH: u32 = 8;
+/// Most refinement patches of the mass field.
+pub const MAX_PATCHES: u32 = 256;
+/// Work groups of a dispatch every Vulkan device supports.
+pub const MAX_WORK_GROUPS: u32 = 65535;
 pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
-pub const SHADER_FORCE_REGION_RADIUS: u32 = 3;
+/// Fixed point units per `particle_mass` of the mass deposited by
+/// `mass.comp`. Pixels hold up to 2^43 particles of that mass.
+pub const MASS_FIXED_POINT_SCALE: f32 = 1048576.0;
+/// Histogram bins per axis of the percentile domain, as in `domain.comp`.
+pub const DOMAIN_HISTOGRAM_BINS: usize = 512;
+/// Fraction of the particles left out on each side of the percentile domain.
+pub const DOMAIN_PERCENTILE: f32 = 0.001;
+/// Space added around the particles on each side of the mass field domain,
+/// as a fraction of its size.
+pub const DOMAIN_MARGIN: f32 = 0.01;
+/// Smallest side of the mass field domain in world units.
+pub const MIN_DOMAIN_SIZE: f32 = 1e-3;
+
+pub const DEFAULT_DELTA_T: f32 = 1.0 / 60.0;
+/// Wall time a single rendered frame can add to the step accumulator.
+pub const MAX_FRAME_TIME: f64 = 0.25;
+pub const MAX_STEPS_PER_FRAME: u64 = 16;
+pub const MIN_TIME_SCALE: f32 = 1.0 / 64.0;
+pub const MAX_TIME_SCALE: f32 = 64.0;
+/// Smallest adaptive timestep as a fraction of the largest one.
+pub const MIN_ADAPTIVE_DELTA_T: f32 = 1.0 / 4096.0;
+/// Species indices the shaders can draw, including the default species 0.
+pub const MAX_SPECIES: usize = 8;
+/// Most block timestep levels, each step recording `2^(levels - 1)` ticks.
+pub const MAX_BLOCK_LEVELS: u32 = 12;
+pub const HEADLESS_STEPS_PER_SUBMIT: u64 = 64;
+pub const HEADLESS_REPORT_EVERY: u64 = 100;
 
 static mut DEVICE: Option<Device> = None;
 
 pub fn get_device_opt() -> Option<Device> {
-    unsafe { DEVICE.clone() }
+    unsafe { (*addr_of!(DEVICE)).clone() }
 }
 
 pub fn get_device() -> Device {
-    unsafe { DEVICE.clone().unwrap() }
+    unsafe { (*addr_of!(DEVICE)).clone().unwrap() }
 }
 
 pub fn set_device(device: &Device) {
@@ -36,7 +70,7 @@ pub fn set_device(device: &Device) {
 
 pub fn destroy_device() {
     unsafe {
-        DEVICE.clone().unwrap().destroy_device(None);
+        (*addr_of!(DEVICE)).clone().unwrap().destroy_device(None);
         DEVICE = None;
     }
 }
@@ -5,6 +5,7 @@ pub mod descriptors_data;
 pub mod globals;
 pub mod image_data;
 pub mod pipeline_data;
+pub mod push_constants;
 pub mod swapchain_data;
 pub mod sync_data;
 pub mod uniform_buffer_object;
@@ -0,0 +1,177 @@
+use std::mem::size_of;
+use std::slice;
+
+use crate::data::globals;
+
+/// A pass of `mass.comp`, depositing the particles in fixed point or
+/// resolving the sums into the mass images, with the second moments if
+/// `quadrupole` is set.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MassPushConstants {
+    pub mode: u32,
+    pub particle_count: u32,
+    pub mass_scale: f32,
+    pub mass_quantum: f32,
+    pub quadrupole: u32,
+    pub deposit_levels: u32,
+}
+
+impl MassPushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+/// A pass of `reduce.comp` building the level at `parent_offset` in the
+/// fixed point sums from the one at `child_offset`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct ReducePushConstants {
+    pub child_offset: u32,
+    pub child_size: u32,
+    pub parent_offset: u32,
+    pub parent_size: u32,
+    pub quadrupole: u32,
+}
+
+impl ReducePushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+/// A pass of `refine.comp` picking the tiles above the threshold from the
+/// tile level at `tile_offset` in the fixed point sums, depositing the
+/// particles in them into their patches or resolving the patches.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RefinePushConstants {
+    pub mode: u32,
+    pub particle_count: u32,
+    pub mass_scale: f32,
+    pub mass_quantum: f32,
+    pub quadrupole: u32,
+    pub field_size: u32,
+    pub tile_offset: u32,
+    pub tiles: u32,
+    pub tile_size: u32,
+    pub refine_factor: u32,
+    pub max_patches: u32,
+    pub threshold_low: u32,
+    pub threshold_high: u32,
+}
+
+impl RefinePushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+/// A pass of `domain.comp` finding the domain in `domain_mode`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DomainPushConstants {
+    pub mode: u32,
+    pub particle_count: u32,
+    pub domain_mode: u32,
+    pub percentile: f32,
+    pub margin: f32,
+    pub min_size: f32,
+}
+
+impl DomainPushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct DirectPushConstants {
+    pub particle_count: u32,
+    pub gravitational_constant: f32,
+    pub softening: f32,
+    pub kernel: u32,
+    pub particle_mass: f32,
+    pub adaptive_softening: u32,
+    pub boundary: u32,
+}
+
+impl DirectPushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+/// The `Update` applied by `gravity.comp` to the `particle_count`
+/// particles and the moments of the mass field.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct GravityPushConstants {
+    pub kick: f32,
+    pub drift: f32,
+    pub symplectic: u32,
+    pub quadrupole: u32,
+    pub particle_count: u32,
+}
+
+impl GravityPushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+/// The `Update` applied by `integrate.comp` with the accelerations of the
+/// direct pass.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct IntegratePushConstants {
+    pub particle_count: u32,
+    pub delta_t: f32,
+    pub kick: f32,
+    pub drift: f32,
+    pub symplectic: u32,
+    pub boundary: u32,
+}
+
+impl IntegratePushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+/// A pass of `block.comp` at `tick` of a block step.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct BlockPushConstants {
+    pub mode: u32,
+    pub particle_count: u32,
+    pub tick: u32,
+    pub max_level: u32,
+    pub delta_t: f32,
+    pub eta: f32,
+    pub softening: f32,
+    pub closing: u32,
+    pub boundary: u32,
+}
+
+impl BlockPushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
+
+/// Colour of every species drawn by `shader.vert`, with zero alpha for the
+/// ones coloured by their speed.
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct RenderPushConstants {
+    pub species_colors: [[f32; 4]; globals::MAX_SPECIES],
+}
+
+impl RenderPushConstants {
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts((self as *const Self).cast(), size_of::<Self>()) }
+    }
+}
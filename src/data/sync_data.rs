@@ -1,23 +1,17 @@
 use log::{info, warn};
 use vulkanalia::prelude::v1_0::*;
 
-use crate::{data::globals, init::device};
+use crate::data::globals;
 
 #[derive(Clone, Debug, Default)]
 pub struct SyncData {
     pub image_available_semaphores: Vec<vk::Semaphore>,
-    pub image_clear_finished_semaphores: Vec<vk::Semaphore>,
     pub render_finished_semaphores: Vec<vk::Semaphore>,
 
-    pub gravity_read_finished_semaphores: Vec<vk::Semaphore>,
-    pub mass_compute_finished_semaphores: Vec<vk::Semaphore>,
-
-    pub gravity_compute_finished_semaphores: Vec<vk::Semaphore>,
+    pub compute_step_finished_semaphores: Vec<vk::Semaphore>,
 
     pub in_flight_fences: Vec<vk::Fence>,
     pub images_in_flight: Vec<vk::Fence>,
-
-    pub first_gravity_compute: bool,
 }
 
 impl Drop for SyncData {
@@ -36,17 +30,8 @@ impl Drop for SyncData {
             self.image_available_semaphores
                 .iter()
                 .for_each(|s| globals::get_device().destroy_semaphore(*s, None));
-            self.image_clear_finished_semaphores
-                .iter()
-                .for_each(|s| globals::get_device().destroy_semaphore(*s, None));
 
-            self.gravity_read_finished_semaphores
-                .iter()
-                .for_each(|s| globals::get_device().destroy_semaphore(*s, None));
-            self.mass_compute_finished_semaphores
-                .iter()
-                .for_each(|s| globals::get_device().destroy_semaphore(*s, None));
-            self.gravity_compute_finished_semaphores
+            self.compute_step_finished_semaphores
                 .iter()
                 .for_each(|s| globals::get_device().destroy_semaphore(*s, None));
 
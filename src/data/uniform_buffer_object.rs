@@ -1,5 +1,13 @@
+/// Constants of `gravity.comp` in world units, which it scales to the
+/// domain of the step.
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
 pub struct UniformBufferObject {
     pub delta_t: f32,
+    pub gravitational_constant: f32,
+    pub softening: f32,
+    pub particle_mass: f32,
+    pub kernel: u32,
+    pub adaptive_softening: u32,
+    pub boundary: u32,
 }
@@ -3,16 +3,37 @@ use vulkanalia::vk::{self, HasBuilder};
 
 type Vec2 = cgmath::Vector2<f32>;
 
+/// A particle in the std430 layout of the `Particle` struct of the compute
+/// shaders.
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct Vertex {
     pub pos: Vec2,
     pub velocity: Vec2,
+    pub mass: f32,
+    /// Index into the species of the run, 0 for particles of populations
+    /// without one.
+    pub species: u32,
 }
 
 impl Vertex {
+    /// A massless particle of the default species, which its population
+    /// gives a mass and species once generated.
     pub fn new(pos: Vec2, velocity: Vec2) -> Self {
-        Self { pos, velocity }
+        Self {
+            pos,
+            velocity,
+            mass: 0.0,
+            species: 0,
+        }
+    }
+
+    pub fn with_species(self, species: u32, mass: f32) -> Self {
+        Self {
+            mass,
+            species,
+            ..self
+        }
     }
 
     pub fn binding_description() -> vk::VertexInputBindingDescription {
@@ -23,7 +44,7 @@ impl Vertex {
             .build()
     }
 
-    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 2] {
+    pub fn attribute_descriptions() -> [vk::VertexInputAttributeDescription; 4] {
         let pos = vk::VertexInputAttributeDescription::builder()
             .binding(0)
             .location(0)
@@ -38,6 +59,20 @@ impl Vertex {
             .offset(size_of::<Vec2>() as u32)
             .build();
 
-        [pos, velocity]
+        let mass = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(2)
+            .format(vk::Format::R32_SFLOAT)
+            .offset((size_of::<Vec2>() * 2) as u32)
+            .build();
+
+        let species = vk::VertexInputAttributeDescription::builder()
+            .binding(0)
+            .location(3)
+            .format(vk::Format::R32_UINT)
+            .offset((size_of::<Vec2>() * 2 + size_of::<f32>()) as u32)
+            .build();
+
+        [pos, velocity, mass, species]
     }
 }
@@ -1 +1,20 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
 pub mod random_generator;
+
+/// Initial particle distributions provided by `random_generator`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GeneratorKind {
+    /// Uniformly spread particles in [-1, 1] with a rotating velocity field.
+    Random,
+    /// Two rectangular clusters with small random velocities.
+    TwoClusters,
+    /// A single ring of `radius` and `thickness` around the origin at rest.
+    CircularCluster,
+    /// Two rings of `radius` and `thickness` side by side.
+    TwoCircularClusters,
+    /// A uniform disk of `radius` rotating rigidly with `angular-velocity`.
+    Disk,
+}
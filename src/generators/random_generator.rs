@@ -1,15 +1,14 @@
 use std::f32::consts::PI;
 
 use crate::data::vertex::Vertex;
+use crate::generators::GeneratorKind;
 use cgmath::{num_traits::Pow, vec2, InnerSpace};
 use rand::Rng;
 
-#[allow(dead_code)]
-pub fn generate_random_vertices(count: u32) -> Vec<Vertex> {
+pub fn generate_random_vertices(count: u32, rng: &mut impl Rng) -> Vec<Vertex> {
     let mut vertices: Vec<Vertex> = vec![];
-    let mut rng = rand::thread_rng();
     let center = vec2(0.0, 0.0);
-    let rot = (-PI / 2.0) as f32;
+    let rot = -PI / 2.0;
 
     for _ in 0..count {
         let pos = vec2(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
@@ -27,10 +26,8 @@ pub fn generate_random_vertices(count: u32) -> Vec<Vertex> {
     vertices
 }
 
-#[allow(dead_code)]
-pub fn generate_two_clusters(count: u32) -> Vec<Vertex> {
+pub fn generate_two_clusters(count: u32, rng: &mut impl Rng) -> Vec<Vertex> {
     let mut vertices: Vec<Vertex> = vec![];
-    let mut rng = rand::thread_rng();
 
     for _ in 0..(count / 2) {
         vertices.push(Vertex::new(
@@ -54,10 +51,13 @@ fn get_circle_pos(radius: f32, x: f32, offset: f32) -> f32 {
     pos.sqrt()
 }
 
-#[allow(dead_code)]
-pub fn generate_circular_cluster(count: u32, radius: f32, thickness: f32) -> Vec<Vertex> {
+pub fn generate_circular_cluster(
+    count: u32,
+    radius: f32,
+    thickness: f32,
+    rng: &mut impl Rng,
+) -> Vec<Vertex> {
     let mut vertices: Vec<Vertex> = vec![];
-    let mut rng = rand::thread_rng();
     let offset = 0.0;
 
     for _ in 0..count {
@@ -78,10 +78,13 @@ pub fn generate_circular_cluster(count: u32, radius: f32, thickness: f32) -> Vec
     vertices
 }
 
-#[allow(dead_code)]
-pub fn generate_2_circular_clusters(count: u32, radius: f32, thickness: f32) -> Vec<Vertex> {
+pub fn generate_2_circular_clusters(
+    count: u32,
+    radius: f32,
+    thickness: f32,
+    rng: &mut impl Rng,
+) -> Vec<Vertex> {
     let mut vertices: Vec<Vertex> = vec![];
-    let mut rng = rand::thread_rng();
     let offset = -0.5;
 
     for _ in 0..(count / 2) {
@@ -109,3 +112,45 @@ pub fn generate_2_circular_clusters(count: u32, radius: f32, thickness: f32) ->
 
     vertices
 }
+
+pub fn generate_disk(
+    count: u32,
+    radius: f32,
+    angular_velocity: f32,
+    rng: &mut impl Rng,
+) -> Vec<Vertex> {
+    let mut vertices: Vec<Vertex> = vec![];
+
+    for _ in 0..count {
+        let distance = radius * rng.gen::<f32>().sqrt();
+        let angle = rng.gen_range(0.0..(2.0 * PI));
+        let direction = vec2(angle.cos(), angle.sin());
+        let tangent = vec2(-direction.y, direction.x);
+
+        vertices.push(Vertex::new(
+            direction * distance,
+            tangent * distance * angular_velocity,
+        ));
+    }
+
+    vertices
+}
+
+pub fn generate(
+    kind: GeneratorKind,
+    count: u32,
+    radius: f32,
+    thickness: f32,
+    angular_velocity: f32,
+    rng: &mut impl Rng,
+) -> Vec<Vertex> {
+    match kind {
+        GeneratorKind::Disk => generate_disk(count, radius, angular_velocity, rng),
+        GeneratorKind::Random => generate_random_vertices(count, rng),
+        GeneratorKind::TwoClusters => generate_two_clusters(count, rng),
+        GeneratorKind::CircularCluster => generate_circular_cluster(count, radius, thickness, rng),
+        GeneratorKind::TwoCircularClusters => {
+            generate_2_circular_clusters(count, radius, thickness, rng)
+        }
+    }
+}
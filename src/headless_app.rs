@@ -0,0 +1,485 @@
+use anyhow::{anyhow, Ok, Result};
+
+use log::info;
+use std::mem::{size_of, size_of_val};
+use std::path::PathBuf;
+use std::ptr::copy_nonoverlapping as memcpy;
+use std::time::{Duration, Instant};
+use vulkanalia::loader::{LibloadingLoader, LIBRARY};
+use vulkanalia::prelude::v1_0::*;
+use vulkanalia::vk::ExtDebugUtilsExtension;
+
+use crate::config::units::{self, Units};
+use crate::config::{Boundary, Integrator, Physics, RunConfig};
+use crate::cpu::boundary;
+use crate::data::buffers_data::BuffersData;
+use crate::data::commands_data::CommandsData;
+use crate::data::common_data::CommonData;
+use crate::data::descriptors_data::DescriptorsData;
+use crate::data::globals;
+use crate::data::pipeline_data::PipelineData;
+use crate::data::push_constants::DirectPushConstants;
+use crate::data::sync_data::SyncData;
+use crate::data::vertex::Vertex;
+use crate::init::{buffers, commands, descriptors, device, instance, pipeline, sync};
+use crate::integrators::Update;
+use crate::snapshot::{self, Snapshot, SnapshotHeader};
+use crate::solvers::mass_field::MassFieldSolver;
+use crate::solvers::{self, direct, GravitySolver};
+use crate::utils::resources::{self, memory_barrier};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Runs the steps of a solver without a window, surface or swapchain, so
+/// the simulation can be driven on machines with no display.
+#[derive(Debug)]
+pub struct HeadlessApp {
+    instance: Instance,
+    step: u64,
+    sim_time: f64,
+    delta_t: f32,
+    physics: Physics,
+    integrator: Integrator,
+    seed: Option<u64>,
+    particle_count: usize,
+    report_every: u64,
+    units: Option<Units>,
+    snapshot_every: Option<u64>,
+    snapshot_dir: PathBuf,
+
+    _entry: Entry,
+    buffers: BuffersData,
+    common: CommonData,
+    commands: CommandsData,
+    sync: SyncData,
+    solver: Box<dyn GravitySolver>,
+}
+
+impl HeadlessApp {
+    pub unsafe fn create(initial: Snapshot, config: &RunConfig) -> Result<Self> {
+        let Snapshot { header, vertices } = initial;
+
+        let loader = LibloadingLoader::new(LIBRARY)?;
+        let entry = Entry::new(loader).map_err(|b| anyhow!("{}", b))?;
+
+        let mut buffers = BuffersData::default();
+        let mut common = CommonData {
+            headless: true,
+            ..Default::default()
+        };
+        let mut commands = CommandsData::default();
+        let mut sync = SyncData::default();
+
+        let instance = instance::create_instance(None, &entry, &mut common)?;
+
+        device::pick_physical_device(&instance, &mut common)?;
+        let device = device::create_logical_device(&instance, &mut common)?;
+        globals::set_device(&device);
+
+        commands.main_command_pool = commands::create_command_pool(&instance, &common)?;
+
+        buffers::create_shader_storage_buffers(
+            &instance,
+            &vertices,
+            &common,
+            &commands,
+            &mut buffers,
+        )?;
+
+        let solver = solvers::create_solver(
+            &instance,
+            &common,
+            &commands,
+            &buffers.storage_buffers,
+            &vertices,
+            config,
+        )?;
+
+        commands.compute_step_command_buffers = commands::create_command_buffers(
+            globals::MAX_FRAMES_IN_FLIGHT,
+            commands.main_command_pool,
+        )?;
+
+        sync::create_headless_sync_objects(&mut sync)?;
+        let mut _self = Self {
+            _entry: entry,
+            instance,
+            step: header.step,
+            sim_time: header.sim_time,
+            delta_t: config.dt.unwrap_or(globals::DEFAULT_DELTA_T),
+            physics: config.physics,
+            integrator: config.integrator,
+            seed: config.seed,
+            particle_count: vertices.len(),
+            report_every: config
+                .report_every
+                .unwrap_or(globals::HEADLESS_REPORT_EVERY),
+            units: config.units.clone(),
+            snapshot_every: config.snapshot_every,
+            snapshot_dir: config.snapshot_dir.clone(),
+            buffers,
+            common,
+            commands,
+            sync,
+            solver,
+        };
+
+        for frame in 0..globals::MAX_FRAMES_IN_FLIGHT {
+            if _self.solver.steps_on_device() {
+                _self.solver.prepare(frame, _self.delta_t)?;
+            }
+            _self.record_compute_step_command_buffer(frame)?;
+        }
+
+        // The first step reads storage buffer `(step + 1) % 2`, which gets
+        // the velocities half a step back.
+        if config.integrator.staggered() {
+            let frame = ((_self.step + 1) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+            _self.kick(frame, -Update::lag(_self.integrator))?;
+        }
+
+        Ok(_self)
+    }
+
+    /// Advances the simulation by `steps` steps. Steps of solvers running
+    /// on the device are submitted in batches so the host only waits for the
+    /// device once per batch.
+    /// Progress is reported every `report_every` steps and snapshots are
+    /// written every `snapshot_every` steps and after the last one.
+    pub unsafe fn run(&mut self, steps: u64) -> Result<()> {
+        let start = Instant::now();
+        let mut done = 0;
+
+        while done < steps {
+            let until_report = self.report_every - (done % self.report_every);
+            let until_snapshot = snapshot::steps_until_snapshot(self.step, self.snapshot_every);
+            let steps_per_submit = if self.solver.steps_on_device() {
+                globals::HEADLESS_STEPS_PER_SUBMIT
+            } else {
+                1
+            };
+            let batch = (steps - done)
+                .min(until_report)
+                .min(until_snapshot)
+                .min(steps_per_submit);
+
+            self.submit_steps(batch)?;
+            done += batch;
+
+            if done.is_multiple_of(self.report_every) || done == steps {
+                info!(
+                    "step {}/{}, sim time {} ({:.1} steps/s)",
+                    done,
+                    steps,
+                    units::format_time(self.sim_time, self.units.as_ref()),
+                    done as f64 / start.elapsed().as_secs_f64()
+                );
+
+                if self.physics.boundary == Boundary::Absorbing {
+                    info!("{} particles absorbed", self.removed_particles()?);
+                }
+            }
+
+            if let Some(every) = self.snapshot_every {
+                if self.step.is_multiple_of(every) || done == steps {
+                    let path = self.snapshot()?.save_to_dir(&self.snapshot_dir)?;
+                    info!("wrote {}", path.display());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Counts the particles of the latest step with non-finite positions,
+    /// which the absorbing boundary removed. Staggered velocities do not
+    /// matter for the positions, so nothing is kicked.
+    unsafe fn removed_particles(&self) -> Result<usize> {
+        let latest = ((self.step + 1) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+        let vertices = buffers::read_shader_storage_buffer(
+            &self.instance,
+            &self.common,
+            &self.commands,
+            self.buffers.storage_buffers[latest],
+            self.particle_count,
+        )?;
+
+        Ok(boundary::removed(&vertices))
+    }
+
+    /// Reads the particles written by the latest step back from the device.
+    pub unsafe fn snapshot(&mut self) -> Result<Snapshot> {
+        // Step `s` writes storage buffer `s % 2` and the next one reads the
+        // other buffer, which before the first step holds the initial state.
+        // Staggered velocities are kicked forward into the buffer the next
+        // step overwrites and read from there.
+        let latest = if self.integrator.staggered() {
+            let frame = (self.step % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+            self.kick(frame, Update::lag(self.integrator))?;
+            frame
+        } else {
+            ((self.step + 1) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize
+        };
+        let vertices = buffers::read_shader_storage_buffer(
+            &self.instance,
+            &self.common,
+            &self.commands,
+            self.buffers.storage_buffers[latest],
+            self.particle_count,
+        )?;
+
+        Ok(Snapshot {
+            header: SnapshotHeader {
+                particle_count: vertices.len() as u64,
+                step: self.step,
+                sim_time: self.sim_time,
+                seed: self.seed,
+                dt: self.delta_t,
+                physics: self.physics,
+            },
+            vertices,
+        })
+    }
+
+    /// Deposits the particles of the latest step with the mass field passes
+    /// of `config` and returns the fixed point mass summed over each level.
+    pub unsafe fn accumulated_mass(
+        &self,
+        vertices: &[Vertex],
+        config: &RunConfig,
+    ) -> Result<Vec<u64>> {
+        let latest = ((self.step + 1) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+        let mut solver = MassFieldSolver::create(
+            &self.instance,
+            &self.common,
+            &self.commands,
+            &self.buffers.storage_buffers,
+            vertices,
+            config,
+        )?;
+        let totals = solver.accumulated_mass(&self.instance, &self.common, &self.commands, latest);
+        solver.destroy();
+
+        totals
+    }
+
+    /// Time of `repeat` deposits of `vertices` into the mass field of
+    /// `config` on the device, with the first `deposit_levels` levels
+    /// deposited into and the others reduced from them.
+    pub unsafe fn deposit_time(
+        &self,
+        vertices: &[Vertex],
+        config: &RunConfig,
+        deposit_levels: u32,
+        repeat: u32,
+    ) -> Result<Duration> {
+        let latest = ((self.step + 1) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+        let mut solver = MassFieldSolver::create(
+            &self.instance,
+            &self.common,
+            &self.commands,
+            &self.buffers.storage_buffers,
+            vertices,
+            config,
+        )?;
+        let time =
+            solver.deposit_time(&self.common, &self.commands, latest, deposit_levels, repeat);
+        solver.destroy();
+
+        time
+    }
+
+    /// Sums the forces of all particles of the latest step on the `targets`
+    /// particles with the direct summation pipeline.
+    pub unsafe fn direct_accelerations(&self, targets: &[u32]) -> Result<Vec<Vec2>> {
+        let latest = ((self.step + 1) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+        let particles_size = (self.particle_count * size_of::<Vertex>()) as u64;
+        let target_list = direct::target_list(targets);
+        let targets_size = size_of_val(target_list.as_slice()) as u64;
+        let accelerations_size = (targets.len() * size_of::<Vec2>()) as u64;
+
+        let host_visible =
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE;
+        let (targets_buffer, targets_memory) = resources::create_buffer(
+            &self.instance,
+            &self.common,
+            targets_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            host_visible,
+        )?;
+        let (accelerations_buffer, accelerations_memory) = resources::create_buffer(
+            &self.instance,
+            &self.common,
+            accelerations_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            host_visible,
+        )?;
+
+        let memory = globals::get_device().map_memory(
+            targets_memory,
+            0,
+            targets_size,
+            vk::MemoryMapFlags::empty(),
+        )?;
+        memcpy(target_list.as_ptr(), memory.cast(), target_list.len());
+        globals::get_device().unmap_memory(targets_memory);
+
+        let mut descriptors = DescriptorsData::default();
+        let mut pipeline = PipelineData::default();
+
+        descriptors.descriptor_set_layout = descriptors::create_direct_descriptor_set_layout()?;
+        descriptors.descriptor_pool = descriptors::create_direct_descriptor_pool(1)?;
+        descriptors::create_storage_descriptor_set(
+            [
+                (self.buffers.storage_buffers[latest], particles_size),
+                (targets_buffer, targets_size),
+                (accelerations_buffer, accelerations_size),
+            ],
+            &mut descriptors,
+        )?;
+        pipeline::create_direct_compute_pipeline(&descriptors, &mut pipeline)?;
+
+        let push_constants = DirectPushConstants {
+            particle_count: self.particle_count as u32,
+            gravitational_constant: self.physics.field_gravitational_constant(),
+            softening: self.physics.field_softening(),
+            kernel: self.physics.kernel as u32,
+            particle_mass: self.physics.particle_mass,
+            adaptive_softening: self.physics.adaptive_softening as u32,
+            boundary: self.physics.boundary as u32,
+        };
+
+        let command_buffer = resources::begin_single_time_commands(&self.commands)?;
+
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::HOST,
+            vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::HOST_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            pipeline.pipeline_layout,
+            0,
+            &descriptors.descriptor_sets,
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            push_constants.as_bytes(),
+        );
+
+        let group_count = (targets.len() as f32 / 256.0).ceil() as u32;
+        globals::get_device().cmd_dispatch(command_buffer, group_count, 1, 1);
+
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::HOST,
+            vk::AccessFlags::HOST_READ,
+        );
+
+        resources::end_single_time_commands(&self.common, &self.commands, command_buffer)?;
+
+        let memory = globals::get_device().map_memory(
+            accelerations_memory,
+            0,
+            accelerations_size,
+            vk::MemoryMapFlags::empty(),
+        )?;
+
+        let mut accelerations = Vec::with_capacity(targets.len());
+        memcpy(memory.cast(), accelerations.as_mut_ptr(), targets.len());
+        accelerations.set_len(targets.len());
+
+        globals::get_device().unmap_memory(accelerations_memory);
+        globals::get_device().destroy_buffer(targets_buffer, None);
+        globals::get_device().free_memory(targets_memory, None);
+        globals::get_device().destroy_buffer(accelerations_buffer, None);
+        globals::get_device().free_memory(accelerations_memory, None);
+
+        Ok(accelerations)
+    }
+
+    /// Records a kick of the velocities by `kick` steps into the storage
+    /// buffer of `frame` and waits for it.
+    unsafe fn kick(&mut self, frame: usize, kick: f32) -> Result<()> {
+        let command_buffer = resources::begin_single_time_commands(&self.commands)?;
+        self.solver.record_kick(command_buffer, frame, kick)?;
+        resources::end_single_time_commands(&self.common, &self.commands, command_buffer)
+    }
+
+    unsafe fn submit_steps(&mut self, count: u64) -> Result<()> {
+        // Solvers stepping on the host submit one step at a time.
+        let delta_t = if self.solver.steps_on_device() {
+            self.delta_t
+        } else {
+            let frame = (self.step % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+            self.solver.prepare(frame, self.delta_t)?
+        };
+
+        let command_buffers = (0..count)
+            .map(|i| {
+                let frame = ((self.step + i) % globals::MAX_FRAMES_IN_FLIGHT as u64) as usize;
+                self.commands.compute_step_command_buffers[frame]
+            })
+            .collect::<Vec<_>>();
+
+        let submit_info = vk::SubmitInfo::builder().command_buffers(&command_buffers);
+        let fence = self.sync.in_flight_fences[0];
+
+        globals::get_device().reset_fences(&[fence])?;
+        globals::get_device().queue_submit(self.common.compute_queue, &[submit_info], fence)?;
+        globals::get_device().wait_for_fences(&[fence], true, u64::MAX)?;
+
+        self.step += count;
+        self.sim_time += count as f64 * delta_t as f64;
+        Ok(())
+    }
+
+    /// Records one full simulation step for `frame`. The command buffer
+    /// reads the particles written by the other frame, so consecutive steps
+    /// alternate between the two recorded buffers.
+    unsafe fn record_compute_step_command_buffer(&mut self, frame: usize) -> Result<()> {
+        let command_buffer = self.commands.compute_step_command_buffers[frame];
+
+        let info = vk::CommandBufferBeginInfo::builder()
+            .flags(vk::CommandBufferUsageFlags::SIMULTANEOUS_USE);
+
+        globals::get_device().begin_command_buffer(command_buffer, &info)?;
+        self.solver.record(command_buffer, frame)?;
+        globals::get_device().end_command_buffer(command_buffer)?;
+
+        Ok(())
+    }
+
+    pub unsafe fn destroy(&mut self) {
+        self.commands = CommandsData::default();
+        self.solver.destroy();
+        self.buffers = BuffersData::default();
+        self.sync = SyncData::default();
+
+        globals::destroy_device();
+
+        if globals::VALIDATION_ENABLED {
+            self.instance
+                .destroy_debug_utils_messenger_ext(self.common.messenger, None);
+        }
+
+        self.instance.destroy_instance(None);
+    }
+}
@@ -1,25 +1,25 @@
 use anyhow::Result;
+use std::mem::{size_of, size_of_val};
 use std::ptr::copy_nonoverlapping as memcpy;
-use std::{cmp::max, mem::size_of};
 use vulkanalia::prelude::v1_0::*;
 
 use crate::data::image_data::ImageData;
 use crate::{
     data::{
         buffers_data::BuffersData, commands_data::CommandsData, common_data::CommonData, globals,
-        swapchain_data::SwapchainData, uniform_buffer_object::UniformBufferObject, vertex::Vertex,
+        uniform_buffer_object::UniformBufferObject, vertex::Vertex,
     },
     utils::resources,
 };
 
 pub unsafe fn create_shader_storage_buffers(
     instance: &Instance,
-    vertices: &Vec<Vertex>,
+    vertices: &[Vertex],
     common: &CommonData,
     commands: &CommandsData,
     buffers: &mut BuffersData,
 ) -> Result<()> {
-    let size = (size_of::<Vertex>() * vertices.len()) as u64;
+    let size = size_of_val(vertices) as u64;
     let mut storage_buffers = vec![];
     let mut storage_buffer_memories = vec![];
 
@@ -46,6 +46,7 @@ pub unsafe fn create_shader_storage_buffers(
             common,
             size,
             vk::BufferUsageFlags::TRANSFER_DST
+                | vk::BufferUsageFlags::TRANSFER_SRC
                 | vk::BufferUsageFlags::VERTEX_BUFFER
                 | vk::BufferUsageFlags::STORAGE_BUFFER,
             vk::MemoryPropertyFlags::DEVICE_LOCAL,
@@ -65,16 +66,105 @@ pub unsafe fn create_shader_storage_buffers(
     Ok(())
 }
 
+/// Creates a host visible buffer per frame in flight that particles
+/// computed on the host are copied from into the storage buffers.
+pub unsafe fn create_upload_buffers(
+    instance: &Instance,
+    vertices: &[Vertex],
+    common: &CommonData,
+    buffers: &mut BuffersData,
+) -> Result<()> {
+    for _ in 0..globals::MAX_FRAMES_IN_FLIGHT {
+        let (upload_buffer, upload_buffer_memory) = resources::create_buffer(
+            instance,
+            common,
+            size_of_val(vertices) as u64,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+        )?;
+
+        buffers.upload_buffers.push(upload_buffer);
+        buffers.upload_buffer_memories.push(upload_buffer_memory);
+    }
+
+    Ok(())
+}
+
+/// Copies the first `count` elements of a storage buffer back to the host,
+/// like the particles of one of the storage buffers. The device must not be
+/// writing to the buffer while it is read.
+pub unsafe fn read_shader_storage_buffer<T: Copy>(
+    instance: &Instance,
+    common: &CommonData,
+    commands: &CommandsData,
+    storage_buffer: vk::Buffer,
+    count: usize,
+) -> Result<Vec<T>> {
+    let size = (count * size_of::<T>()) as u64;
+
+    let (staging_buffer, staging_buffer_memory) = resources::create_buffer(
+        instance,
+        common,
+        size,
+        vk::BufferUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE,
+    )?;
+
+    let command_buffer = resources::begin_single_time_commands(commands)?;
+
+    resources::memory_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::COMPUTE_SHADER,
+        vk::AccessFlags::SHADER_WRITE,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::AccessFlags::TRANSFER_READ,
+    );
+
+    let regions = vk::BufferCopy::builder().size(size);
+    globals::get_device().cmd_copy_buffer(
+        command_buffer,
+        storage_buffer,
+        staging_buffer,
+        &[regions],
+    );
+
+    resources::memory_barrier(
+        command_buffer,
+        vk::PipelineStageFlags::TRANSFER,
+        vk::AccessFlags::TRANSFER_WRITE,
+        vk::PipelineStageFlags::HOST,
+        vk::AccessFlags::HOST_READ,
+    );
+
+    resources::end_single_time_commands(common, commands, command_buffer)?;
+
+    let memory = globals::get_device().map_memory(
+        staging_buffer_memory,
+        0,
+        size,
+        vk::MemoryMapFlags::empty(),
+    )?;
+
+    let mut elements = Vec::with_capacity(count);
+    memcpy(memory.cast(), elements.as_mut_ptr(), count);
+    elements.set_len(count);
+
+    globals::get_device().unmap_memory(staging_buffer_memory);
+    globals::get_device().destroy_buffer(staging_buffer, None);
+    globals::get_device().free_memory(staging_buffer_memory, None);
+
+    Ok(elements)
+}
+
 pub unsafe fn create_uniform_buffers(
     instance: &Instance,
     common: &CommonData,
-    swapchain: &SwapchainData,
     buffers: &mut BuffersData,
 ) -> Result<()> {
     buffers.uniform_buffers.clear();
     buffers.uniform_buffers_memory.clear();
 
-    for _ in 0..swapchain.swapchain_images.len() {
+    for _ in 0..globals::MAX_FRAMES_IN_FLIGHT {
         let (uniform_buffer, uniform_buffer_memory) = resources::create_buffer(
             instance,
             common,
@@ -90,16 +180,18 @@ pub unsafe fn create_uniform_buffers(
     Ok(())
 }
 
+/// The images of every level of `sizes` for each frame.
 pub unsafe fn create_offscreen_images(
     instance: &Instance,
     common: &CommonData,
     commands: &CommandsData,
+    sizes: &[u32],
 ) -> Result<Vec<Vec<ImageData>>> {
     let mut image_sets = vec![];
 
     for _ in 0..globals::MAX_FRAMES_IN_FLIGHT {
         image_sets.push(create_downsampled_images(
-            instance, common, commands,
+            instance, common, commands, sizes,
         )?);
     }
 
@@ -110,20 +202,16 @@ unsafe fn create_downsampled_images(
     instance: &Instance,
     common: &CommonData,
     commands: &CommandsData,
+    sizes: &[u32],
 ) -> Result<Vec<ImageData>> {
     let mut images = vec![];
 
-    let mut width = globals::MASS_FIELD_SIZE;
-    let mut height = globals::MASS_FIELD_SIZE;
-
-    let min_len = globals::SHADER_FORCE_REGION_RADIUS / 2 + 1;
-
-    loop {
+    for &size in sizes {
         let (image, image_memory) = resources::create_image(
             instance,
             common,
-            width,
-            height,
+            size,
+            size,
             1,
             vk::SampleCountFlags::_1,
             vk::Format::R32G32B32A32_SFLOAT,
@@ -155,13 +243,6 @@ unsafe fn create_downsampled_images(
             image_memory,
             image_view,
         });
-
-        if width <= min_len && height <= min_len {
-            break;
-        }
-
-        height = max(1, height / globals::MIP_LEVEL_DOWNSAMLING);
-        width = max(1, width / globals::MIP_LEVEL_DOWNSAMLING);
     }
 
     Ok(images)
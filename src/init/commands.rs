@@ -1,7 +1,10 @@
 use anyhow::Result;
 use vulkanalia::prelude::v1_0::*;
 
-use crate::{data::{common_data::CommonData, globals}, utils::queue_family_indices::QueueFamilyIndices};
+use crate::{
+    data::{common_data::CommonData, globals},
+    utils::queue_family_indices::QueueFamilyIndices,
+};
 
 pub unsafe fn create_command_pool(
     instance: &Instance,
@@ -1,14 +1,16 @@
-use std::mem::size_of;
+use std::mem::{size_of, size_of_val};
 
 use anyhow::{Ok, Result};
 use vulkanalia::prelude::v1_0::*;
 
 use crate::data::{
-    buffers_data::BuffersData, descriptors_data::DescriptorsData, globals,
-    swapchain_data::SwapchainData, uniform_buffer_object::UniformBufferObject, vertex::Vertex,
+    buffers_data::BuffersData, descriptors_data::DescriptorsData, globals, image_data::ImageData,
+    uniform_buffer_object::UniformBufferObject, vertex::Vertex,
 };
 
-pub unsafe fn create_gravity_descriptor_set_layout() -> Result<vk::DescriptorSetLayout> {
+/// Binds an image of each of the `levels` of the mass field and the
+/// refinement patches.
+pub unsafe fn create_gravity_descriptor_set_layout(levels: u32) -> Result<vk::DescriptorSetLayout> {
     let storage_binding = vk::DescriptorSetLayoutBinding::builder()
         .binding(0)
         .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
@@ -24,7 +26,7 @@ pub unsafe fn create_gravity_descriptor_set_layout() -> Result<vk::DescriptorSet
     let image_storage_binding = vk::DescriptorSetLayoutBinding::builder()
         .binding(3)
         .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
-        .descriptor_count(globals::MAX_MIP_LEVELS)
+        .descriptor_count(levels)
         .stage_flags(vk::ShaderStageFlags::COMPUTE);
 
     let bindings = &[
@@ -32,12 +34,17 @@ pub unsafe fn create_gravity_descriptor_set_layout() -> Result<vk::DescriptorSet
         storage_binding.binding(1),
         ubo_binding,
         image_storage_binding,
+        storage_binding.binding(4),
+        image_storage_binding.binding(5),
+        storage_binding.binding(6),
+        storage_binding.binding(7),
     ];
     let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
     Ok(globals::get_device().create_descriptor_set_layout(&info, None)?)
 }
 
-pub unsafe fn create_mass_descriptor_set_layout() -> Result<vk::DescriptorSetLayout> {
+/// Binds an image of each of the `levels` of the mass field.
+pub unsafe fn create_mass_descriptor_set_layout(levels: u32) -> Result<vk::DescriptorSetLayout> {
     let storage_binding = vk::DescriptorSetLayoutBinding::builder()
         .binding(0)
         .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
@@ -47,62 +54,82 @@ pub unsafe fn create_mass_descriptor_set_layout() -> Result<vk::DescriptorSetLay
     let image_storage_binding = vk::DescriptorSetLayoutBinding::builder()
         .binding(1)
         .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
-        .descriptor_count(globals::MAX_MIP_LEVELS)
+        .descriptor_count(levels)
         .stage_flags(vk::ShaderStageFlags::COMPUTE);
 
-    let bindings = &[storage_binding, image_storage_binding];
+    let bindings = &[
+        storage_binding,
+        image_storage_binding,
+        storage_binding.binding(2),
+        storage_binding.binding(3),
+        image_storage_binding.binding(4),
+    ];
     let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
     Ok(globals::get_device().create_descriptor_set_layout(&info, None)?)
 }
 
-pub unsafe fn create_gravity_descriptor_pool(
-    swapchain: &SwapchainData,
-) -> Result<vk::DescriptorPool> {
+pub unsafe fn create_gravity_descriptor_pool(levels: u32) -> Result<vk::DescriptorPool> {
+    let sets = globals::MAX_FRAMES_IN_FLIGHT as u32;
     let storage_buffer_size = vk::DescriptorPoolSize::builder()
         .type_(vk::DescriptorType::STORAGE_BUFFER)
-        .descriptor_count(swapchain.swapchain_images.len() as u32);
+        .descriptor_count(sets);
 
     let ubo_size = vk::DescriptorPoolSize::builder()
         .type_(vk::DescriptorType::UNIFORM_BUFFER)
-        .descriptor_count(swapchain.swapchain_images.len() as u32);
+        .descriptor_count(sets);
 
     let image_storage_buffer_size = vk::DescriptorPoolSize::builder()
         .type_(vk::DescriptorType::STORAGE_IMAGE)
-        .descriptor_count(swapchain.swapchain_images.len() as u32 * globals::MAX_MIP_LEVELS);
+        .descriptor_count(sets * levels);
 
     let pool_sizes = &[
         storage_buffer_size,
         storage_buffer_size,
         ubo_size,
         image_storage_buffer_size,
+        storage_buffer_size,
+        image_storage_buffer_size,
+        storage_buffer_size,
+        storage_buffer_size,
     ];
     let info = vk::DescriptorPoolCreateInfo::builder()
         .pool_sizes(pool_sizes)
-        .max_sets(swapchain.swapchain_images.len() as u32);
+        .max_sets(sets);
 
     Ok(globals::get_device().create_descriptor_pool(&info, None)?)
 }
 
-pub unsafe fn create_mass_descriptor_pool(swapchain: &SwapchainData) -> Result<vk::DescriptorPool> {
+pub unsafe fn create_mass_descriptor_pool(levels: u32) -> Result<vk::DescriptorPool> {
+    let sets = globals::MAX_FRAMES_IN_FLIGHT as u32;
     let storage_buffer_size = vk::DescriptorPoolSize::builder()
         .type_(vk::DescriptorType::STORAGE_BUFFER)
-        .descriptor_count(swapchain.swapchain_images.len() as u32);
+        .descriptor_count(sets);
 
     let image_storage_buffer_size = vk::DescriptorPoolSize::builder()
         .type_(vk::DescriptorType::STORAGE_IMAGE)
-        .descriptor_count(swapchain.swapchain_images.len() as u32 * globals::MAX_MIP_LEVELS);
+        .descriptor_count(sets * levels);
 
-    let pool_sizes = &[storage_buffer_size, image_storage_buffer_size];
+    let pool_sizes = &[
+        storage_buffer_size,
+        image_storage_buffer_size,
+        storage_buffer_size,
+        storage_buffer_size,
+        image_storage_buffer_size,
+    ];
     let info = vk::DescriptorPoolCreateInfo::builder()
         .pool_sizes(pool_sizes)
-        .max_sets(swapchain.swapchain_images.len() as u32);
+        .max_sets(sets);
 
     Ok(globals::get_device().create_descriptor_pool(&info, None)?)
 }
 
 pub unsafe fn create_gravity_descriptor_sets(
+    storage_buffers: &[vk::Buffer],
+    domain_buffers: &[(vk::Buffer, u64)],
+    patch_maps: &[(vk::Buffer, u64)],
+    patch_cells: &[(vk::Buffer, u64)],
     buffers: &BuffersData,
-    vertices: &Vec<Vertex>,
+    vertices: &[Vertex],
     descriptors: &mut DescriptorsData,
 ) -> Result<()> {
     let layouts = vec![descriptors.descriptor_set_layout; globals::MAX_FRAMES_IN_FLIGHT];
@@ -127,9 +154,9 @@ pub unsafe fn create_gravity_descriptor_sets(
             .buffer_info(buffer_info);
 
         let storage_last_frame_info = vk::DescriptorBufferInfo::builder()
-            .buffer(buffers.storage_buffers[(i + 1) % globals::MAX_FRAMES_IN_FLIGHT])
+            .buffer(storage_buffers[(i + 1) % globals::MAX_FRAMES_IN_FLIGHT])
             .offset(0)
-            .range((size_of::<Vertex>() * vertices.len()) as u64);
+            .range(size_of_val(vertices) as u64);
 
         let storage_infos = &[storage_last_frame_info];
         let ssbo_last_frame_write = vk::WriteDescriptorSet::builder()
@@ -140,9 +167,9 @@ pub unsafe fn create_gravity_descriptor_sets(
             .buffer_info(storage_infos);
 
         let storage_curr_frame_info = vk::DescriptorBufferInfo::builder()
-            .buffer(buffers.storage_buffers[i])
+            .buffer(storage_buffers[i])
             .offset(0)
-            .range((size_of::<Vertex>() * vertices.len()) as u64);
+            .range(size_of_val(vertices) as u64);
 
         let storage_infos = &[storage_curr_frame_info];
         let ssbo_curr_frame_write = vk::WriteDescriptorSet::builder()
@@ -152,14 +179,8 @@ pub unsafe fn create_gravity_descriptor_sets(
             .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
             .buffer_info(storage_infos);
 
-        let storage_image_infos = buffers.offscreen_images[i]
-            .iter()
-            .map(|image| {
-                vk::DescriptorImageInfo::builder()
-                    .image_view(image.image_view)
-                    .image_layout(vk::ImageLayout::GENERAL)
-            })
-            .collect::<Vec<_>>();
+        let storage_image_infos = image_infos(&buffers.offscreen_images[i]);
+        let moment_image_infos = image_infos(moment_images(buffers, i));
 
         let storage_image_write = vk::WriteDescriptorSet::builder()
             .dst_set(descriptors.descriptor_sets[i])
@@ -168,12 +189,65 @@ pub unsafe fn create_gravity_descriptor_sets(
             .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
             .image_info(&storage_image_infos);
 
+        let (domain_buffer, domain_size) = domain_buffers[i];
+        let domain_info = vk::DescriptorBufferInfo::builder()
+            .buffer(domain_buffer)
+            .offset(0)
+            .range(domain_size);
+
+        let domain_infos = &[domain_info];
+        let domain_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptors.descriptor_sets[i])
+            .dst_binding(4)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(domain_infos);
+
+        let moment_image_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptors.descriptor_sets[i])
+            .dst_binding(5)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .image_info(&moment_image_infos);
+
+        let (patch_map_buffer, patch_map_size) = patch_maps[i];
+        let patch_map_info = vk::DescriptorBufferInfo::builder()
+            .buffer(patch_map_buffer)
+            .offset(0)
+            .range(patch_map_size);
+
+        let patch_map_infos = &[patch_map_info];
+        let patch_map_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptors.descriptor_sets[i])
+            .dst_binding(6)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(patch_map_infos);
+
+        let (patch_cell_buffer, patch_cell_size) = patch_cells[i];
+        let patch_cell_info = vk::DescriptorBufferInfo::builder()
+            .buffer(patch_cell_buffer)
+            .offset(0)
+            .range(patch_cell_size);
+
+        let patch_cell_infos = &[patch_cell_info];
+        let patch_cell_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptors.descriptor_sets[i])
+            .dst_binding(7)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(patch_cell_infos);
+
         globals::get_device().update_descriptor_sets(
             &[
                 ssbo_last_frame_write,
                 ssbo_curr_frame_write,
                 ubo_write,
                 storage_image_write,
+                domain_write,
+                moment_image_write,
+                patch_map_write,
+                patch_cell_write,
             ],
             &[] as &[vk::CopyDescriptorSet],
         );
@@ -183,8 +257,11 @@ pub unsafe fn create_gravity_descriptor_sets(
 }
 
 pub unsafe fn create_mass_descriptor_sets(
+    storage_buffers: &[vk::Buffer],
+    domain_buffers: &[(vk::Buffer, u64)],
+    deposit_buffers: &[(vk::Buffer, u64)],
     buffers: &BuffersData,
-    vertices: &Vec<Vertex>,
+    vertices: &[Vertex],
     descriptors: &mut DescriptorsData,
 ) -> Result<()> {
     let layouts = vec![descriptors.descriptor_set_layout; globals::MAX_FRAMES_IN_FLIGHT];
@@ -196,9 +273,9 @@ pub unsafe fn create_mass_descriptor_sets(
 
     for i in 0..globals::MAX_FRAMES_IN_FLIGHT {
         let storage_buffer_info = vk::DescriptorBufferInfo::builder()
-            .buffer(buffers.storage_buffers[(i + 1) % globals::MAX_FRAMES_IN_FLIGHT])
+            .buffer(storage_buffers[(i + 1) % globals::MAX_FRAMES_IN_FLIGHT])
             .offset(0)
-            .range((size_of::<Vertex>() * vertices.len()) as u64);
+            .range(size_of_val(vertices) as u64);
 
         let storage_infos = &[storage_buffer_info];
         let storage_buffer_write = vk::WriteDescriptorSet::builder()
@@ -208,14 +285,8 @@ pub unsafe fn create_mass_descriptor_sets(
             .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
             .buffer_info(storage_infos);
 
-        let storage_image_infos = buffers.offscreen_images[i]
-            .iter()
-            .map(|image| {
-                vk::DescriptorImageInfo::builder()
-                    .image_view(image.image_view)
-                    .image_layout(vk::ImageLayout::GENERAL)
-            })
-            .collect::<Vec<_>>();
+        let storage_image_infos = image_infos(&buffers.offscreen_images[i]);
+        let moment_image_infos = image_infos(moment_images(buffers, i));
 
         let storage_image_write = vk::WriteDescriptorSet::builder()
             .dst_set(descriptors.descriptor_sets[i])
@@ -224,11 +295,257 @@ pub unsafe fn create_mass_descriptor_sets(
             .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
             .image_info(&storage_image_infos);
 
+        let (domain_buffer, domain_size) = domain_buffers[i];
+        let domain_info = vk::DescriptorBufferInfo::builder()
+            .buffer(domain_buffer)
+            .offset(0)
+            .range(domain_size);
+
+        let domain_infos = &[domain_info];
+        let domain_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptors.descriptor_sets[i])
+            .dst_binding(2)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(domain_infos);
+
+        let (deposit_buffer, deposit_size) = deposit_buffers[i];
+        let deposit_info = vk::DescriptorBufferInfo::builder()
+            .buffer(deposit_buffer)
+            .offset(0)
+            .range(deposit_size);
+
+        let deposit_infos = &[deposit_info];
+        let deposit_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptors.descriptor_sets[i])
+            .dst_binding(3)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .buffer_info(deposit_infos);
+
+        let moment_image_write = vk::WriteDescriptorSet::builder()
+            .dst_set(descriptors.descriptor_sets[i])
+            .dst_binding(4)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .image_info(&moment_image_infos);
+
         globals::get_device().update_descriptor_sets(
-            &[storage_buffer_write, storage_image_write],
+            &[
+                storage_buffer_write,
+                storage_image_write,
+                domain_write,
+                deposit_write,
+                moment_image_write,
+            ],
             &[] as &[vk::CopyDescriptorSet],
         );
     }
 
     Ok(())
 }
+
+fn image_infos(images: &[ImageData]) -> Vec<vk::DescriptorImageInfoBuilder> {
+    images
+        .iter()
+        .map(|image| {
+            vk::DescriptorImageInfo::builder()
+                .image_view(image.image_view)
+                .image_layout(vk::ImageLayout::GENERAL)
+        })
+        .collect()
+}
+
+/// The moment images of `frame`, or the mass images standing in for them
+/// without quadrupoles, which the shaders then leave untouched.
+fn moment_images(buffers: &BuffersData, frame: usize) -> &[ImageData] {
+    buffers
+        .moment_images
+        .get(frame)
+        .unwrap_or(&buffers.offscreen_images[frame])
+}
+
+pub unsafe fn create_direct_descriptor_set_layout() -> Result<vk::DescriptorSetLayout> {
+    let storage_binding = vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::COMPUTE);
+
+    let bindings = &[
+        storage_binding,
+        storage_binding.binding(1),
+        storage_binding.binding(2),
+    ];
+    let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+    Ok(globals::get_device().create_descriptor_set_layout(&info, None)?)
+}
+
+pub unsafe fn create_direct_descriptor_pool(sets: u32) -> Result<vk::DescriptorPool> {
+    let storage_buffer_size = vk::DescriptorPoolSize::builder()
+        .type_(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(3 * sets);
+
+    let pool_sizes = &[storage_buffer_size];
+    let info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(pool_sizes)
+        .max_sets(sets);
+
+    Ok(globals::get_device().create_descriptor_pool(&info, None)?)
+}
+
+/// Binds the particles and the domain buffer of `domain.comp`.
+pub unsafe fn create_domain_descriptor_set_layout() -> Result<vk::DescriptorSetLayout> {
+    let storage_binding = vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::COMPUTE);
+
+    let bindings = &[storage_binding, storage_binding.binding(1)];
+    let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+    Ok(globals::get_device().create_descriptor_set_layout(&info, None)?)
+}
+
+pub unsafe fn create_domain_descriptor_pool(sets: u32) -> Result<vk::DescriptorPool> {
+    let storage_buffer_size = vk::DescriptorPoolSize::builder()
+        .type_(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(2 * sets);
+
+    let pool_sizes = &[storage_buffer_size];
+    let info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(pool_sizes)
+        .max_sets(sets);
+
+    Ok(globals::get_device().create_descriptor_pool(&info, None)?)
+}
+
+/// Binds the fixed point sums of `mass.comp` for `reduce.comp`.
+pub unsafe fn create_reduce_descriptor_set_layout() -> Result<vk::DescriptorSetLayout> {
+    let storage_binding = vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::COMPUTE);
+
+    let bindings = &[storage_binding];
+    let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+    Ok(globals::get_device().create_descriptor_set_layout(&info, None)?)
+}
+
+pub unsafe fn create_reduce_descriptor_pool(sets: u32) -> Result<vk::DescriptorPool> {
+    let storage_buffer_size = vk::DescriptorPoolSize::builder()
+        .type_(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(sets);
+
+    let pool_sizes = &[storage_buffer_size];
+    let info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(pool_sizes)
+        .max_sets(sets);
+
+    Ok(globals::get_device().create_descriptor_pool(&info, None)?)
+}
+
+/// Binds the particles, the domain, the fixed point sums of `mass.comp`
+/// and the patch map, sums and cells of `refine.comp`.
+pub unsafe fn create_refine_descriptor_set_layout() -> Result<vk::DescriptorSetLayout> {
+    let storage_binding = vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::COMPUTE);
+
+    let bindings = &[
+        storage_binding,
+        storage_binding.binding(1),
+        storage_binding.binding(2),
+        storage_binding.binding(3),
+        storage_binding.binding(4),
+        storage_binding.binding(5),
+    ];
+    let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+    Ok(globals::get_device().create_descriptor_set_layout(&info, None)?)
+}
+
+pub unsafe fn create_refine_descriptor_pool(sets: u32) -> Result<vk::DescriptorPool> {
+    let storage_buffer_size = vk::DescriptorPoolSize::builder()
+        .type_(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(6 * sets);
+
+    let pool_sizes = &[storage_buffer_size];
+    let info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(pool_sizes)
+        .max_sets(sets);
+
+    Ok(globals::get_device().create_descriptor_pool(&info, None)?)
+}
+
+pub unsafe fn create_block_descriptor_set_layout() -> Result<vk::DescriptorSetLayout> {
+    let storage_binding = vk::DescriptorSetLayoutBinding::builder()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::COMPUTE);
+
+    let bindings = &[
+        storage_binding,
+        storage_binding.binding(1),
+        storage_binding.binding(2),
+        storage_binding.binding(3),
+        storage_binding.binding(4),
+    ];
+    let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(bindings);
+    Ok(globals::get_device().create_descriptor_set_layout(&info, None)?)
+}
+
+pub unsafe fn create_block_descriptor_pool(sets: u32) -> Result<vk::DescriptorPool> {
+    let storage_buffer_size = vk::DescriptorPoolSize::builder()
+        .type_(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(5 * sets);
+
+    let pool_sizes = &[storage_buffer_size];
+    let info = vk::DescriptorPoolCreateInfo::builder()
+        .pool_sizes(pool_sizes)
+        .max_sets(sets);
+
+    Ok(globals::get_device().create_descriptor_pool(&info, None)?)
+}
+
+/// Adds a set binding the storage buffers to bindings 0 and up in order,
+/// for every pass whose layout only holds storage buffers.
+pub unsafe fn create_storage_descriptor_set<const N: usize>(
+    buffers: [(vk::Buffer, u64); N],
+    descriptors: &mut DescriptorsData,
+) -> Result<()> {
+    let layouts = &[descriptors.descriptor_set_layout];
+    let info = vk::DescriptorSetAllocateInfo::builder()
+        .descriptor_pool(descriptors.descriptor_pool)
+        .set_layouts(layouts);
+
+    let descriptor_set = globals::get_device().allocate_descriptor_sets(&info)?[0];
+    descriptors.descriptor_sets.push(descriptor_set);
+
+    let infos = buffers.map(|(buffer, size)| {
+        [vk::DescriptorBufferInfo::builder()
+            .buffer(buffer)
+            .offset(0)
+            .range(size)]
+    });
+
+    let writes = infos
+        .iter()
+        .enumerate()
+        .map(|(binding, info)| {
+            vk::WriteDescriptorSet::builder()
+                .dst_set(descriptor_set)
+                .dst_binding(binding as u32)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(info)
+        })
+        .collect::<Vec<_>>();
+
+    globals::get_device().update_descriptor_sets(&writes, &[] as &[vk::CopyDescriptorSet]);
+
+    Ok(())
+}
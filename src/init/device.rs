@@ -15,12 +15,22 @@ unsafe fn check_physical_device(
     physical_device: vk::PhysicalDevice,
 ) -> Result<()> {
     QueueFamilyIndices::get(instance, common, physical_device)?;
-    check_physical_device_extensions(instance, physical_device)?;
+    check_physical_device_extensions(instance, common, physical_device)?;
     Ok(())
 }
 
+fn required_device_extensions(common: &CommonData) -> Vec<vk::ExtensionName> {
+    let mut extensions = globals::DEVICE_EXTENSIONS.to_vec();
+    if !common.headless {
+        extensions.extend_from_slice(globals::WINDOW_DEVICE_EXTENSIONS);
+    }
+
+    extensions
+}
+
 unsafe fn check_physical_device_extensions(
     instance: &Instance,
+    common: &CommonData,
     physical_device: vk::PhysicalDevice,
 ) -> Result<()> {
     let extensions = instance
@@ -29,7 +39,7 @@ unsafe fn check_physical_device_extensions(
         .map(|e| e.extension_name)
         .collect::<HashSet<_>>();
 
-    if globals::DEVICE_EXTENSIONS
+    if required_device_extensions(common)
         .iter()
         .all(|e| extensions.contains(e))
     {
@@ -84,7 +94,9 @@ pub unsafe fn create_logical_device(
     let mut unique_indices = HashSet::new();
 
     unique_indices.insert(indices.graphics_compute);
-    unique_indices.insert(indices.present);
+    if let Some(present) = indices.present {
+        unique_indices.insert(present);
+    }
 
     let queue_priorities = &[1.0];
     let queue_infos = unique_indices
@@ -102,12 +114,13 @@ pub unsafe fn create_logical_device(
         vec![]
     };
 
-    let extensions = globals::DEVICE_EXTENSIONS
+    let required_extensions = required_device_extensions(common);
+    let extensions = required_extensions
         .iter()
         .map(|n| n.as_ptr())
         .collect::<Vec<_>>();
 
-    let features = vk::PhysicalDeviceFeatures::builder().fill_mode_non_solid(true);
+    let features = vk::PhysicalDeviceFeatures::builder().fill_mode_non_solid(!common.headless);
 
     let info = vk::DeviceCreateInfo::builder()
         .queue_create_infos(&queue_infos)
@@ -117,7 +130,9 @@ pub unsafe fn create_logical_device(
 
     let device = instance.create_device(common.physical_device, &info, None)?;
 
-    common.present_queue = device.get_device_queue(indices.present, 0);
+    if let Some(present) = indices.present {
+        common.present_queue = device.get_device_queue(present, 0);
+    }
     common.graphics_queue = device.get_device_queue(indices.graphics_compute, 0);
     common.compute_queue = device.get_device_queue(indices.graphics_compute, 0);
 
@@ -6,7 +6,7 @@ use crate::data::globals;
 pub unsafe fn create_framebuffers(
     render_pass: vk::RenderPass,
     extent: &vk::Extent2D,
-    image_views: &Vec<vk::ImageView>,
+    image_views: &[vk::ImageView],
 ) -> Result<Vec<vk::Framebuffer>> {
     let framebuffers = image_views
         .iter()
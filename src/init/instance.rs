@@ -11,7 +11,7 @@ use crate::data::globals;
 use log::{debug, error, trace, warn};
 
 pub unsafe fn create_instance(
-    window: &Window,
+    window: Option<&Window>,
     entry: &Entry,
     common: &mut CommonData,
 ) -> Result<Instance> {
@@ -38,10 +38,13 @@ pub unsafe fn create_instance(
         Vec::new()
     };
 
-    let mut extensions = vk_window::get_required_instance_extensions(window)
-        .iter()
-        .map(|e| e.as_ptr())
-        .collect::<Vec<_>>();
+    let mut extensions = match window {
+        Some(window) => vk_window::get_required_instance_extensions(window)
+            .iter()
+            .map(|e| e.as_ptr())
+            .collect::<Vec<_>>(),
+        None => vec![],
+    };
 
     if globals::VALIDATION_ENABLED {
         extensions.push(vk::EXT_DEBUG_UTILS_EXTENSION.name.as_ptr());
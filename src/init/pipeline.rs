@@ -1,9 +1,20 @@
+use std::mem::{size_of, size_of_val};
+use std::slice;
+
 use anyhow::{anyhow, Ok, Result};
 use vulkanalia::prelude::v1_0::*;
 
 use crate::data::{
-    descriptors_data::DescriptorsData, globals, pipeline_data::PipelineData,
-    swapchain_data::SwapchainData, vertex::Vertex,
+    descriptors_data::DescriptorsData,
+    globals,
+    pipeline_data::PipelineData,
+    push_constants::{
+        BlockPushConstants, DirectPushConstants, DomainPushConstants, GravityPushConstants,
+        IntegratePushConstants, MassPushConstants, ReducePushConstants, RefinePushConstants,
+        RenderPushConstants,
+    },
+    swapchain_data::SwapchainData,
+    vertex::Vertex,
 };
 
 pub unsafe fn create_pipeline(
@@ -78,7 +89,14 @@ pub unsafe fn create_pipeline(
         .attachments(attachments)
         .blend_constants([0.0, 0.0, 0.0, 0.0]);
 
-    let layout_info = vk::PipelineLayoutCreateInfo::builder();
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::VERTEX)
+        .offset(0)
+        .size(size_of::<RenderPushConstants>() as u32);
+
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info =
+        vk::PipelineLayoutCreateInfo::builder().push_constant_ranges(push_constant_ranges);
     pipeline.pipeline_layout = globals::get_device().create_pipeline_layout(&layout_info, None)?;
 
     let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
@@ -108,23 +126,36 @@ pub unsafe fn create_pipeline(
     Ok(())
 }
 
+/// Specializes the shader to the `levels` of the mass field, the `reach`
+/// around the particles and the refinement patches, with a `tile_size` of
+/// zero without them.
 pub unsafe fn create_gravity_compute_pipeline(
     descriptors: &DescriptorsData,
     pipeline: &mut PipelineData,
+    levels: u32,
+    reach: u32,
+    tile_size: u32,
+    refine_factor: u32,
 ) -> Result<()> {
     let comp = include_bytes!("../../shaders/gravity.comp.spv");
     let comp_shader_module = create_shader_module(&comp[..])?;
+    let constants = [levels, reach, tile_size, refine_factor];
+    let map_entries = specialization_map_entries(&constants);
+    let specialization_info = vk::SpecializationInfo::builder()
+        .map_entries(&map_entries)
+        .data(specialization_data(&constants));
     let comp_stage = vk::PipelineShaderStageCreateInfo::builder()
         .stage(vk::ShaderStageFlags::COMPUTE)
         .module(comp_shader_module)
-        .name(b"main\0");
+        .name(b"main\0")
+        .specialization_info(&specialization_info);
 
     let set_layouts = &[descriptors.descriptor_set_layout];
 
     let mip_level_push_constant_range = vk::PushConstantRange::builder()
         .stage_flags(vk::ShaderStageFlags::COMPUTE)
         .offset(0)
-        .size(4);
+        .size(size_of::<GravityPushConstants>() as u32);
     let mip_level_push_constant_ranges = &[mip_level_push_constant_range];
     let layout_info = vk::PipelineLayoutCreateInfo::builder()
         .set_layouts(set_layouts)
@@ -146,24 +177,186 @@ pub unsafe fn create_gravity_compute_pipeline(
     Ok(())
 }
 
+pub unsafe fn create_direct_compute_pipeline(
+    descriptors: &DescriptorsData,
+    pipeline: &mut PipelineData,
+) -> Result<()> {
+    let comp = include_bytes!("../../shaders/direct.comp.spv");
+    let comp_shader_module = create_shader_module(&comp[..])?;
+    let comp_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(comp_shader_module)
+        .name(b"main\0");
+
+    let set_layouts = &[descriptors.descriptor_set_layout];
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .offset(0)
+        .size(size_of::<DirectPushConstants>() as u32);
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+
+    pipeline.pipeline_layout = globals::get_device().create_pipeline_layout(&layout_info, None)?;
+
+    let info = vk::ComputePipelineCreateInfo::builder()
+        .stage(comp_stage)
+        .layout(pipeline.pipeline_layout);
+
+    let infos = &[info];
+
+    pipeline.pipeline = globals::get_device()
+        .create_compute_pipelines(vk::PipelineCache::null(), infos, None)?
+        .0[0];
+
+    globals::get_device().destroy_shader_module(comp_shader_module, None);
+    Ok(())
+}
+
+/// Uses the layout of the direct pass, which also binds three storage
+/// buffers.
+pub unsafe fn create_integrate_compute_pipeline(
+    descriptors: &DescriptorsData,
+    pipeline: &mut PipelineData,
+) -> Result<()> {
+    let comp = include_bytes!("../../shaders/integrate.comp.spv");
+    let comp_shader_module = create_shader_module(&comp[..])?;
+    let comp_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(comp_shader_module)
+        .name(b"main\0");
+
+    let set_layouts = &[descriptors.descriptor_set_layout];
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .offset(0)
+        .size(size_of::<IntegratePushConstants>() as u32);
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+
+    pipeline.pipeline_layout = globals::get_device().create_pipeline_layout(&layout_info, None)?;
+
+    let info = vk::ComputePipelineCreateInfo::builder()
+        .stage(comp_stage)
+        .layout(pipeline.pipeline_layout);
+
+    let infos = &[info];
+
+    pipeline.pipeline = globals::get_device()
+        .create_compute_pipelines(vk::PipelineCache::null(), infos, None)?
+        .0[0];
+
+    globals::get_device().destroy_shader_module(comp_shader_module, None);
+    Ok(())
+}
+
+pub unsafe fn create_block_compute_pipeline(
+    descriptors: &DescriptorsData,
+    pipeline: &mut PipelineData,
+) -> Result<()> {
+    let comp = include_bytes!("../../shaders/block.comp.spv");
+    let comp_shader_module = create_shader_module(&comp[..])?;
+    let comp_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(comp_shader_module)
+        .name(b"main\0");
+
+    let set_layouts = &[descriptors.descriptor_set_layout];
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .offset(0)
+        .size(size_of::<BlockPushConstants>() as u32);
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+
+    pipeline.pipeline_layout = globals::get_device().create_pipeline_layout(&layout_info, None)?;
+
+    let info = vk::ComputePipelineCreateInfo::builder()
+        .stage(comp_stage)
+        .layout(pipeline.pipeline_layout);
+
+    let infos = &[info];
+
+    pipeline.pipeline = globals::get_device()
+        .create_compute_pipelines(vk::PipelineCache::null(), infos, None)?
+        .0[0];
+
+    globals::get_device().destroy_shader_module(comp_shader_module, None);
+    Ok(())
+}
+
+pub unsafe fn create_domain_compute_pipeline(
+    descriptors: &DescriptorsData,
+    pipeline: &mut PipelineData,
+) -> Result<()> {
+    let comp = include_bytes!("../../shaders/domain.comp.spv");
+    let comp_shader_module = create_shader_module(&comp[..])?;
+    let comp_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(comp_shader_module)
+        .name(b"main\0");
+
+    let set_layouts = &[descriptors.descriptor_set_layout];
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .offset(0)
+        .size(size_of::<DomainPushConstants>() as u32);
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+
+    pipeline.pipeline_layout = globals::get_device().create_pipeline_layout(&layout_info, None)?;
+
+    let info = vk::ComputePipelineCreateInfo::builder()
+        .stage(comp_stage)
+        .layout(pipeline.pipeline_layout);
+
+    let infos = &[info];
+
+    pipeline.pipeline = globals::get_device()
+        .create_compute_pipelines(vk::PipelineCache::null(), infos, None)?
+        .0[0];
+
+    globals::get_device().destroy_shader_module(comp_shader_module, None);
+    Ok(())
+}
+
+/// Specializes the shader to the `levels` of the mass field.
 pub unsafe fn create_mass_compute_pipeline(
     descriptors: &DescriptorsData,
     pipeline: &mut PipelineData,
+    levels: u32,
 ) -> Result<()> {
     let comp = include_bytes!("../../shaders/mass.comp.spv");
     let comp_shader_module = create_shader_module(&comp[..])?;
+    let constants = [levels];
+    let map_entries = specialization_map_entries(&constants);
+    let specialization_info = vk::SpecializationInfo::builder()
+        .map_entries(&map_entries)
+        .data(specialization_data(&constants));
 
     let comp_stage = vk::PipelineShaderStageCreateInfo::builder()
         .stage(vk::ShaderStageFlags::COMPUTE)
         .module(comp_shader_module)
-        .name(b"main\0");
+        .name(b"main\0")
+        .specialization_info(&specialization_info);
 
     let set_layouts = &[descriptors.descriptor_set_layout];
 
     let mip_level_push_constant_range = vk::PushConstantRange::builder()
         .stage_flags(vk::ShaderStageFlags::COMPUTE)
         .offset(0)
-        .size(4);
+        .size(size_of::<MassPushConstants>() as u32);
 
     let mip_level_push_constant_ranges = &[mip_level_push_constant_range];
     let layout_info = vk::PipelineLayoutCreateInfo::builder()
@@ -186,6 +379,86 @@ pub unsafe fn create_mass_compute_pipeline(
     Ok(())
 }
 
+pub unsafe fn create_reduce_compute_pipeline(
+    descriptors: &DescriptorsData,
+    pipeline: &mut PipelineData,
+) -> Result<()> {
+    let comp = include_bytes!("../../shaders/reduce.comp.spv");
+    let comp_shader_module = create_shader_module(&comp[..])?;
+
+    let comp_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(comp_shader_module)
+        .name(b"main\0");
+
+    let set_layouts = &[descriptors.descriptor_set_layout];
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .offset(0)
+        .size(size_of::<ReducePushConstants>() as u32);
+
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+
+    pipeline.pipeline_layout = globals::get_device().create_pipeline_layout(&layout_info, None)?;
+
+    let info = vk::ComputePipelineCreateInfo::builder()
+        .stage(comp_stage)
+        .layout(pipeline.pipeline_layout);
+
+    let infos = &[info];
+
+    pipeline.pipeline = globals::get_device()
+        .create_compute_pipelines(vk::PipelineCache::null(), infos, None)?
+        .0[0];
+
+    globals::get_device().destroy_shader_module(comp_shader_module, None);
+    Ok(())
+}
+
+pub unsafe fn create_refine_compute_pipeline(
+    descriptors: &DescriptorsData,
+    pipeline: &mut PipelineData,
+) -> Result<()> {
+    let comp = include_bytes!("../../shaders/refine.comp.spv");
+    let comp_shader_module = create_shader_module(&comp[..])?;
+
+    let comp_stage = vk::PipelineShaderStageCreateInfo::builder()
+        .stage(vk::ShaderStageFlags::COMPUTE)
+        .module(comp_shader_module)
+        .name(b"main\0");
+
+    let set_layouts = &[descriptors.descriptor_set_layout];
+
+    let push_constant_range = vk::PushConstantRange::builder()
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        .offset(0)
+        .size(size_of::<RefinePushConstants>() as u32);
+
+    let push_constant_ranges = &[push_constant_range];
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+
+    pipeline.pipeline_layout = globals::get_device().create_pipeline_layout(&layout_info, None)?;
+
+    let info = vk::ComputePipelineCreateInfo::builder()
+        .stage(comp_stage)
+        .layout(pipeline.pipeline_layout);
+
+    let infos = &[info];
+
+    pipeline.pipeline = globals::get_device()
+        .create_compute_pipelines(vk::PipelineCache::null(), infos, None)?
+        .0[0];
+
+    globals::get_device().destroy_shader_module(comp_shader_module, None);
+    Ok(())
+}
+
 unsafe fn create_shader_module(bytecode: &[u8]) -> Result<vk::ShaderModule> {
     let bytecode = Vec::<u8>::from(bytecode);
     let (prefix, code, suffix) = bytecode.align_to::<u32>();
@@ -203,3 +476,21 @@ unsafe fn create_shader_module(bytecode: &[u8]) -> Result<vk::ShaderModule> {
 
     Ok(globals::get_device().create_shader_module(&info, None)?)
 }
+
+/// Map entries of 32 bit specialization `constants`, the constant id of
+/// each being its index.
+fn specialization_map_entries(constants: &[u32]) -> Vec<vk::SpecializationMapEntry> {
+    (0..constants.len() as u32)
+        .map(|id| {
+            vk::SpecializationMapEntry::builder()
+                .constant_id(id)
+                .offset(id * size_of::<u32>() as u32)
+                .size(size_of::<u32>())
+                .build()
+        })
+        .collect()
+}
+
+fn specialization_data(constants: &[u32]) -> &[u8] {
+    unsafe { slice::from_raw_parts(constants.as_ptr().cast(), size_of_val(constants)) }
+}
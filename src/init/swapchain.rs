@@ -1,6 +1,6 @@
 use anyhow::{Ok, Result};
-use vulkanalia::{prelude::v1_0::*, vk::SampleCountFlags};
 use vulkanalia::vk::KhrSwapchainExtension;
+use vulkanalia::{prelude::v1_0::*, vk::SampleCountFlags};
 use winit::window::Window;
 
 use crate::{
@@ -32,9 +32,10 @@ pub unsafe fn create_swapchain(
     }
 
     let mut queue_family_indices = vec![];
-    let image_sharing_mode = if indices.graphics_compute != indices.present {
+    let present = indices.present()?;
+    let image_sharing_mode = if indices.graphics_compute != present {
         queue_family_indices.push(indices.graphics_compute);
-        queue_family_indices.push(indices.present);
+        queue_family_indices.push(present);
         vk::SharingMode::CONCURRENT
     } else {
         vk::SharingMode::EXCLUSIVE
@@ -89,7 +90,7 @@ pub fn get_swapchain_extent(
     window: &Window,
     capabilities: vk::SurfaceCapabilitiesKHR,
 ) -> vk::Extent2D {
-    if capabilities.current_extent.width != u32::max_value() {
+    if capabilities.current_extent.width != u32::MAX {
         capabilities.current_extent
     } else {
         let size = window.inner_size();
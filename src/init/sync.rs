@@ -6,22 +6,14 @@ use crate::data::{globals, swapchain_data::SwapchainData, sync_data::SyncData};
 pub unsafe fn create_sync_objects(swapchain: &SwapchainData, sync: &mut SyncData) -> Result<()> {
     let semaphore_info = vk::SemaphoreCreateInfo::builder();
     let fence_info = vk::FenceCreateInfo::builder().flags(vk::FenceCreateFlags::SIGNALED);
-    sync.first_gravity_compute = true;
 
     for _ in 0..globals::MAX_FRAMES_IN_FLIGHT {
         sync.image_available_semaphores
             .push(globals::get_device().create_semaphore(&semaphore_info, None)?);
-        sync.image_clear_finished_semaphores
-            .push(globals::get_device().create_semaphore(&semaphore_info, None)?);
         sync.render_finished_semaphores
             .push(globals::get_device().create_semaphore(&semaphore_info, None)?);
 
-        sync.gravity_compute_finished_semaphores
-            .push(globals::get_device().create_semaphore(&semaphore_info, None)?);
-        sync.mass_compute_finished_semaphores
-            .push(globals::get_device().create_semaphore(&semaphore_info, None)?);
-
-        sync.gravity_read_finished_semaphores
+        sync.compute_step_finished_semaphores
             .push(globals::get_device().create_semaphore(&semaphore_info, None)?);
 
         sync.in_flight_fences
@@ -36,3 +28,11 @@ pub unsafe fn create_sync_objects(swapchain: &SwapchainData, sync: &mut SyncData
 
     Ok(())
 }
+
+pub unsafe fn create_headless_sync_objects(sync: &mut SyncData) -> Result<()> {
+    let fence_info = vk::FenceCreateInfo::builder();
+    sync.in_flight_fences
+        .push(globals::get_device().create_fence(&fence_info, None)?);
+
+    Ok(())
+}
@@ -0,0 +1,262 @@
+//! Block timesteps. Every particle steps with `dt / 2^level`, the level
+//! following from its acceleration like the acceleration criterion of the
+//! adaptive timestep. A step of `dt` is split into `2^(levels - 1)` ticks of
+//! the smallest timestep. Each tick drifts all particles and then evaluates
+//! the forces only on the particles whose timestep ends, which get the
+//! closing half kick of that timestep and the opening half kick of their
+//! next one. The velocities are at the time of the positions between
+//! steps, and the accelerations and levels of the last tick are kept for
+//! the opening kicks of the next step. `block.comp` runs the same ticks on
+//! the device.
+
+use cgmath::{vec2, InnerSpace};
+use rayon::prelude::*;
+
+use crate::config::{Physics, RunConfig};
+use crate::cpu::{boundary, CpuSolver};
+use crate::data::vertex::Vertex;
+use crate::integrators::timestep;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+#[derive(Debug)]
+pub struct BlockTimesteps {
+    eta: f32,
+    max_level: u32,
+    /// Acceleration and level of every particle from its latest force
+    /// evaluation, empty until the first step.
+    timesteps: Vec<(Vec2, u32)>,
+}
+
+impl BlockTimesteps {
+    pub fn new(config: &RunConfig) -> Self {
+        Self {
+            eta: config.eta,
+            max_level: config.block_levels.max(1) - 1,
+            timesteps: Vec::new(),
+        }
+    }
+
+    /// Ticks of the smallest timestep in one step.
+    pub fn ticks(&self) -> u32 {
+        1 << self.max_level
+    }
+
+    /// Steps the particles of `read` by `delta_t` into `write`.
+    pub fn step(
+        &mut self,
+        solver: &mut dyn CpuSolver,
+        read: &[Vertex],
+        write: &mut [Vertex],
+        physics: &Physics,
+        delta_t: f32,
+    ) {
+        // Every particle is due at the last tick, which gives the levels
+        // and accelerations of the first step.
+        if self.timesteps.len() != read.len() {
+            self.timesteps = vec![(vec2(0.0, 0.0), 0); read.len()];
+            write.copy_from_slice(read);
+            self.kick(solver, write, physics, delta_t, self.ticks(), false);
+        }
+
+        let substep = delta_t / self.ticks() as f32;
+        write
+            .par_iter_mut()
+            .zip(read.par_iter().zip(&self.timesteps))
+            .for_each(|(out, (particle, (acceleration, level)))| {
+                *out = *particle;
+                if is_finite(particle.pos) {
+                    out.velocity += acceleration * (level_delta_t(delta_t, *level) * 0.5);
+                    out.pos += out.velocity * substep;
+                    boundary::apply(physics.boundary, out);
+                }
+            });
+        self.kick(solver, write, physics, delta_t, 1, true);
+
+        for tick in 2..=self.ticks() {
+            write
+                .par_iter_mut()
+                .filter(|particle| is_finite(particle.pos))
+                .for_each(|particle| {
+                    particle.pos += particle.velocity * substep;
+                    boundary::apply(physics.boundary, particle);
+                });
+            self.kick(solver, write, physics, delta_t, tick, true);
+        }
+    }
+
+    /// Evaluates the forces on the particles whose timestep ends at `tick`,
+    /// closes their timestep and opens the next one, unless it is the last
+    /// tick of the step.
+    fn kick(
+        &mut self,
+        solver: &mut dyn CpuSolver,
+        particles: &mut [Vertex],
+        physics: &Physics,
+        delta_t: f32,
+        tick: u32,
+        closing: bool,
+    ) {
+        let targets = particles
+            .iter()
+            .zip(&self.timesteps)
+            .enumerate()
+            .filter(|(_, (particle, (_, level)))| {
+                is_finite(particle.pos) && (tick == self.ticks() || self.due(*level, tick))
+            })
+            .map(|(i, _)| i as u32)
+            .collect::<Vec<_>>();
+
+        let accelerations = solver.accelerations(particles, &targets, physics);
+        let last = tick == self.ticks();
+
+        for (&target, acceleration) in targets.iter().zip(accelerations) {
+            let acceleration = acceleration.unwrap_or(vec2(0.0, 0.0));
+            let particle = &mut particles[target as usize];
+            let (cached, level) = &mut self.timesteps[target as usize];
+
+            if closing {
+                particle.velocity += acceleration * (level_delta_t(delta_t, *level) * 0.5);
+            }
+
+            *cached = acceleration;
+            *level = level_at(
+                self.eta,
+                self.max_level,
+                physics,
+                acceleration,
+                delta_t,
+                tick,
+            );
+
+            if !last {
+                particle.velocity += acceleration * (level_delta_t(delta_t, *level) * 0.5);
+            }
+        }
+    }
+
+    /// Whether the timestep of `level` ends at `tick`.
+    fn due(&self, level: u32, tick: u32) -> bool {
+        tick.is_multiple_of(1 << (self.max_level - level.min(self.max_level)))
+    }
+}
+
+/// Timestep of `level` in a step of `delta_t`.
+fn level_delta_t(delta_t: f32, level: u32) -> f32 {
+    delta_t / (1 << level) as f32
+}
+
+/// Level of a particle with `acceleration` starting a timestep at `tick`.
+/// Going to a smaller timestep is always possible, but only to larger
+/// timesteps that also start at `tick`, so the ticks of the levels stay
+/// aligned.
+fn level_at(
+    eta: f32,
+    max_level: u32,
+    physics: &Physics,
+    acceleration: Vec2,
+    delta_t: f32,
+    tick: u32,
+) -> u32 {
+    let magnitude = acceleration.magnitude();
+    let mut level = 0;
+    if magnitude > 0.0 {
+        let wanted = timestep::acceleration_delta_t(eta, physics, magnitude);
+        level = (delta_t / wanted)
+            .log2()
+            .ceil()
+            .clamp(0.0, max_level as f32) as u32;
+    }
+
+    let aligned = if tick == 1 << max_level {
+        0
+    } else {
+        max_level - tick.trailing_zeros()
+    };
+    level.max(aligned)
+}
+
+fn is_finite(v: Vec2) -> bool {
+    v.x.is_finite() && v.y.is_finite()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ETA: f32 = 0.02;
+    const DELTA_T: f32 = 0.01;
+    const MAX_LEVEL: u32 = 3;
+
+    /// An acceleration that wants a timestep of 1.5 times the one of
+    /// `level`, which is only met by `level`.
+    fn acceleration(physics: &Physics, level: u32) -> Vec2 {
+        let wanted = level_delta_t(DELTA_T, level) * 1.5;
+        vec2(2.0 * ETA * physics.softening / (wanted * wanted), 0.0)
+    }
+
+    fn blocks() -> BlockTimesteps {
+        BlockTimesteps::new(&RunConfig {
+            eta: ETA,
+            block_levels: MAX_LEVEL + 1,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn assigns_the_level_of_the_acceleration() {
+        let physics = Physics::default();
+        let last = 1 << MAX_LEVEL;
+
+        assert_eq!(
+            level_at(ETA, MAX_LEVEL, &physics, vec2(0.0, 0.0), DELTA_T, last),
+            0
+        );
+        for level in 0..=MAX_LEVEL {
+            let a = acceleration(&physics, level);
+            assert_eq!(level_at(ETA, MAX_LEVEL, &physics, a, DELTA_T, last), level);
+        }
+
+        let a = acceleration(&physics, MAX_LEVEL + 2);
+        assert_eq!(
+            level_at(ETA, MAX_LEVEL, &physics, a, DELTA_T, last),
+            MAX_LEVEL
+        );
+    }
+
+    #[test]
+    fn only_moves_to_larger_timesteps_on_their_ticks() {
+        let physics = Physics::default();
+        let a = vec2(0.0, 0.0);
+
+        // Ticks 1 to 7 of a step with 8 ticks
+        for (tick, aligned) in [(1, 3), (2, 2), (3, 3), (4, 1), (5, 3), (6, 2), (7, 3)] {
+            assert_eq!(
+                level_at(ETA, MAX_LEVEL, &physics, a, DELTA_T, tick),
+                aligned
+            );
+        }
+
+        let a = acceleration(&physics, 2);
+        assert_eq!(level_at(ETA, MAX_LEVEL, &physics, a, DELTA_T, 4), 2);
+        assert_eq!(level_at(ETA, MAX_LEVEL, &physics, a, DELTA_T, 2), 2);
+        assert_eq!(level_at(ETA, MAX_LEVEL, &physics, a, DELTA_T, 1), 3);
+    }
+
+    #[test]
+    fn levels_are_due_at_the_end_of_their_timesteps() {
+        let blocks = blocks();
+        assert_eq!(blocks.ticks(), 8);
+
+        for level in 0..=MAX_LEVEL {
+            let due = (1..=blocks.ticks())
+                .filter(|&tick| blocks.due(level, tick))
+                .collect::<Vec<_>>();
+            let every = 8 >> level;
+            assert_eq!(due, (1..=8 / every).map(|i| i * every).collect::<Vec<_>>());
+        }
+
+        // Levels beyond the last one step with the smallest timestep.
+        assert!(blocks.due(MAX_LEVEL + 1, 1));
+    }
+}
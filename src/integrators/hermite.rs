@@ -0,0 +1,54 @@
+//! Fourth order Hermite predictor-corrector. The positions and velocities
+//! are predicted with the accelerations and jerks of the start of the step
+//! and corrected with the ones of the predicted state, which the next step
+//! starts from.
+
+use rayon::prelude::*;
+
+use crate::config::Physics;
+use crate::cpu::direct;
+use crate::data::vertex::Vertex;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Advances `read` into `write` from the accelerations and jerks of `read`
+/// in `start` and returns the ones of the predicted state.
+pub fn step(
+    read: &[Vertex],
+    write: &mut [Vertex],
+    physics: &Physics,
+    start: &[(Vec2, Vec2)],
+    delta_t: f32,
+) -> Vec<(Vec2, Vec2)> {
+    let h = delta_t;
+
+    let predicted = read
+        .par_iter()
+        .zip(start)
+        .map(|(particle, (a, j))| Vertex {
+            pos: particle.pos + particle.velocity * h + a * (h * h / 2.0) + j * (h * h * h / 6.0),
+            velocity: particle.velocity + a * h + j * (h * h / 2.0),
+            ..*particle
+        })
+        .collect::<Vec<_>>();
+
+    let end = direct::accelerations_and_jerks(&predicted, physics);
+
+    write
+        .par_iter_mut()
+        .zip(read.par_iter().zip(start.par_iter().zip(&end)))
+        .for_each(|(out, (particle, ((a0, j0), (a1, j1))))| {
+            let velocity = particle.velocity + (a0 + a1) * (h / 2.0) + (j0 - j1) * (h * h / 12.0);
+            let pos = particle.pos
+                + (particle.velocity + velocity) * (h / 2.0)
+                + (a0 - a1) * (h * h / 12.0);
+
+            *out = Vertex {
+                pos,
+                velocity,
+                ..*particle
+            };
+        });
+
+    end
+}
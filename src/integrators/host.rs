@@ -0,0 +1,205 @@
+//! Steps of every integrator with one of the CPU solvers, for
+//! `CpuSimulation` and `HostSolver`.
+
+use crate::config::{Integrator, Physics, RunConfig};
+use crate::cpu::{boundary, direct, CpuSolver};
+use crate::data::globals;
+use crate::data::vertex::Vertex;
+use crate::integrators::block::BlockTimesteps;
+use crate::integrators::timestep::TimestepController;
+use crate::integrators::{hermite, rk4, Update};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Advances a pair of particle buffers ping-ponged like the storage
+/// buffers, with a fixed or adaptive timestep.
+#[derive(Debug)]
+pub struct HostIntegrator {
+    integrator: Integrator,
+    controller: Option<TimestepController>,
+    /// Timestep of the latest step, which staggered velocities lag by.
+    last_delta_t: f32,
+    /// Accelerations and jerks the next Hermite step starts from.
+    hermite: Option<Vec<(Vec2, Vec2)>>,
+    block: BlockTimesteps,
+}
+
+impl HostIntegrator {
+    pub fn new(config: &RunConfig) -> Self {
+        Self {
+            integrator: config.integrator,
+            controller: TimestepController::new(config),
+            last_delta_t: config.dt.unwrap_or(globals::DEFAULT_DELTA_T),
+            hermite: None,
+            block: BlockTimesteps::new(config),
+        }
+    }
+
+    /// Computes the step of `frame` from the particles of the other buffer
+    /// into `particles[frame]`, and returns the timestep taken. Adaptive
+    /// runs take up to `delta_t`, the others exactly that.
+    pub fn step(
+        &mut self,
+        solver: &mut dyn CpuSolver,
+        particles: &mut [Vec<Vertex>; 2],
+        frame: usize,
+        physics: &Physics,
+        delta_t: f32,
+    ) -> f32 {
+        let delta_t = match self.integrator {
+            Integrator::Rk4 => self.rk4(solver, particles, frame, physics, delta_t),
+            Integrator::Hermite => self.hermite(particles, frame, physics, delta_t),
+            Integrator::Block => {
+                let (read, write) = split(particles, frame);
+                self.block.step(solver, read, write, physics, delta_t);
+                delta_t
+            }
+            _ => self.passes(solver, particles, frame, physics, delta_t),
+        };
+
+        self.last_delta_t = delta_t;
+        delta_t
+    }
+
+    /// Kicks the velocities of the other buffer by `kick` latest timesteps
+    /// into `particles[frame]`. Particles the solver skips keep their state.
+    pub fn kick(
+        &mut self,
+        solver: &mut dyn CpuSolver,
+        particles: &mut [Vec<Vertex>; 2],
+        frame: usize,
+        physics: &Physics,
+        kick: f32,
+    ) {
+        particles[frame] = particles[(frame + 1) % 2].clone();
+
+        let (read, write) = split(particles, frame);
+        solver.step(read, write, physics, &Update::kick(kick), self.last_delta_t);
+    }
+
+    fn passes(
+        &mut self,
+        solver: &mut dyn CpuSolver,
+        particles: &mut [Vec<Vertex>; 2],
+        frame: usize,
+        physics: &Physics,
+        max_delta_t: f32,
+    ) -> f32 {
+        let mut delta_t = max_delta_t;
+
+        for (i, update) in Update::passes(self.integrator).iter().enumerate() {
+            let (read, write) = split(particles, (frame + i) % 2);
+
+            match self.controller.filter(|_| i == 0) {
+                None => solver.step(read, write, physics, update, delta_t),
+                Some(controller) => {
+                    let accelerations;
+                    (accelerations, delta_t) =
+                        start(solver, controller, read, physics, max_delta_t);
+
+                    // The opening kick takes the lagging velocities from the
+                    // middle of the previous timestep to the middle of this one.
+                    let lag = Update::lag(self.integrator);
+                    let update = Update {
+                        kick: update.kick + lag * (self.last_delta_t / delta_t - 1.0),
+                        ..*update
+                    };
+                    solver.apply(read, write, &accelerations, &update, delta_t);
+                }
+            }
+
+            boundary::apply_all(physics.boundary, write);
+        }
+
+        delta_t
+    }
+
+    fn rk4(
+        &mut self,
+        solver: &mut dyn CpuSolver,
+        particles: &mut [Vec<Vertex>; 2],
+        frame: usize,
+        physics: &Physics,
+        max_delta_t: f32,
+    ) -> f32 {
+        let (read, write) = split(particles, frame);
+        let (accelerations, delta_t) = match self.controller {
+            Some(controller) => start(solver, controller, read, physics, max_delta_t),
+            None => (all_accelerations(solver, read, physics), max_delta_t),
+        };
+
+        rk4::step(solver, read, write, physics, &accelerations, delta_t);
+        delta_t
+    }
+
+    fn hermite(
+        &mut self,
+        particles: &mut [Vec<Vertex>; 2],
+        frame: usize,
+        physics: &Physics,
+        max_delta_t: f32,
+    ) -> f32 {
+        let (read, write) = split(particles, frame);
+        let start = self
+            .hermite
+            .take()
+            .unwrap_or_else(|| direct::accelerations_and_jerks(read, physics));
+
+        let delta_t = match self.controller {
+            Some(controller) => {
+                let (accelerations, jerks): (Vec<_>, Vec<_>) =
+                    start.iter().map(|(a, j)| (Some(*a), *j)).unzip();
+                controller.delta_t(&accelerations, Some(&jerks), physics, max_delta_t)
+            }
+            None => max_delta_t,
+        };
+
+        self.hermite = Some(hermite::step(read, write, physics, &start, delta_t));
+        delta_t
+    }
+}
+
+/// Accelerations of the particles at the start of an adaptive step and
+/// the timestep they ask for.
+fn start(
+    solver: &mut dyn CpuSolver,
+    controller: TimestepController,
+    particles: &[Vertex],
+    physics: &Physics,
+    max_delta_t: f32,
+) -> (Vec<Option<Vec2>>, f32) {
+    if !controller.needs_jerks() {
+        let accelerations = all_accelerations(solver, particles, physics);
+        let delta_t = controller.delta_t(&accelerations, None, physics, max_delta_t);
+        return (accelerations, delta_t);
+    }
+
+    // The jerk criterion is only allowed with direct summation, which
+    // gives the same accelerations.
+    let (accelerations, jerks): (Vec<_>, Vec<_>) =
+        direct::accelerations_and_jerks(particles, physics)
+            .into_iter()
+            .map(|(a, j)| (Some(a), j))
+            .unzip();
+    let delta_t = controller.delta_t(&accelerations, Some(&jerks), physics, max_delta_t);
+    (accelerations, delta_t)
+}
+
+fn all_accelerations(
+    solver: &mut dyn CpuSolver,
+    particles: &[Vertex],
+    physics: &Physics,
+) -> Vec<Option<Vec2>> {
+    let targets = (0..particles.len() as u32).collect::<Vec<_>>();
+    solver.accelerations(particles, &targets, physics)
+}
+
+/// The buffer the step of `frame` reads and the one it writes.
+fn split(particles: &mut [Vec<Vertex>; 2], frame: usize) -> (&[Vertex], &mut [Vertex]) {
+    let [first, second] = particles;
+    if frame == 0 {
+        (second, first)
+    } else {
+        (first, second)
+    }
+}
@@ -0,0 +1,119 @@
+//! The velocity and position updates of the integrators, shared by the CPU
+//! solvers and the push constants of the compute shaders.
+//!
+//! Leapfrog keeps the velocities half a step behind the positions between
+//! steps, so each step is a single force evaluation, `v += a dt` followed by
+//! `x += v dt`. Merging the closing kick of one step with the opening kick
+//! of the next makes this the same as kick-drift-kick. A run starts with a
+//! half kick backwards, and snapshots apply the missing half kick forwards
+//! to report velocities at the same time as the positions.
+//!
+//! Yoshida's integrator composes three leapfrog steps of `w1`, `w0` and `w1`
+//! steps, so it is three such passes with the velocities `w1 / 2` steps
+//! behind. RK4 and Hermite need the accelerations of several states at once
+//! and only run on the host, see `host.rs`. Block timesteps give every
+//! particle its own timestep, see `block.rs`.
+
+use crate::config::Integrator;
+use crate::data::vertex::Vertex;
+
+pub mod block;
+pub mod hermite;
+pub mod host;
+pub mod rk4;
+pub mod timestep;
+
+/// `1 / (2 - 2^(1/3))`
+const YOSHIDA_W1: f32 = 1.351_207_2;
+/// `-2^(1/3) / (2 - 2^(1/3))`
+const YOSHIDA_W0: f32 = -1.702_414_4;
+
+const EULER: [Update; 1] = [Update {
+    kick: 1.0,
+    drift: 1.0,
+    symplectic: false,
+}];
+
+const LEAPFROG: [Update; 1] = [Update {
+    kick: 1.0,
+    drift: 1.0,
+    symplectic: true,
+}];
+
+const YOSHIDA4: [Update; 3] = [
+    Update {
+        kick: YOSHIDA_W1,
+        drift: YOSHIDA_W1,
+        symplectic: true,
+    },
+    Update {
+        kick: (YOSHIDA_W0 + YOSHIDA_W1) * 0.5,
+        drift: YOSHIDA_W0,
+        symplectic: true,
+    },
+    Update {
+        kick: (YOSHIDA_W0 + YOSHIDA_W1) * 0.5,
+        drift: YOSHIDA_W1,
+        symplectic: true,
+    },
+];
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// One pass of `v' = v + a dt kick` and `x' = x + u dt drift`, where `u` is
+/// `v'` for the symplectic schemes and `v` for Euler.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Update {
+    pub kick: f32,
+    pub drift: f32,
+    pub symplectic: bool,
+}
+
+impl Update {
+    /// The passes of a full step of `integrator`, empty for the integrators
+    /// that are not a sequence of updates. Pass `i` of the step of frame `f`
+    /// writes the buffer of frame `(f + i) % 2`, and as there is an odd
+    /// number of them the step ends in the buffer of frame `f`.
+    pub fn passes(integrator: Integrator) -> &'static [Update] {
+        match integrator {
+            Integrator::Euler => &EULER,
+            Integrator::Leapfrog => &LEAPFROG,
+            Integrator::Yoshida4 => &YOSHIDA4,
+            Integrator::Rk4 | Integrator::Hermite | Integrator::Block => &[],
+        }
+    }
+
+    /// Steps the velocities of `integrator` are behind the positions
+    /// between steps, half of its first kick.
+    pub fn lag(integrator: Integrator) -> f32 {
+        if integrator.staggered() {
+            Self::passes(integrator)[0].kick * 0.5
+        } else {
+            0.0
+        }
+    }
+
+    /// Only changes the velocities by `kick` steps.
+    pub fn kick(kick: f32) -> Self {
+        Self {
+            kick,
+            drift: 0.0,
+            symplectic: true,
+        }
+    }
+
+    pub fn apply(&self, particle: &Vertex, acceleration: Vec2, delta_t: f32) -> Vertex {
+        let velocity = particle.velocity + acceleration * (delta_t * self.kick);
+        let drift_velocity = if self.symplectic {
+            velocity
+        } else {
+            particle.velocity
+        };
+
+        Vertex {
+            pos: particle.pos + drift_velocity * (delta_t * self.drift),
+            velocity,
+            ..*particle
+        }
+    }
+}
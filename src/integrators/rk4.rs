@@ -0,0 +1,76 @@
+//! Classic fourth order Runge–Kutta over positions and velocities, with
+//! four force evaluations per step.
+
+use rayon::prelude::*;
+
+use crate::config::Physics;
+use crate::cpu::CpuSolver;
+use crate::data::vertex::Vertex;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Advances `read` into `write`, starting from the `accelerations` of
+/// `read`. Particles the solver skips in any stage keep their state.
+pub fn step(
+    solver: &mut dyn CpuSolver,
+    read: &[Vertex],
+    write: &mut [Vertex],
+    physics: &Physics,
+    accelerations: &[Option<Vec2>],
+    delta_t: f32,
+) {
+    let targets = (0..read.len() as u32).collect::<Vec<_>>();
+
+    let first = stage(read, read, accelerations, delta_t * 0.5);
+    let first_accelerations = solver.accelerations(&first, &targets, physics);
+    let second = stage(read, &first, &first_accelerations, delta_t * 0.5);
+    let second_accelerations = solver.accelerations(&second, &targets, physics);
+    let third = stage(read, &second, &second_accelerations, delta_t);
+    let third_accelerations = solver.accelerations(&third, &targets, physics);
+
+    write.par_iter_mut().enumerate().for_each(|(i, out)| {
+        let particle = read[i];
+        *out = particle;
+
+        let (Some(a0), Some(a1), Some(a2), Some(a3)) = (
+            accelerations[i],
+            first_accelerations[i],
+            second_accelerations[i],
+            third_accelerations[i],
+        ) else {
+            return;
+        };
+
+        let velocity =
+            particle.velocity + (first[i].velocity + second[i].velocity) * 2.0 + third[i].velocity;
+        let acceleration = a0 + (a1 + a2) * 2.0 + a3;
+
+        *out = Vertex {
+            pos: particle.pos + velocity * (delta_t / 6.0),
+            velocity: particle.velocity + acceleration * (delta_t / 6.0),
+            ..particle
+        };
+    });
+}
+
+/// `initial` moved by `delta_t` along the velocities of `rates` and the
+/// `accelerations`.
+fn stage(
+    initial: &[Vertex],
+    rates: &[Vertex],
+    accelerations: &[Option<Vec2>],
+    delta_t: f32,
+) -> Vec<Vertex> {
+    initial
+        .par_iter()
+        .zip(rates.par_iter().zip(accelerations))
+        .map(|(particle, (rate, acceleration))| match acceleration {
+            Some(acceleration) => Vertex {
+                pos: particle.pos + rate.velocity * delta_t,
+                velocity: particle.velocity + acceleration * delta_t,
+                ..*particle
+            },
+            None => *particle,
+        })
+        .collect()
+}
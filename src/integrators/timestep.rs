@@ -0,0 +1,73 @@
+//! Global adaptive timestep. Every step takes the smallest timestep any
+//! particle asks for, between `MIN_ADAPTIVE_DELTA_T` of the largest one and
+//! the largest one itself.
+
+use cgmath::InnerSpace;
+
+use crate::config::{Physics, RunConfig, TimestepCriterion};
+use crate::data::globals;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct TimestepController {
+    criterion: TimestepCriterion,
+    eta: f32,
+}
+
+impl TimestepController {
+    /// The controller of adaptive runs, `None` for fixed timesteps.
+    pub fn new(config: &RunConfig) -> Option<Self> {
+        config.adaptive.map(|criterion| Self {
+            criterion,
+            eta: config.eta,
+        })
+    }
+
+    pub fn needs_jerks(&self) -> bool {
+        self.criterion == TimestepCriterion::Jerk
+    }
+
+    /// Timestep up to `max_delta_t` for particles with `accelerations`, and
+    /// `jerks`, which the jerk criterion needs. Particles without an
+    /// acceleration are ignored.
+    pub fn delta_t(
+        &self,
+        accelerations: &[Option<Vec2>],
+        jerks: Option<&[Vec2]>,
+        physics: &Physics,
+        max_delta_t: f32,
+    ) -> f32 {
+        let delta_t = match self.criterion {
+            TimestepCriterion::Acceleration => {
+                let max_acceleration = accelerations
+                    .iter()
+                    .flatten()
+                    .map(|a| a.magnitude())
+                    .filter(|a| a.is_finite())
+                    .fold(0.0, f32::max);
+
+                acceleration_delta_t(self.eta, physics, max_acceleration)
+            }
+            TimestepCriterion::Jerk => {
+                let jerks = jerks.expect("the jerk criterion needs jerks");
+                let min_ratio = accelerations
+                    .iter()
+                    .zip(jerks)
+                    .filter_map(|(a, j)| Some(a.as_ref()?.magnitude() / j.magnitude()))
+                    .filter(|ratio| ratio.is_finite())
+                    .fold(f32::INFINITY, f32::min);
+
+                self.eta * min_ratio
+            }
+        };
+
+        delta_t.clamp(max_delta_t * globals::MIN_ADAPTIVE_DELTA_T, max_delta_t)
+    }
+}
+
+/// `sqrt(2 eta softening / |a|)` for an acceleration of magnitude
+/// `acceleration`.
+pub fn acceleration_delta_t(eta: f32, physics: &Physics, acceleration: f32) -> f32 {
+    (2.0 * eta * physics.softening / acceleration).sqrt()
+}
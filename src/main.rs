@@ -1,31 +1,105 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use app::App;
+use clap::Parser;
+use config::{cli::Args, metadata, RunConfig};
+use cpu::simulation::CpuSimulation;
 use data::globals;
-use log::info;
+use headless_app::HeadlessApp;
+use log::{info, warn};
+use snapshot::Snapshot;
 use vulkanalia::prelude::v1_0::*;
 use winit::{
     dpi::LogicalSize,
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
 
 mod app;
+mod config;
+mod cpu;
 mod data;
 mod generators;
+mod headless_app;
 mod init;
+mod integrators;
+mod snapshot;
+mod solvers;
+mod tools;
 mod utils;
 
 fn main() -> Result<()> {
     pretty_env_logger::init();
 
+    let mut args = Args::parse();
+    if let Some(command) = args.command.take() {
+        return tools::run(command);
+    }
+
+    let mut config = args.into_run_config()?;
+    config.resume_header()?;
+    config.validate()?;
+    let initial = config.initial_state()?;
+
+    if config.snapshot_every.is_some() {
+        let path = metadata::write_run_metadata(&config, initial.header.step)?;
+        info!("wrote {}", path.display());
+    }
+
+    let host = config.headless && !config.steps_on_device();
+    if host && !config.cpu {
+        warn!(
+            "the {:?} integrator with the {:?} solver{} does not step on the device, running on the CPU",
+            config.integrator,
+            config.solver,
+            if config.adaptive.is_some() { " and adaptive timesteps" } else { "" }
+        );
+    }
+
+    if config.cpu || host {
+        run_cpu(&config, initial)
+    } else if config.headless {
+        run_headless(&config, initial)
+    } else {
+        run_windowed(&config, initial)
+    }
+}
+
+fn run_cpu(config: &RunConfig, initial: Snapshot) -> Result<()> {
+    let steps = config
+        .steps
+        .ok_or_else(|| anyhow!("Headless runs need a step count"))?;
+
+    CpuSimulation::create(initial, config).run(steps)?;
+
+    info!("DONE");
+    Ok(())
+}
+
+fn run_headless(config: &RunConfig, initial: Snapshot) -> Result<()> {
+    let steps = config
+        .steps
+        .ok_or_else(|| anyhow!("Headless runs need a step count"))?;
+    let mut app = unsafe { HeadlessApp::create(initial, config)? };
+
+    unsafe {
+        app.run(steps)?;
+        globals::get_device().device_wait_idle()?;
+        app.destroy();
+    }
+
+    info!("DONE");
+    Ok(())
+}
+
+fn run_windowed(config: &RunConfig, initial: Snapshot) -> Result<()> {
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("gravity simulator")
-        .with_inner_size(LogicalSize::new(1024, 768))
+        .with_inner_size(LogicalSize::new(config.width, config.height))
         .build(&event_loop)?;
 
-    let mut app = unsafe { App::create(&window)? };
+    let mut app = unsafe { App::create(&window, initial, config)? };
 
     let mut destroying = false;
     let mut minimized = false;
@@ -35,6 +109,16 @@ fn main() -> Result<()> {
         match event {
             Event::MainEventsCleared if !destroying && !minimized => unsafe {
                 app.render(&window).unwrap();
+
+                if app.finished() {
+                    destroying = true;
+                    *control_flow = ControlFlow::Exit;
+                    app.write_snapshot().unwrap();
+                    globals::get_device().device_wait_idle().unwrap();
+                    app.destroy();
+
+                    info!("DONE");
+                }
             },
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
@@ -43,12 +127,36 @@ fn main() -> Result<()> {
                 destroying = true;
                 *control_flow = ControlFlow::Exit;
                 unsafe {
+                    app.write_snapshot().unwrap();
                     globals::get_device().device_wait_idle().unwrap();
                     app.destroy();
                 }
 
                 info!("DONE");
             }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(key),
+                                ..
+                            },
+                        ..
+                    },
+                ..
+            } => match key {
+                VirtualKeyCode::Space => app.toggle_pause(),
+                VirtualKeyCode::Period | VirtualKeyCode::Right => app.request_step(),
+                VirtualKeyCode::Up | VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => {
+                    app.scale_time(2.0)
+                }
+                VirtualKeyCode::Down | VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => {
+                    app.scale_time(0.5)
+                }
+                _ => {}
+            },
             Event::WindowEvent {
                 event: WindowEvent::Resized(size),
                 ..
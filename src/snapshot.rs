@@ -0,0 +1,443 @@
+//! Binary snapshots of the full particle state.
+//!
+//! All values are little endian. A snapshot starts with a fixed size header
+//! followed by the particle arrays:
+//!
+//! | offset | size | field                                           |
+//! |--------|------|-------------------------------------------------|
+//! | 0      | 8    | magic `GSIM2DSN`                                |
+//! | 8      | 4    | format version (`u32`, currently 5)             |
+//! | 12     | 4    | flags (`u32`, bit 0: seed is set, bit 1:        |
+//! |        |      | adaptive softening)                             |
+//! | 16     | 8    | particle count `n` (`u64`)                      |
+//! | 24     | 8    | step (`u64`)                                    |
+//! | 32     | 8    | simulation time (`f64`)                         |
+//! | 40     | 8    | RNG seed of the initial conditions (`u64`)      |
+//! | 48     | 4    | timestep (`f32`, 0 if it followed the clock)    |
+//! | 52     | 4    | gravitational constant (`f32`)                  |
+//! | 56     | 4    | particle mass (`f32`)                           |
+//! | 60     | 4    | softening (`f32`)                               |
+//! | 64     | 4    | softening kernel (`u32`, 0 cutoff, 1 Plummer,   |
+//! |        |      | 2 spline)                                       |
+//! | 68     | 4    | boundary (`u32`, 0 open, 1 periodic,            |
+//! |        |      | 2 reflecting, 3 absorbing)                      |
+//! | 72     | 8n   | positions, `n` pairs of `f32` x, y              |
+//! | 72+8n  | 8n   | velocities, `n` pairs of `f32` x, y             |
+//! | 72+16n | 4n   | masses, `n` `f32`                               |
+//! | 72+20n | 4n   | species, `n` `u32`                              |
+//!
+//! The physics are in the units of the run with world lengths. Before
+//! version 3 they were in normalized field units and are converted, see
+//! `Physics::from_field_units`. Version 1 snapshots end after the
+//! velocities and are read with every particle of the default species and
+//! the particle mass of the header. Before version 4 the header ends after
+//! the softening and the forces were cut off below it. Before version 5 it
+//! ends after the kernel and the boundary is open, except for the runs of
+//! the particle-mesh solver, which is always periodic.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use cgmath::vec2;
+
+use crate::config::{Boundary, Physics, SofteningKernel};
+use crate::data::globals;
+use crate::data::vertex::Vertex;
+
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"GSIM2DSN";
+pub const SNAPSHOT_VERSION: u32 = 5;
+pub const DEFAULT_SNAPSHOT_DIR: &str = "snapshots";
+
+const FLAG_SEED: u32 = 1;
+const FLAG_ADAPTIVE_SOFTENING: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotHeader {
+    pub particle_count: u64,
+    pub step: u64,
+    pub sim_time: f64,
+    pub seed: Option<u64>,
+    pub dt: f32,
+    pub physics: Physics,
+}
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub header: SnapshotHeader,
+    pub vertices: Vec<Vertex>,
+}
+
+impl SnapshotHeader {
+    /// Reads only the header, without the particles.
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        read_header(&mut BufReader::new(file))
+            .map(|(header, _)| header)
+            .map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+}
+
+impl Snapshot {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        let mut writer = BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes the snapshot into `dir` as `snapshot_<step>.bin`, creating the
+    /// directory if needed.
+    pub fn save_to_dir(&self, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir).map_err(|e| anyhow!("{}: {}", dir.display(), e))?;
+
+        let path = dir.join(format!("snapshot_{:010}.bin", self.header.step));
+        self.save(&path)?;
+
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+        Self::read(&mut BufReader::new(file)).map_err(|e| anyhow!("{}: {}", path.display(), e))
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        let header = &self.header;
+        if header.particle_count != self.vertices.len() as u64 {
+            return Err(anyhow!("Snapshot header does not match the particle count"));
+        }
+
+        let mut flags = 0;
+        if header.seed.is_some() {
+            flags |= FLAG_SEED;
+        }
+        if header.physics.adaptive_softening {
+            flags |= FLAG_ADAPTIVE_SOFTENING;
+        }
+
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        writer.write_all(&flags.to_le_bytes())?;
+        writer.write_all(&header.particle_count.to_le_bytes())?;
+        writer.write_all(&header.step.to_le_bytes())?;
+        writer.write_all(&header.sim_time.to_le_bytes())?;
+        writer.write_all(&header.seed.unwrap_or_default().to_le_bytes())?;
+        writer.write_all(&header.dt.to_le_bytes())?;
+        writer.write_all(&header.physics.gravitational_constant.to_le_bytes())?;
+        writer.write_all(&header.physics.particle_mass.to_le_bytes())?;
+        writer.write_all(&header.physics.softening.to_le_bytes())?;
+        writer.write_all(&(header.physics.kernel as u32).to_le_bytes())?;
+        writer.write_all(&(header.physics.boundary as u32).to_le_bytes())?;
+
+        for v in &self.vertices {
+            writer.write_all(&v.pos.x.to_le_bytes())?;
+            writer.write_all(&v.pos.y.to_le_bytes())?;
+        }
+
+        for v in &self.vertices {
+            writer.write_all(&v.velocity.x.to_le_bytes())?;
+            writer.write_all(&v.velocity.y.to_le_bytes())?;
+        }
+
+        for v in &self.vertices {
+            writer.write_all(&v.mass.to_le_bytes())?;
+        }
+
+        for v in &self.vertices {
+            writer.write_all(&v.species.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self> {
+        let (header, version) = read_header(reader)?;
+
+        let count = usize::try_from(header.particle_count)?;
+        // The count comes from the file, so it only hints the allocation.
+        let mut vertices = Vec::with_capacity(count.min(1 << 20));
+        for _ in 0..count {
+            let pos = vec2(read_f32(reader)?, read_f32(reader)?);
+            vertices.push(
+                Vertex::new(pos, vec2(0.0, 0.0)).with_species(0, header.physics.particle_mass),
+            );
+        }
+
+        for v in vertices.iter_mut() {
+            v.velocity = vec2(read_f32(reader)?, read_f32(reader)?);
+        }
+
+        if version >= 2 {
+            for v in vertices.iter_mut() {
+                v.mass = read_f32(reader)?;
+            }
+
+            for v in vertices.iter_mut() {
+                v.species = read_u32(reader)?;
+                if v.species as usize >= globals::MAX_SPECIES {
+                    return Err(anyhow!("Unknown species {}", v.species));
+                }
+            }
+        }
+
+        Ok(Self { header, vertices })
+    }
+}
+
+/// Steps from `step` to the next multiple of `every`, `u64::MAX` without
+/// snapshots. An interval of zero writes none.
+pub fn steps_until_snapshot(step: u64, every: Option<u64>) -> u64 {
+    every
+        .filter(|&every| every > 0)
+        .map_or(u64::MAX, |every| every - step % every)
+}
+
+/// Reads the header and returns it with the format version.
+fn read_header(reader: &mut impl Read) -> Result<(SnapshotHeader, u32)> {
+    let magic: [u8; 8] = read_bytes(reader)?;
+    if &magic != SNAPSHOT_MAGIC {
+        return Err(anyhow!("Not a snapshot file"));
+    }
+
+    let version = read_u32(reader)?;
+    if !(1..=SNAPSHOT_VERSION).contains(&version) {
+        return Err(anyhow!("Unsupported snapshot version {}", version));
+    }
+
+    let flags = read_u32(reader)?;
+    let particle_count = read_u64(reader)?;
+    let step = read_u64(reader)?;
+    let sim_time = f64::from_le_bytes(read_bytes(reader)?);
+    let seed = read_u64(reader)?;
+    let dt = read_f32(reader)?;
+    let mut physics = Physics {
+        gravitational_constant: read_f32(reader)?,
+        particle_mass: read_f32(reader)?,
+        softening: read_f32(reader)?,
+        kernel: SofteningKernel::Cutoff,
+        adaptive_softening: flags & FLAG_ADAPTIVE_SOFTENING != 0,
+        boundary: Boundary::Open,
+    };
+    if version < 3 {
+        physics = Physics::from_field_units(
+            physics.gravitational_constant,
+            physics.particle_mass,
+            physics.softening,
+        );
+    }
+    if version >= 4 {
+        physics.kernel = match read_u32(reader)? {
+            0 => SofteningKernel::Cutoff,
+            1 => SofteningKernel::Plummer,
+            2 => SofteningKernel::Spline,
+            kernel => return Err(anyhow!("Unknown softening kernel {}", kernel)),
+        };
+    }
+    if version >= 5 {
+        physics.boundary = match read_u32(reader)? {
+            0 => Boundary::Open,
+            1 => Boundary::Periodic,
+            2 => Boundary::Reflecting,
+            3 => Boundary::Absorbing,
+            boundary => return Err(anyhow!("Unknown boundary {}", boundary)),
+        };
+    }
+
+    let header = SnapshotHeader {
+        particle_count,
+        step,
+        sim_time,
+        seed: (flags & FLAG_SEED != 0).then_some(seed),
+        dt,
+        physics,
+    };
+
+    Ok((header, version))
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(|_| anyhow!("Snapshot file is truncated"))?;
+    Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64> {
+    Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_f32(reader: &mut impl Read) -> Result<f32> {
+    Ok(f32::from_le_bytes(read_bytes(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let vertices = vec![
+            Vertex::new(vec2(0.5, -0.25), vec2(1.0, 2.0)).with_species(0, 0.1),
+            Vertex::new(vec2(-1.0, 0.75), vec2(-3.0, 0.5)).with_species(2, 40.0),
+        ];
+        Snapshot {
+            header: SnapshotHeader {
+                particle_count: vertices.len() as u64,
+                step: 1000,
+                sim_time: 12.5,
+                seed: Some(42),
+                dt: 0.001,
+                physics: Physics {
+                    gravitational_constant: 4.5,
+                    particle_mass: 0.1,
+                    softening: 0.02,
+                    kernel: SofteningKernel::Spline,
+                    adaptive_softening: true,
+                    boundary: Boundary::Reflecting,
+                },
+            },
+            vertices,
+        }
+    }
+
+    fn to_bytes(snapshot: &Snapshot) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        snapshot.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// The header of an older version up to the softening, with `G = 2`,
+    /// a particle mass of 0.5 and a softening of 0.1.
+    fn old_header(version: u32, count: u64) -> Vec<u8> {
+        let mut bytes = SNAPSHOT_MAGIC.to_vec();
+        bytes.extend(version.to_le_bytes());
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(count.to_le_bytes());
+        bytes.extend(7u64.to_le_bytes());
+        bytes.extend(0.5f64.to_le_bytes());
+        bytes.extend(0u64.to_le_bytes());
+        bytes.extend(0.01f32.to_le_bytes());
+        for value in [2.0f32, 0.5, 0.1] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// One particle at (1, 2) moving with (3, 4).
+    fn old_particle(bytes: &mut Vec<u8>) {
+        for value in [1.0f32, 2.0, 3.0, 4.0] {
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let written = snapshot();
+        let read = Snapshot::read(&mut to_bytes(&written).as_slice()).unwrap();
+
+        assert_eq!(read.header.particle_count, 2);
+        assert_eq!(read.header.step, written.header.step);
+        assert_eq!(read.header.sim_time, written.header.sim_time);
+        assert_eq!(read.header.seed, written.header.seed);
+        assert_eq!(read.header.dt, written.header.dt);
+        assert_eq!(read.header.physics, written.header.physics);
+        for (a, b) in read.vertices.iter().zip(&written.vertices) {
+            assert_eq!((a.pos, a.velocity), (b.pos, b.velocity));
+            assert_eq!((a.mass, a.species), (b.mass, b.species));
+        }
+
+        let header = read_header(&mut to_bytes(&written).as_slice()).unwrap();
+        assert_eq!(header.1, SNAPSHOT_VERSION);
+        assert_eq!(header.0.physics, written.header.physics);
+    }
+
+    #[test]
+    fn reads_version_1() {
+        let mut bytes = old_header(1, 1);
+        old_particle(&mut bytes);
+        let read = Snapshot::read(&mut bytes.as_slice()).unwrap();
+
+        let physics = Physics::from_field_units(2.0, 0.5, 0.1);
+        assert_eq!(read.header.physics, physics);
+        assert_eq!(read.header.seed, None);
+        assert_eq!(read.header.step, 7);
+        let v = read.vertices[0];
+        assert_eq!((v.pos, v.velocity), (vec2(1.0, 2.0), vec2(3.0, 4.0)));
+        assert_eq!((v.mass, v.species), (0.5, 0));
+    }
+
+    #[test]
+    fn reads_versions_2_to_4() {
+        for version in 2..=4 {
+            let mut bytes = old_header(version, 1);
+            if version == 4 {
+                bytes.extend((SofteningKernel::Plummer as u32).to_le_bytes());
+            }
+            old_particle(&mut bytes);
+            bytes.extend(3.0f32.to_le_bytes());
+            bytes.extend(1u32.to_le_bytes());
+            let read = Snapshot::read(&mut bytes.as_slice()).unwrap();
+
+            let physics = read.header.physics;
+            if version == 2 {
+                assert_eq!(physics, Physics::from_field_units(2.0, 0.5, 0.1));
+            } else {
+                assert_eq!(
+                    (physics.gravitational_constant, physics.particle_mass),
+                    (2.0, 0.5)
+                );
+                assert_eq!(physics.softening, 0.1);
+            }
+            let kernel = match version {
+                4 => SofteningKernel::Plummer,
+                _ => SofteningKernel::Cutoff,
+            };
+            assert_eq!(physics.kernel, kernel);
+            assert_eq!(physics.boundary, Boundary::Open);
+            let v = read.vertices[0];
+            assert_eq!((v.mass, v.species), (3.0, 1));
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_and_invalid_files() {
+        let bytes = to_bytes(&snapshot());
+        for len in [0, 4, 20, 71, 72, bytes.len() - 1] {
+            let error = Snapshot::read(&mut &bytes[..len]).unwrap_err();
+            assert_eq!(error.to_string(), "Snapshot file is truncated", "{}", len);
+        }
+
+        let mut bytes = old_header(1, 2);
+        old_particle(&mut bytes);
+        assert!(Snapshot::read(&mut bytes.as_slice()).is_err());
+
+        let mut bytes = to_bytes(&snapshot());
+        bytes[8] = SNAPSHOT_VERSION as u8 + 1;
+        let error = Snapshot::read(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported snapshot version 6");
+
+        let mut bytes = to_bytes(&snapshot());
+        let species = bytes.len() - 4;
+        bytes[species..].copy_from_slice(&(globals::MAX_SPECIES as u32).to_le_bytes());
+        assert!(Snapshot::read(&mut bytes.as_slice()).is_err());
+
+        assert!(Snapshot::read(&mut &b"GSIM2DXX"[..]).is_err());
+    }
+
+    #[test]
+    fn counts_the_steps_to_the_next_snapshot() {
+        assert_eq!(steps_until_snapshot(0, Some(10)), 10);
+        assert_eq!(steps_until_snapshot(7, Some(10)), 3);
+        assert_eq!(steps_until_snapshot(10, Some(10)), 10);
+        assert_eq!(steps_until_snapshot(5, Some(0)), u64::MAX);
+        assert_eq!(steps_until_snapshot(5, None), u64::MAX);
+    }
+}
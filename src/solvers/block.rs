@@ -0,0 +1,379 @@
+use anyhow::Result;
+use std::mem::{size_of, size_of_val};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{Physics, RunConfig};
+use crate::data::commands_data::CommandsData;
+use crate::data::common_data::CommonData;
+use crate::data::descriptors_data::DescriptorsData;
+use crate::data::globals;
+use crate::data::pipeline_data::PipelineData;
+use crate::data::push_constants::{BlockPushConstants, DirectPushConstants};
+use crate::data::vertex::Vertex;
+use crate::init::{descriptors, pipeline};
+use crate::solvers::GravitySolver;
+use crate::utils::resources::{self, memory_barrier};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Modes of `block.comp`.
+const OPEN: u32 = 0;
+const DRIFT: u32 = 1;
+const SELECT: u32 = 2;
+const ARGS: u32 = 3;
+const KICK: u32 = 4;
+
+/// Block timesteps with direct summation, see `integrators/block.rs`. Each
+/// tick `block.comp` lists the particles whose timestep ends, `direct.comp`
+/// evaluates the forces on them with an indirect dispatch and `block.comp`
+/// kicks them.
+#[derive(Debug)]
+pub struct BlockSolver {
+    physics: Physics,
+    eta: f32,
+    max_level: u32,
+    particle_count: usize,
+    delta_t: f32,
+
+    /// Indirect dispatch and indices of the particles due at a tick.
+    targets_buffer: vk::Buffer,
+    targets_memory: vk::DeviceMemory,
+    accelerations_buffer: vk::Buffer,
+    accelerations_memory: vk::DeviceMemory,
+    /// Latest acceleration and level of every particle.
+    timesteps_buffer: vk::Buffer,
+    timesteps_memory: vk::DeviceMemory,
+
+    direct_pipeline: PipelineData,
+    block_pipeline: PipelineData,
+    /// Direct sets evaluating the particles of each frame.
+    direct_descriptors: DescriptorsData,
+    block_descriptors: DescriptorsData,
+}
+
+impl BlockSolver {
+    /// Creates the solver and evaluates the forces on all particles, which
+    /// the first step opens the timesteps with.
+    pub unsafe fn create(
+        instance: &Instance,
+        common: &CommonData,
+        commands: &CommandsData,
+        storage_buffers: &[vk::Buffer],
+        vertices: &[Vertex],
+        config: &RunConfig,
+    ) -> Result<Self> {
+        let particles_size = size_of_val(vertices) as u64;
+        let targets_size = ((4 + vertices.len()) * size_of::<u32>()) as u64;
+        let accelerations_size = (vertices.len() * size_of::<Vec2>()) as u64;
+        // `Timestep` of `block.comp`, padded to the alignment of its `vec2`.
+        let timesteps_size = (vertices.len() * 4 * size_of::<u32>()) as u64;
+
+        let (targets_buffer, targets_memory) = resources::create_buffer(
+            instance,
+            common,
+            targets_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER
+                | vk::BufferUsageFlags::INDIRECT_BUFFER
+                | vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        let (accelerations_buffer, accelerations_memory) = resources::create_buffer(
+            instance,
+            common,
+            accelerations_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+        let (timesteps_buffer, timesteps_memory) = resources::create_buffer(
+            instance,
+            common,
+            timesteps_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let mut direct_descriptors = DescriptorsData::default();
+        let mut block_descriptors = DescriptorsData::default();
+        let mut direct_pipeline = PipelineData::default();
+        let mut block_pipeline = PipelineData::default();
+
+        direct_descriptors.descriptor_set_layout =
+            descriptors::create_direct_descriptor_set_layout()?;
+        direct_descriptors.descriptor_pool =
+            descriptors::create_direct_descriptor_pool(globals::MAX_FRAMES_IN_FLIGHT as u32)?;
+        block_descriptors.descriptor_set_layout =
+            descriptors::create_block_descriptor_set_layout()?;
+        block_descriptors.descriptor_pool =
+            descriptors::create_block_descriptor_pool(globals::MAX_FRAMES_IN_FLIGHT as u32)?;
+
+        for frame in 0..globals::MAX_FRAMES_IN_FLIGHT {
+            descriptors::create_storage_descriptor_set(
+                [
+                    (storage_buffers[frame], particles_size),
+                    (targets_buffer, targets_size),
+                    (accelerations_buffer, accelerations_size),
+                ],
+                &mut direct_descriptors,
+            )?;
+
+            descriptors::create_storage_descriptor_set(
+                [
+                    (storage_buffers[(frame + 1) % 2], particles_size),
+                    (storage_buffers[frame], particles_size),
+                    (targets_buffer, targets_size),
+                    (accelerations_buffer, accelerations_size),
+                    (timesteps_buffer, timesteps_size),
+                ],
+                &mut block_descriptors,
+            )?;
+        }
+
+        pipeline::create_direct_compute_pipeline(&direct_descriptors, &mut direct_pipeline)?;
+        pipeline::create_block_compute_pipeline(&block_descriptors, &mut block_pipeline)?;
+
+        let solver = Self {
+            physics: config.physics,
+            eta: config.eta,
+            max_level: config.block_levels.max(1) - 1,
+            particle_count: vertices.len(),
+            delta_t: config.dt.unwrap_or(globals::DEFAULT_DELTA_T),
+            targets_buffer,
+            targets_memory,
+            accelerations_buffer,
+            accelerations_memory,
+            timesteps_buffer,
+            timesteps_memory,
+            direct_pipeline,
+            block_pipeline,
+            direct_descriptors,
+            block_descriptors,
+        };
+
+        // Both storage buffers hold the initial particles. Every particle
+        // is due at the last tick, without a timestep to close.
+        let command_buffer = resources::begin_single_time_commands(commands)?;
+        solver.record_tick(command_buffer, 0, solver.ticks(), false);
+        resources::end_single_time_commands(common, commands, command_buffer)?;
+
+        Ok(solver)
+    }
+
+    fn ticks(&self) -> u32 {
+        1 << self.max_level
+    }
+
+    fn push_constants(&self, mode: u32, tick: u32, closing: bool) -> BlockPushConstants {
+        BlockPushConstants {
+            mode,
+            particle_count: self.particle_count as u32,
+            tick,
+            max_level: self.max_level,
+            delta_t: self.delta_t,
+            eta: self.eta,
+            softening: self.physics.softening,
+            closing: closing as u32,
+            boundary: self.physics.boundary as u32,
+        }
+    }
+
+    /// Dispatches a pass of `block.comp`, the kicks over the listed
+    /// particles and the other passes over all of them.
+    unsafe fn record_block(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        push_constants: BlockPushConstants,
+    ) {
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.block_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.block_pipeline.pipeline_layout,
+            0,
+            &[self.block_descriptors.descriptor_sets[frame]],
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.block_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            push_constants.as_bytes(),
+        );
+
+        match push_constants.mode {
+            KICK => {
+                globals::get_device().cmd_dispatch_indirect(command_buffer, self.targets_buffer, 0)
+            }
+            ARGS => globals::get_device().cmd_dispatch(command_buffer, 1, 1, 1),
+            _ => {
+                let group_count = (self.particle_count as u32).div_ceil(256);
+                globals::get_device().cmd_dispatch(command_buffer, group_count, 1, 1);
+            }
+        }
+
+        pass_barrier(command_buffer);
+    }
+
+    /// Lists the particles of `frame` due at `tick`, evaluates their forces
+    /// and kicks them.
+    unsafe fn record_tick(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        tick: u32,
+        closing: bool,
+    ) {
+        pass_barrier(command_buffer);
+        globals::get_device().cmd_fill_buffer(
+            command_buffer,
+            self.targets_buffer,
+            0,
+            (4 * size_of::<u32>()) as u64,
+            0,
+        );
+        pass_barrier(command_buffer);
+
+        self.record_block(
+            command_buffer,
+            frame,
+            self.push_constants(SELECT, tick, closing),
+        );
+        self.record_block(
+            command_buffer,
+            frame,
+            self.push_constants(ARGS, tick, closing),
+        );
+
+        let direct_push_constants = DirectPushConstants {
+            particle_count: self.particle_count as u32,
+            gravitational_constant: self.physics.field_gravitational_constant(),
+            softening: self.physics.field_softening(),
+            kernel: self.physics.kernel as u32,
+            particle_mass: self.physics.particle_mass,
+            adaptive_softening: self.physics.adaptive_softening as u32,
+            boundary: self.physics.boundary as u32,
+        };
+
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.direct_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.direct_pipeline.pipeline_layout,
+            0,
+            &[self.direct_descriptors.descriptor_sets[frame]],
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.direct_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            direct_push_constants.as_bytes(),
+        );
+
+        globals::get_device().cmd_dispatch_indirect(command_buffer, self.targets_buffer, 0);
+        pass_barrier(command_buffer);
+
+        self.record_block(
+            command_buffer,
+            frame,
+            self.push_constants(KICK, tick, closing),
+        );
+    }
+}
+
+/// Makes the writes of a pass visible to the next one, which may read the
+/// indirect dispatch, or fill the targets buffer.
+unsafe fn pass_barrier(command_buffer: vk::CommandBuffer) {
+    let stages = vk::PipelineStageFlags::COMPUTE_SHADER
+        | vk::PipelineStageFlags::TRANSFER
+        | vk::PipelineStageFlags::DRAW_INDIRECT;
+
+    memory_barrier(
+        command_buffer,
+        stages,
+        vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
+        stages,
+        vk::AccessFlags::SHADER_READ
+            | vk::AccessFlags::SHADER_WRITE
+            | vk::AccessFlags::TRANSFER_WRITE
+            | vk::AccessFlags::INDIRECT_COMMAND_READ,
+    );
+}
+
+impl GravitySolver for BlockSolver {
+    unsafe fn prepare(&mut self, _frame: usize, delta_t: f32) -> Result<f32> {
+        self.delta_t = delta_t;
+        Ok(delta_t)
+    }
+
+    unsafe fn record(&self, command_buffer: vk::CommandBuffer, frame: usize) -> Result<()> {
+        pass_barrier(command_buffer);
+        self.record_block(command_buffer, frame, self.push_constants(OPEN, 0, true));
+        self.record_tick(command_buffer, frame, 1, true);
+
+        for tick in 2..=self.ticks() {
+            self.record_block(
+                command_buffer,
+                frame,
+                self.push_constants(DRIFT, tick, true),
+            );
+            self.record_tick(command_buffer, frame, tick, true);
+        }
+
+        Ok(())
+    }
+
+    /// The velocities are at the time of the positions between steps, so
+    /// there is nothing to kick.
+    unsafe fn record_kick(
+        &mut self,
+        _command_buffer: vk::CommandBuffer,
+        _frame: usize,
+        _kick: f32,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn steps_on_device(&self) -> bool {
+        true
+    }
+
+    unsafe fn destroy(&mut self) {
+        self.direct_pipeline = PipelineData::default();
+        self.block_pipeline = PipelineData::default();
+        self.direct_descriptors = DescriptorsData::default();
+        self.block_descriptors = DescriptorsData::default();
+
+        if globals::get_device().device_wait_idle().is_err() {
+            return;
+        }
+
+        for (buffer, memory) in [
+            (&mut self.targets_buffer, &mut self.targets_memory),
+            (
+                &mut self.accelerations_buffer,
+                &mut self.accelerations_memory,
+            ),
+            (&mut self.timesteps_buffer, &mut self.timesteps_memory),
+        ] {
+            globals::get_device().destroy_buffer(*buffer, None);
+            globals::get_device().free_memory(*memory, None);
+            *buffer = vk::Buffer::null();
+            *memory = vk::DeviceMemory::null();
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use anyhow::Result;
+use std::mem::{size_of, size_of_val};
+use std::ptr::copy_nonoverlapping as memcpy;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{Integrator, Physics, RunConfig};
+use crate::data::common_data::CommonData;
+use crate::data::descriptors_data::DescriptorsData;
+use crate::data::globals;
+use crate::data::pipeline_data::PipelineData;
+use crate::data::push_constants::{DirectPushConstants, IntegratePushConstants};
+use crate::data::vertex::Vertex;
+use crate::init::{descriptors, pipeline};
+use crate::integrators::Update;
+use crate::solvers::GravitySolver;
+use crate::utils::resources::{self, memory_barrier};
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Sums the forces of all pairs with `direct.comp` and applies them with
+/// `integrate.comp`.
+#[derive(Debug)]
+pub struct DirectSolver {
+    physics: Physics,
+    integrator: Integrator,
+    particle_count: usize,
+    delta_t: f32,
+
+    /// Every particle index, the direct pass evaluates all of them.
+    targets_buffer: vk::Buffer,
+    targets_memory: vk::DeviceMemory,
+    accelerations_buffer: vk::Buffer,
+    accelerations_memory: vk::DeviceMemory,
+
+    direct_pipeline: PipelineData,
+    integrate_pipeline: PipelineData,
+    /// The direct sets of both frames followed by their integrate sets.
+    descriptors: DescriptorsData,
+}
+
+impl DirectSolver {
+    pub unsafe fn create(
+        instance: &Instance,
+        common: &CommonData,
+        storage_buffers: &[vk::Buffer],
+        vertices: &[Vertex],
+        config: &RunConfig,
+    ) -> Result<Self> {
+        let targets = target_list(&(0..vertices.len() as u32).collect::<Vec<_>>());
+        let particles_size = size_of_val(vertices) as u64;
+        let targets_size = size_of_val(targets.as_slice()) as u64;
+        let accelerations_size = (vertices.len() * size_of::<Vec2>()) as u64;
+
+        let host_visible =
+            vk::MemoryPropertyFlags::HOST_COHERENT | vk::MemoryPropertyFlags::HOST_VISIBLE;
+        let (targets_buffer, targets_memory) = resources::create_buffer(
+            instance,
+            common,
+            targets_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            host_visible,
+        )?;
+        let (accelerations_buffer, accelerations_memory) = resources::create_buffer(
+            instance,
+            common,
+            accelerations_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let memory = globals::get_device().map_memory(
+            targets_memory,
+            0,
+            targets_size,
+            vk::MemoryMapFlags::empty(),
+        )?;
+        memcpy(targets.as_ptr(), memory.cast(), targets.len());
+        globals::get_device().unmap_memory(targets_memory);
+
+        let mut descriptors = DescriptorsData::default();
+        let mut direct_pipeline = PipelineData::default();
+        let mut integrate_pipeline = PipelineData::default();
+
+        descriptors.descriptor_set_layout = descriptors::create_direct_descriptor_set_layout()?;
+        descriptors.descriptor_pool =
+            descriptors::create_direct_descriptor_pool(2 * globals::MAX_FRAMES_IN_FLIGHT as u32)?;
+
+        for frame in 0..globals::MAX_FRAMES_IN_FLIGHT {
+            descriptors::create_storage_descriptor_set(
+                [
+                    (storage_buffers[(frame + 1) % 2], particles_size),
+                    (targets_buffer, targets_size),
+                    (accelerations_buffer, accelerations_size),
+                ],
+                &mut descriptors,
+            )?;
+        }
+
+        for frame in 0..globals::MAX_FRAMES_IN_FLIGHT {
+            descriptors::create_storage_descriptor_set(
+                [
+                    (storage_buffers[(frame + 1) % 2], particles_size),
+                    (accelerations_buffer, accelerations_size),
+                    (storage_buffers[frame], particles_size),
+                ],
+                &mut descriptors,
+            )?;
+        }
+
+        pipeline::create_direct_compute_pipeline(&descriptors, &mut direct_pipeline)?;
+        pipeline::create_integrate_compute_pipeline(&descriptors, &mut integrate_pipeline)?;
+
+        Ok(Self {
+            physics: config.physics,
+            integrator: config.integrator,
+            particle_count: vertices.len(),
+            delta_t: 0.0,
+            targets_buffer,
+            targets_memory,
+            accelerations_buffer,
+            accelerations_memory,
+            direct_pipeline,
+            integrate_pipeline,
+            descriptors,
+        })
+    }
+
+    /// Computes the accelerations and applies `update`.
+    unsafe fn record_update(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        update: &Update,
+    ) -> Result<()> {
+        // Previous step has to finish writing the particles and reading the
+        // accelerations before they are computed again.
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::SHADER_READ
+                | vk::AccessFlags::SHADER_WRITE
+                | vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+
+        let direct_push_constants = DirectPushConstants {
+            particle_count: self.particle_count as u32,
+            gravitational_constant: self.physics.field_gravitational_constant(),
+            softening: self.physics.field_softening(),
+            kernel: self.physics.kernel as u32,
+            particle_mass: self.physics.particle_mass,
+            adaptive_softening: self.physics.adaptive_softening as u32,
+            boundary: self.physics.boundary as u32,
+        };
+        let integrate_push_constants = IntegratePushConstants {
+            particle_count: self.particle_count as u32,
+            delta_t: self.delta_t,
+            kick: update.kick,
+            drift: update.drift,
+            symplectic: update.symplectic as u32,
+            boundary: self.physics.boundary as u32,
+        };
+        let group_count = (self.particle_count as f32 / 256.0).ceil() as u32;
+
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.direct_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.direct_pipeline.pipeline_layout,
+            0,
+            &[self.descriptors.descriptor_sets[frame]],
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.direct_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            direct_push_constants.as_bytes(),
+        );
+
+        globals::get_device().cmd_dispatch(command_buffer, group_count, 1, 1);
+
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        );
+
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.integrate_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.integrate_pipeline.pipeline_layout,
+            0,
+            &[self.descriptors.descriptor_sets[globals::MAX_FRAMES_IN_FLIGHT + frame]],
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.integrate_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            integrate_push_constants.as_bytes(),
+        );
+
+        globals::get_device().cmd_dispatch(command_buffer, group_count, 1, 1);
+
+        Ok(())
+    }
+}
+
+/// The targets buffer of `direct.comp`, the indirect dispatch evaluating
+/// `targets` followed by their count and the targets themselves.
+pub fn target_list(targets: &[u32]) -> Vec<u32> {
+    let group_count = (targets.len() as u32).div_ceil(256);
+    let mut list = vec![group_count, 1, 1, targets.len() as u32];
+    list.extend_from_slice(targets);
+    list
+}
+
+impl GravitySolver for DirectSolver {
+    unsafe fn prepare(&mut self, _frame: usize, delta_t: f32) -> Result<f32> {
+        self.delta_t = delta_t;
+        Ok(delta_t)
+    }
+
+    unsafe fn record(&self, command_buffer: vk::CommandBuffer, frame: usize) -> Result<()> {
+        for (i, update) in Update::passes(self.integrator).iter().enumerate() {
+            self.record_update(command_buffer, (frame + i) % 2, update)?;
+        }
+
+        Ok(())
+    }
+
+    unsafe fn record_kick(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        kick: f32,
+    ) -> Result<()> {
+        self.record_update(command_buffer, frame, &Update::kick(kick))
+    }
+
+    fn steps_on_device(&self) -> bool {
+        true
+    }
+
+    unsafe fn destroy(&mut self) {
+        self.direct_pipeline = PipelineData::default();
+        self.integrate_pipeline = PipelineData::default();
+        self.descriptors = DescriptorsData::default();
+
+        if globals::get_device().device_wait_idle().is_err() {
+            return;
+        }
+
+        globals::get_device().destroy_buffer(self.targets_buffer, None);
+        globals::get_device().free_memory(self.targets_memory, None);
+        globals::get_device().destroy_buffer(self.accelerations_buffer, None);
+        globals::get_device().free_memory(self.accelerations_memory, None);
+        self.targets_buffer = vk::Buffer::null();
+        self.targets_memory = vk::DeviceMemory::null();
+        self.accelerations_buffer = vk::Buffer::null();
+        self.accelerations_memory = vk::DeviceMemory::null();
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use std::mem::size_of_val;
+use std::ptr::copy_nonoverlapping as memcpy;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{Physics, RunConfig};
+use crate::cpu::{self, CpuSolver};
+use crate::data::buffers_data::BuffersData;
+use crate::data::common_data::CommonData;
+use crate::data::globals;
+use crate::data::vertex::Vertex;
+use crate::init::buffers;
+use crate::integrators::host::HostIntegrator;
+use crate::solvers::GravitySolver;
+use crate::utils::resources::memory_barrier;
+
+/// Steps the particles with one of the CPU solvers and copies the result
+/// into the storage buffer of the frame. The host keeps its own pair of
+/// particle buffers, ping-ponged like the storage buffers. Integrators and
+/// timesteps the shaders cannot run also step here, with the CPU version of
+/// the device solvers.
+#[derive(Debug)]
+pub struct HostSolver {
+    physics: Physics,
+    solver: Box<dyn CpuSolver>,
+    host_integrator: HostIntegrator,
+    particles: [Vec<Vertex>; 2],
+
+    storage_buffers: Vec<vk::Buffer>,
+    /// Host visible upload buffer of each frame.
+    buffers: BuffersData,
+}
+
+impl HostSolver {
+    pub unsafe fn create(
+        instance: &Instance,
+        common: &CommonData,
+        storage_buffers: &[vk::Buffer],
+        vertices: &[Vertex],
+        config: &RunConfig,
+    ) -> Result<Self> {
+        let mut buffers = BuffersData::default();
+        buffers::create_upload_buffers(instance, vertices, common, &mut buffers)?;
+
+        Ok(Self {
+            physics: config.physics,
+            solver: cpu::create_solver(config),
+            host_integrator: HostIntegrator::new(config),
+            particles: [vertices.to_vec(), vertices.to_vec()],
+            storage_buffers: storage_buffers.to_vec(),
+            buffers,
+        })
+    }
+
+    /// Stages the particles of `frame` in the upload buffer.
+    unsafe fn stage(&mut self, frame: usize) -> Result<()> {
+        let particles = &self.particles[frame];
+
+        let memory = globals::get_device().map_memory(
+            self.buffers.upload_buffer_memories[frame],
+            0,
+            size_of_val(particles.as_slice()) as u64,
+            vk::MemoryMapFlags::empty(),
+        )?;
+
+        memcpy(particles.as_ptr(), memory.cast(), particles.len());
+
+        globals::get_device().unmap_memory(self.buffers.upload_buffer_memories[frame]);
+        Ok(())
+    }
+}
+
+impl GravitySolver for HostSolver {
+    /// Computes the step and stages the particles in the upload buffer.
+    /// Adaptive runs take a smaller timestep than `delta_t` when the
+    /// particles need it.
+    unsafe fn prepare(&mut self, frame: usize, delta_t: f32) -> Result<f32> {
+        let delta_t = self.host_integrator.step(
+            self.solver.as_mut(),
+            &mut self.particles,
+            frame,
+            &self.physics,
+            delta_t,
+        );
+
+        self.stage(frame)?;
+        Ok(delta_t)
+    }
+
+    unsafe fn record(&self, command_buffer: vk::CommandBuffer, frame: usize) -> Result<()> {
+        // Several steps can be recorded into one command buffer.
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+        );
+
+        let regions =
+            vk::BufferCopy::builder().size(size_of_val(self.particles[0].as_slice()) as u64);
+        globals::get_device().cmd_copy_buffer(
+            command_buffer,
+            self.buffers.upload_buffers[frame],
+            self.storage_buffers[frame],
+            &[regions],
+        );
+
+        Ok(())
+    }
+
+    unsafe fn record_kick(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        kick: f32,
+    ) -> Result<()> {
+        self.host_integrator.kick(
+            self.solver.as_mut(),
+            &mut self.particles,
+            frame,
+            &self.physics,
+            kick,
+        );
+
+        self.stage(frame)?;
+        self.record(command_buffer, frame)
+    }
+
+    fn steps_on_device(&self) -> bool {
+        false
+    }
+
+    unsafe fn destroy(&mut self) {
+        self.buffers = BuffersData::default();
+    }
+}
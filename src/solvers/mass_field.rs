@@ -0,0 +1,878 @@
+use anyhow::Result;
+use std::mem::{size_of, size_of_val};
+use std::ptr::copy_nonoverlapping as memcpy;
+use std::time::{Duration, Instant};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{DomainMode, Integrator, MassFieldConfig, Multipoles, Physics, RunConfig};
+use crate::cpu::mass_field::fixed_point_scale;
+use crate::data::buffers_data::BuffersData;
+use crate::data::commands_data::CommandsData;
+use crate::data::common_data::CommonData;
+use crate::data::descriptors_data::DescriptorsData;
+use crate::data::globals;
+use crate::data::pipeline_data::PipelineData;
+use crate::data::push_constants::{
+    DomainPushConstants, GravityPushConstants, MassPushConstants, ReducePushConstants,
+    RefinePushConstants,
+};
+use crate::data::uniform_buffer_object::UniformBufferObject;
+use crate::data::vertex::Vertex;
+use crate::init::{buffers, descriptors, pipeline};
+use crate::integrators::Update;
+use crate::solvers::GravitySolver;
+use crate::utils::mass_field;
+use crate::utils::resources::{self, memory_barrier};
+
+/// Passes of `domain.comp`.
+const BOUNDS: u32 = 0;
+const HISTOGRAM: u32 = 1;
+const RESOLVE: u32 = 2;
+
+/// Passes of `mass.comp`.
+const DEPOSIT: u32 = 0;
+const RESOLVE_SUMS: u32 = 1;
+
+/// Passes of `refine.comp`.
+const SELECT: u32 = 0;
+const DEPOSIT_PATCHES: u32 = 1;
+const RESOLVE_PATCHES: u32 = 2;
+
+/// Words per pixel of the fixed point sums of `mass.comp`, two for each of
+/// the mass, its position and with quadrupoles its second moments.
+fn cell_words(multipoles: Multipoles) -> u64 {
+    match multipoles {
+        Multipoles::Monopole => 6,
+        Multipoles::Quadrupole => 12,
+    }
+}
+
+/// Sizes of the patch map, sums and cells of `refine.comp`, a few words
+/// for the unused bindings without refinement.
+fn patch_buffer_sizes(config: &MassFieldConfig, multipoles: Multipoles) -> (u64, u64, u64) {
+    if config.refine_density.is_none() {
+        return (16, 16, 16);
+    }
+
+    let tiles = (config.size / config.patch_size) as u64;
+    let side = (config.patch_size * config.refine_factor) as u64;
+    let pixels = config.max_patches as u64 * side * side;
+    let word = size_of::<u32>() as u64;
+    (
+        (1 + tiles * tiles) * word,
+        pixels * cell_words(multipoles) * word,
+        pixels * 2 * 4 * size_of::<f32>() as u64,
+    )
+}
+
+/// Size of the `Domain` buffer of `domain.comp`, eight words before the
+/// histograms.
+const DOMAIN_BUFFER_SIZE: u64 =
+    ((8 + 2 * globals::DOMAIN_HISTOGRAM_BINS) * size_of::<u32>()) as u64;
+
+/// The mass field pyramid of `mass.comp` and `gravity.comp`, covering the
+/// domain `domain.comp` finds before every step.
+#[derive(Debug)]
+pub struct MassFieldSolver {
+    physics: Physics,
+    integrator: Integrator,
+    domain: DomainMode,
+    multipoles: Multipoles,
+    /// Side lengths of the levels, finest first.
+    level_sizes: Vec<u32>,
+    mass_field: MassFieldConfig,
+    /// Fixed point mass of a refined tile, if refinement is on.
+    refine_threshold: Option<u64>,
+    particle_count: usize,
+    storage_buffers: Vec<vk::Buffer>,
+
+    /// Uniform buffers, mass and moment images of each frame.
+    buffers: BuffersData,
+    /// Domain of each frame.
+    domain_buffers: Vec<vk::Buffer>,
+    domain_memories: Vec<vk::DeviceMemory>,
+    /// Fixed point sums of the deposit of each frame, zero between steps.
+    deposit_buffers: Vec<vk::Buffer>,
+    deposit_memories: Vec<vk::DeviceMemory>,
+    /// Patch map, fixed point sums and cells of the refinement patches of
+    /// each frame, in that order.
+    patch_buffers: Vec<vk::Buffer>,
+    patch_memories: Vec<vk::DeviceMemory>,
+    domain_pipeline: PipelineData,
+    mass_pipeline: PipelineData,
+    reduce_pipeline: PipelineData,
+    refine_pipeline: PipelineData,
+    gravity_pipeline: PipelineData,
+    domain_descriptors: DescriptorsData,
+    mass_descriptors: DescriptorsData,
+    reduce_descriptors: DescriptorsData,
+    refine_descriptors: DescriptorsData,
+    gravity_descriptors: DescriptorsData,
+}
+
+impl MassFieldSolver {
+    pub unsafe fn create(
+        instance: &Instance,
+        common: &CommonData,
+        commands: &CommandsData,
+        storage_buffers: &[vk::Buffer],
+        vertices: &[Vertex],
+        config: &RunConfig,
+    ) -> Result<Self> {
+        let mut buffers = BuffersData::default();
+        let mut domain_pipeline = PipelineData::default();
+        let mut mass_pipeline = PipelineData::default();
+        let mut reduce_pipeline = PipelineData::default();
+        let mut refine_pipeline = PipelineData::default();
+        let mut gravity_pipeline = PipelineData::default();
+        let mut domain_descriptors = DescriptorsData::default();
+        let mut gravity_descriptors = DescriptorsData::default();
+        let mut mass_descriptors = DescriptorsData::default();
+        let mut reduce_descriptors = DescriptorsData::default();
+        let mut refine_descriptors = DescriptorsData::default();
+
+        let level_sizes = mass_field::level_sizes(&config.mass_field);
+        let levels = level_sizes.len() as u32;
+        buffers.offscreen_images =
+            buffers::create_offscreen_images(instance, common, commands, &level_sizes)?;
+        if config.multipoles == Multipoles::Quadrupole {
+            buffers.moment_images =
+                buffers::create_offscreen_images(instance, common, commands, &level_sizes)?;
+        }
+        buffers::create_uniform_buffers(instance, common, &mut buffers)?;
+
+        let mut domain_buffers = vec![];
+        let mut domain_memories = vec![];
+        for _ in 0..globals::MAX_FRAMES_IN_FLIGHT {
+            let (buffer, memory) = resources::create_buffer(
+                instance,
+                common,
+                DOMAIN_BUFFER_SIZE,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            domain_buffers.push(buffer);
+            domain_memories.push(memory);
+        }
+        let domains = domain_buffers
+            .iter()
+            .map(|buffer| (*buffer, DOMAIN_BUFFER_SIZE))
+            .collect::<Vec<_>>();
+
+        // The resolve pass clears the sums after reading them, so they only
+        // start at zero once.
+        let deposit_size = mass_field::cell_count(&config.mass_field) as u64
+            * cell_words(config.multipoles)
+            * size_of::<u32>() as u64;
+        let mut deposit_buffers = vec![];
+        let mut deposit_memories = vec![];
+        let command_buffer = resources::begin_single_time_commands(commands)?;
+        for _ in 0..globals::MAX_FRAMES_IN_FLIGHT {
+            let (buffer, memory) = resources::create_buffer(
+                instance,
+                common,
+                deposit_size,
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            )?;
+            globals::get_device().cmd_fill_buffer(command_buffer, buffer, 0, deposit_size, 0);
+            deposit_buffers.push(buffer);
+            deposit_memories.push(memory);
+        }
+
+        // The patch sums are cleared like the deposit ones. The map starts
+        // without patches.
+        let (map_size, sums_size, cells_size) =
+            patch_buffer_sizes(&config.mass_field, config.multipoles);
+        let mut patch_buffers = vec![];
+        let mut patch_memories = vec![];
+        for _ in 0..globals::MAX_FRAMES_IN_FLIGHT {
+            for size in [map_size, sums_size, cells_size] {
+                let (buffer, memory) = resources::create_buffer(
+                    instance,
+                    common,
+                    size,
+                    vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                    vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                )?;
+                globals::get_device().cmd_fill_buffer(command_buffer, buffer, 0, size, 0);
+                patch_buffers.push(buffer);
+                patch_memories.push(memory);
+            }
+        }
+        resources::end_single_time_commands(common, commands, command_buffer)?;
+        let deposits = deposit_buffers
+            .iter()
+            .map(|buffer| (*buffer, deposit_size))
+            .collect::<Vec<_>>();
+        let patch_maps = patch_buffers
+            .iter()
+            .step_by(3)
+            .map(|buffer| (*buffer, map_size))
+            .collect::<Vec<_>>();
+        let patch_cells = patch_buffers
+            .iter()
+            .skip(2)
+            .step_by(3)
+            .map(|buffer| (*buffer, cells_size))
+            .collect::<Vec<_>>();
+
+        domain_descriptors.descriptor_set_layout =
+            descriptors::create_domain_descriptor_set_layout()?;
+        domain_descriptors.descriptor_pool =
+            descriptors::create_domain_descriptor_pool(globals::MAX_FRAMES_IN_FLIGHT as u32)?;
+        for (frame, domain) in domains.iter().enumerate() {
+            descriptors::create_storage_descriptor_set(
+                [
+                    (
+                        storage_buffers[(frame + 1) % 2],
+                        size_of_val(vertices) as u64,
+                    ),
+                    *domain,
+                ],
+                &mut domain_descriptors,
+            )?;
+        }
+        pipeline::create_domain_compute_pipeline(&domain_descriptors, &mut domain_pipeline)?;
+
+        reduce_descriptors.descriptor_set_layout =
+            descriptors::create_reduce_descriptor_set_layout()?;
+        reduce_descriptors.descriptor_pool =
+            descriptors::create_reduce_descriptor_pool(globals::MAX_FRAMES_IN_FLIGHT as u32)?;
+        for deposit in &deposits {
+            descriptors::create_storage_descriptor_set([*deposit], &mut reduce_descriptors)?;
+        }
+        pipeline::create_reduce_compute_pipeline(&reduce_descriptors, &mut reduce_pipeline)?;
+
+        refine_descriptors.descriptor_set_layout =
+            descriptors::create_refine_descriptor_set_layout()?;
+        refine_descriptors.descriptor_pool =
+            descriptors::create_refine_descriptor_pool(globals::MAX_FRAMES_IN_FLIGHT as u32)?;
+        for frame in 0..globals::MAX_FRAMES_IN_FLIGHT {
+            descriptors::create_storage_descriptor_set(
+                [
+                    (
+                        storage_buffers[(frame + 1) % 2],
+                        size_of_val(vertices) as u64,
+                    ),
+                    domains[frame],
+                    deposits[frame],
+                    patch_maps[frame],
+                    (patch_buffers[3 * frame + 1], sums_size),
+                    patch_cells[frame],
+                ],
+                &mut refine_descriptors,
+            )?;
+        }
+        pipeline::create_refine_compute_pipeline(&refine_descriptors, &mut refine_pipeline)?;
+
+        // Descriptor layouts
+        gravity_descriptors.descriptor_set_layout =
+            descriptors::create_gravity_descriptor_set_layout(levels)?;
+        mass_descriptors.descriptor_set_layout =
+            descriptors::create_mass_descriptor_set_layout(levels)?;
+
+        // Pipelines
+        pipeline::create_mass_compute_pipeline(&mass_descriptors, &mut mass_pipeline, levels)?;
+        let refine_threshold = mass_field::refine_threshold(&config.mass_field);
+        pipeline::create_gravity_compute_pipeline(
+            &gravity_descriptors,
+            &mut gravity_pipeline,
+            levels,
+            config.mass_field.reach,
+            refine_threshold.map_or(0, |_| config.mass_field.patch_size),
+            config.mass_field.refine_factor,
+        )?;
+
+        gravity_descriptors.descriptor_pool = descriptors::create_gravity_descriptor_pool(levels)?;
+        mass_descriptors.descriptor_pool = descriptors::create_mass_descriptor_pool(levels)?;
+
+        descriptors::create_gravity_descriptor_sets(
+            storage_buffers,
+            &domains,
+            &patch_maps,
+            &patch_cells,
+            &buffers,
+            vertices,
+            &mut gravity_descriptors,
+        )?;
+
+        descriptors::create_mass_descriptor_sets(
+            storage_buffers,
+            &domains,
+            &deposits,
+            &buffers,
+            vertices,
+            &mut mass_descriptors,
+        )?;
+
+        Ok(Self {
+            physics: config.physics,
+            integrator: config.integrator,
+            domain: config.mass_field_domain(),
+            multipoles: config.multipoles,
+            level_sizes,
+            mass_field: config.mass_field,
+            refine_threshold,
+            particle_count: vertices.len(),
+            storage_buffers: storage_buffers.to_vec(),
+            buffers,
+            domain_buffers,
+            domain_memories,
+            deposit_buffers,
+            deposit_memories,
+            patch_buffers,
+            patch_memories,
+            domain_pipeline,
+            mass_pipeline,
+            reduce_pipeline,
+            refine_pipeline,
+            gravity_pipeline,
+            domain_descriptors,
+            mass_descriptors,
+            reduce_descriptors,
+            refine_descriptors,
+            gravity_descriptors,
+        })
+    }
+
+    /// Dispatches a pass of `domain.comp`, the resolve pass with a single
+    /// invocation and the others over all particles.
+    unsafe fn record_domain(&self, command_buffer: vk::CommandBuffer, frame: usize, mode: u32) {
+        let push_constants = DomainPushConstants {
+            mode,
+            particle_count: self.particle_count as u32,
+            domain_mode: self.domain as u32,
+            percentile: globals::DOMAIN_PERCENTILE,
+            margin: globals::DOMAIN_MARGIN,
+            min_size: globals::MIN_DOMAIN_SIZE,
+        };
+
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.domain_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.domain_pipeline.pipeline_layout,
+            0,
+            &[self.domain_descriptors.descriptor_sets[frame]],
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.domain_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            push_constants.as_bytes(),
+        );
+
+        let group_count = match mode {
+            RESOLVE => 1,
+            _ => (self.particle_count as u32).div_ceil(256),
+        };
+        globals::get_device().cmd_dispatch(command_buffer, group_count, 1, 1);
+
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+    }
+
+    /// Dispatches a pass of `mass.comp`, the deposit over all particles into
+    /// the first `deposit_levels` levels and the resolve pass over all
+    /// pixels.
+    unsafe fn record_mass(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        mode: u32,
+        deposit_levels: u32,
+    ) {
+        let (mass_scale, mass_quantum) = fixed_point_scale(&self.physics);
+        let push_constants = MassPushConstants {
+            mode,
+            particle_count: self.particle_count as u32,
+            mass_scale,
+            mass_quantum,
+            quadrupole: (self.multipoles == Multipoles::Quadrupole) as u32,
+            deposit_levels,
+        };
+
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.mass_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.mass_pipeline.pipeline_layout,
+            0,
+            &[self.mass_descriptors.descriptor_sets[frame]],
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.mass_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            push_constants.as_bytes(),
+        );
+
+        // The resolve pass loops over the pixels beyond the largest dispatch.
+        let group_count = match mode {
+            DEPOSIT => (self.particle_count as u32).div_ceil(256),
+            _ => self
+                .cell_count()
+                .div_ceil(256)
+                .min(globals::MAX_WORK_GROUPS),
+        };
+        globals::get_device().cmd_dispatch(command_buffer, group_count, 1, 1);
+
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+    }
+
+    /// Builds the levels of the fixed point sums from `first` on out of the
+    /// level below each with `reduce.comp`.
+    unsafe fn record_reduce(&self, command_buffer: vk::CommandBuffer, frame: usize, first: usize) {
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.reduce_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.reduce_pipeline.pipeline_layout,
+            0,
+            &[self.reduce_descriptors.descriptor_sets[frame]],
+            &[],
+        );
+
+        let sizes = &self.level_sizes;
+        let mut child_offset = sizes[..first.max(1) - 1]
+            .iter()
+            .map(|size| size * size)
+            .sum::<u32>();
+
+        for level in first.max(1)..sizes.len() {
+            let child_size = sizes[level - 1];
+            let push_constants = ReducePushConstants {
+                child_offset,
+                child_size,
+                parent_offset: child_offset + child_size * child_size,
+                parent_size: sizes[level],
+                quadrupole: (self.multipoles == Multipoles::Quadrupole) as u32,
+            };
+            child_offset = push_constants.parent_offset;
+
+            globals::get_device().cmd_push_constants(
+                command_buffer,
+                self.reduce_pipeline.pipeline_layout,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                push_constants.as_bytes(),
+            );
+
+            let cells = sizes[level] * sizes[level];
+            globals::get_device().cmd_dispatch(command_buffer, cells.div_ceil(256), 1, 1);
+
+            memory_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_WRITE,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            );
+        }
+    }
+
+    /// Dispatches a pass of `refine.comp`, the selection with a single
+    /// invocation, the deposit over all particles and the resolve pass over
+    /// the pixels of all patches.
+    unsafe fn record_refine(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        mode: u32,
+        threshold: u64,
+    ) {
+        let config = &self.mass_field;
+        let tile_level = mass_field::tile_level(config).unwrap_or(1);
+        let (mass_scale, mass_quantum) = fixed_point_scale(&self.physics);
+        let push_constants = RefinePushConstants {
+            mode,
+            particle_count: self.particle_count as u32,
+            mass_scale,
+            mass_quantum,
+            quadrupole: (self.multipoles == Multipoles::Quadrupole) as u32,
+            field_size: config.size,
+            tile_offset: self.level_sizes[..tile_level]
+                .iter()
+                .map(|size| size * size)
+                .sum(),
+            tiles: self.level_sizes[tile_level],
+            tile_size: config.patch_size,
+            refine_factor: config.refine_factor,
+            max_patches: config.max_patches,
+            threshold_low: threshold as u32,
+            threshold_high: (threshold >> 32) as u32,
+        };
+
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.refine_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.refine_pipeline.pipeline_layout,
+            0,
+            &[self.refine_descriptors.descriptor_sets[frame]],
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.refine_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            push_constants.as_bytes(),
+        );
+
+        let side = config.patch_size * config.refine_factor;
+        let group_count = match mode {
+            SELECT => 1,
+            DEPOSIT_PATCHES => (self.particle_count as u32).div_ceil(256),
+            _ => (config.max_patches * side * side)
+                .div_ceil(256)
+                .min(globals::MAX_WORK_GROUPS),
+        };
+        globals::get_device().cmd_dispatch(command_buffer, group_count, 1, 1);
+
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+    }
+
+    /// Resolves the fixed point sums of the levels and of the patches into
+    /// the images and patch cells and clears them.
+    unsafe fn record_resolve(&self, command_buffer: vk::CommandBuffer, frame: usize) {
+        self.record_mass(command_buffer, frame, RESOLVE_SUMS, 1);
+        if let Some(threshold) = self.refine_threshold {
+            self.record_refine(command_buffer, frame, RESOLVE_PATCHES, threshold);
+        }
+    }
+
+    /// Pixels of all levels together.
+    fn cell_count(&self) -> u32 {
+        self.level_sizes.iter().map(|size| size * size).sum()
+    }
+
+    /// Finds the domain and deposits the particles into the fixed point sums
+    /// of the first `deposit_levels` levels, building the others from them.
+    unsafe fn record_deposit(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        deposit_levels: u32,
+    ) {
+        // Previous step has to finish writing particles and reading the mass
+        // images before they are deposited again.
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+            vk::PipelineStageFlags::TRANSFER | vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::TRANSFER_WRITE
+                | vk::AccessFlags::SHADER_READ
+                | vk::AccessFlags::SHADER_WRITE,
+        );
+
+        // The lower bounds start at the largest encoded float, the rest of
+        // the domain at zero.
+        let domain_buffer = self.domain_buffers[frame];
+        globals::get_device().cmd_fill_buffer(
+            command_buffer,
+            domain_buffer,
+            0,
+            DOMAIN_BUFFER_SIZE,
+            0,
+        );
+        globals::get_device().cmd_fill_buffer(
+            command_buffer,
+            domain_buffer,
+            (4 * size_of::<u32>()) as u64,
+            (2 * size_of::<u32>()) as u64,
+            u32::MAX,
+        );
+
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+
+        if self.domain != DomainMode::Fixed {
+            self.record_domain(command_buffer, frame, BOUNDS);
+        }
+        if self.domain == DomainMode::Percentile {
+            self.record_domain(command_buffer, frame, HISTOGRAM);
+        }
+        self.record_domain(command_buffer, frame, RESOLVE);
+
+        self.record_mass(command_buffer, frame, DEPOSIT, deposit_levels);
+        self.record_reduce(command_buffer, frame, deposit_levels as usize);
+
+        // The tiles are picked from the reduced sums before they are
+        // resolved and cleared.
+        if let Some(threshold) = self.refine_threshold {
+            self.record_refine(command_buffer, frame, SELECT, threshold);
+            self.record_refine(command_buffer, frame, DEPOSIT_PATCHES, threshold);
+        }
+    }
+
+    /// Finds the domain, deposits the mass into the mass images and applies
+    /// `update`.
+    unsafe fn record_update(
+        &self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        update: &Update,
+    ) -> Result<()> {
+        self.record_deposit(command_buffer, frame, 1);
+        self.record_resolve(command_buffer, frame);
+
+        let gravity_push_constants = GravityPushConstants {
+            kick: update.kick,
+            drift: update.drift,
+            symplectic: update.symplectic as u32,
+            quadrupole: (self.multipoles == Multipoles::Quadrupole) as u32,
+            particle_count: self.particle_count as u32,
+        };
+        let group_count = (self.particle_count as f32 / 256.0).ceil() as u32;
+
+        globals::get_device().cmd_bind_pipeline(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.gravity_pipeline.pipeline,
+        );
+
+        globals::get_device().cmd_bind_descriptor_sets(
+            command_buffer,
+            vk::PipelineBindPoint::COMPUTE,
+            self.gravity_pipeline.pipeline_layout,
+            0,
+            &[self.gravity_descriptors.descriptor_sets[frame]],
+            &[],
+        );
+
+        globals::get_device().cmd_push_constants(
+            command_buffer,
+            self.gravity_pipeline.pipeline_layout,
+            vk::ShaderStageFlags::COMPUTE,
+            0,
+            gravity_push_constants.as_bytes(),
+        );
+
+        globals::get_device().cmd_dispatch(command_buffer, group_count, 1, 1);
+
+        Ok(())
+    }
+
+    /// Deposits the particles the steps of `frame` read and returns the
+    /// fixed point mass summed over every level, before the sums are
+    /// resolved and cleared again.
+    pub unsafe fn accumulated_mass(
+        &self,
+        instance: &Instance,
+        common: &CommonData,
+        commands: &CommandsData,
+        frame: usize,
+    ) -> Result<Vec<u64>> {
+        let command_buffer = resources::begin_single_time_commands(commands)?;
+        self.record_deposit(command_buffer, frame, 1);
+        resources::end_single_time_commands(common, commands, command_buffer)?;
+
+        let words = buffers::read_shader_storage_buffer::<u32>(
+            instance,
+            common,
+            commands,
+            self.deposit_buffers[frame],
+            (self.cell_count() as u64 * cell_words(self.multipoles)) as usize,
+        )?;
+
+        let command_buffer = resources::begin_single_time_commands(commands)?;
+        self.record_resolve(command_buffer, frame);
+        resources::end_single_time_commands(common, commands, command_buffer)?;
+
+        let mut cells = words.chunks(cell_words(self.multipoles) as usize);
+        let totals = self
+            .level_sizes
+            .iter()
+            .map(|size| {
+                cells
+                    .by_ref()
+                    .take((size * size) as usize)
+                    .map(|cell| cell[0] as u64 | (cell[1] as u64) << 32)
+                    .fold(0_u64, u64::wrapping_add)
+            })
+            .collect();
+
+        Ok(totals)
+    }
+
+    /// Time of `repeat` deposits and resolves of the particles of `frame`,
+    /// depositing into the first `deposit_levels` levels and reducing the
+    /// others.
+    pub unsafe fn deposit_time(
+        &self,
+        common: &CommonData,
+        commands: &CommandsData,
+        frame: usize,
+        deposit_levels: u32,
+        repeat: u32,
+    ) -> Result<Duration> {
+        let command_buffer = resources::begin_single_time_commands(commands)?;
+        for _ in 0..repeat {
+            self.record_deposit(command_buffer, frame, deposit_levels);
+            self.record_resolve(command_buffer, frame);
+        }
+
+        let start = Instant::now();
+        resources::end_single_time_commands(common, commands, command_buffer)?;
+        Ok(start.elapsed())
+    }
+}
+
+impl GravitySolver for MassFieldSolver {
+    unsafe fn prepare(&mut self, frame: usize, delta_t: f32) -> Result<f32> {
+        let ubo = UniformBufferObject {
+            delta_t,
+            gravitational_constant: self.physics.gravitational_constant,
+            softening: self.physics.softening,
+            particle_mass: self.physics.particle_mass,
+            kernel: self.physics.kernel as u32,
+            adaptive_softening: self.physics.adaptive_softening as u32,
+            boundary: self.physics.boundary as u32,
+        };
+
+        let memory = globals::get_device().map_memory(
+            self.buffers.uniform_buffers_memory[frame],
+            0,
+            size_of::<UniformBufferObject>() as u64,
+            vk::MemoryMapFlags::empty(),
+        )?;
+
+        memcpy(&ubo, memory.cast(), 1);
+
+        globals::get_device().unmap_memory(self.buffers.uniform_buffers_memory[frame]);
+        Ok(delta_t)
+    }
+
+    unsafe fn record(&self, command_buffer: vk::CommandBuffer, frame: usize) -> Result<()> {
+        for (i, update) in Update::passes(self.integrator).iter().enumerate() {
+            self.record_update(command_buffer, (frame + i) % 2, update)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies the particles into the storage buffer of `frame` first, so the
+    /// ones `gravity.comp` skips are not left behind.
+    unsafe fn record_kick(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        kick: f32,
+    ) -> Result<()> {
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::VERTEX_INPUT,
+            vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::VERTEX_ATTRIBUTE_READ,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_READ | vk::AccessFlags::TRANSFER_WRITE,
+        );
+
+        let regions =
+            vk::BufferCopy::builder().size((self.particle_count * size_of::<Vertex>()) as u64);
+        globals::get_device().cmd_copy_buffer(
+            command_buffer,
+            self.storage_buffers[(frame + 1) % 2],
+            self.storage_buffers[frame],
+            &[regions],
+        );
+
+        memory_barrier(
+            command_buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+        );
+
+        self.record_update(command_buffer, frame, &Update::kick(kick))
+    }
+
+    fn steps_on_device(&self) -> bool {
+        true
+    }
+
+    unsafe fn destroy(&mut self) {
+        self.domain_pipeline = PipelineData::default();
+        self.mass_pipeline = PipelineData::default();
+        self.reduce_pipeline = PipelineData::default();
+        self.refine_pipeline = PipelineData::default();
+        self.gravity_pipeline = PipelineData::default();
+        self.buffers = BuffersData::default();
+        self.domain_descriptors = DescriptorsData::default();
+        self.gravity_descriptors = DescriptorsData::default();
+        self.mass_descriptors = DescriptorsData::default();
+        self.reduce_descriptors = DescriptorsData::default();
+        self.refine_descriptors = DescriptorsData::default();
+
+        if globals::get_device().device_wait_idle().is_err() {
+            return;
+        }
+
+        let buffers = self
+            .domain_buffers
+            .drain(..)
+            .chain(self.deposit_buffers.drain(..))
+            .chain(self.patch_buffers.drain(..));
+        let memories = self
+            .domain_memories
+            .drain(..)
+            .chain(self.deposit_memories.drain(..))
+            .chain(self.patch_memories.drain(..));
+        for (buffer, memory) in buffers.zip(memories) {
+            globals::get_device().destroy_buffer(buffer, None);
+            globals::get_device().free_memory(memory, None);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use std::fmt::Debug;
+
+use anyhow::Result;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{Integrator, RunConfig, SolverKind};
+use crate::data::commands_data::CommandsData;
+use crate::data::common_data::CommonData;
+use crate::data::vertex::Vertex;
+
+use self::block::BlockSolver;
+use self::direct::DirectSolver;
+use self::host::HostSolver;
+use self::mass_field::MassFieldSolver;
+
+pub mod block;
+pub mod direct;
+pub mod host;
+pub mod mass_field;
+
+/// Computes the simulation steps for `App` and `HeadlessApp`. A solver
+/// owns the resources it needs and records one step into the command buffer
+/// submitted for a frame. The step of frame `f` reads the particles from
+/// storage buffer `(f + 1) % 2` and writes them into storage buffer `f`.
+pub trait GravitySolver: Debug {
+    /// Does the host side of the next step of `frame`, before its commands
+    /// are submitted, and returns the timestep it takes. Only adaptive runs
+    /// on the host take less than `delta_t`.
+    unsafe fn prepare(&mut self, frame: usize, delta_t: f32) -> Result<f32>;
+
+    /// Records the step of `frame` into a command buffer that has begun.
+    unsafe fn record(&self, command_buffer: vk::CommandBuffer, frame: usize) -> Result<()>;
+
+    /// Records a pass only changing the velocities by `kick` of the latest
+    /// timestep, reading and writing the same buffers as the first pass of
+    /// the step of `frame`. Runs with a staggered integrator use it to move
+    /// the velocities to and from the time of the positions.
+    unsafe fn record_kick(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        frame: usize,
+        kick: f32,
+    ) -> Result<()>;
+
+    /// Whether the steps only run on the device, so `prepare` only depends
+    /// on the frame and the timestep and recorded steps can be submitted
+    /// again without calling it.
+    fn steps_on_device(&self) -> bool;
+
+    /// Destroys the device resources of the solver.
+    unsafe fn destroy(&mut self);
+}
+
+/// Creates the solver selected by `config` for the particles in
+/// `storage_buffers`. Runs the shaders cannot step use `HostSolver`.
+pub unsafe fn create_solver(
+    instance: &Instance,
+    common: &CommonData,
+    commands: &CommandsData,
+    storage_buffers: &[vk::Buffer],
+    vertices: &[Vertex],
+    config: &RunConfig,
+) -> Result<Box<dyn GravitySolver>> {
+    let on_device = config.steps_on_device();
+
+    Ok(match config.solver {
+        SolverKind::MassField if on_device => Box::new(MassFieldSolver::create(
+            instance,
+            common,
+            commands,
+            storage_buffers,
+            vertices,
+            config,
+        )?),
+        SolverKind::Direct if on_device && config.integrator == Integrator::Block => {
+            Box::new(BlockSolver::create(
+                instance,
+                common,
+                commands,
+                storage_buffers,
+                vertices,
+                config,
+            )?)
+        }
+        SolverKind::Direct if on_device => Box::new(DirectSolver::create(
+            instance,
+            common,
+            storage_buffers,
+            vertices,
+            config,
+        )?),
+        _ => Box::new(HostSolver::create(
+            instance,
+            common,
+            storage_buffers,
+            vertices,
+            config,
+        )?),
+    })
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use cgmath::InnerSpace;
+
+use crate::snapshot::Snapshot;
+use crate::tools::percentiles;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Prints how far the particles of two snapshots of the same run are apart.
+pub fn compare_snapshots(a: &Path, b: &Path) -> Result<()> {
+    let a = Snapshot::load(a)?;
+    let b = Snapshot::load(b)?;
+
+    if a.vertices.len() != b.vertices.len() {
+        return Err(anyhow!(
+            "Particle counts differ: {} and {}",
+            a.vertices.len(),
+            b.vertices.len()
+        ));
+    }
+
+    if a.header.step != b.header.step {
+        println!(
+            "warning: comparing step {} with step {}",
+            a.header.step, b.header.step
+        );
+    }
+
+    let relabeled = a
+        .vertices
+        .iter()
+        .zip(&b.vertices)
+        .filter(|(a, b)| a.mass != b.mass || a.species != b.species)
+        .count();
+    if relabeled > 0 {
+        println!("warning: {} particles differ in mass or species", relabeled);
+    }
+
+    let mut positions = vec![];
+    let mut velocities = vec![];
+    let mut mismatched = 0;
+
+    for (a, b) in a.vertices.iter().zip(&b.vertices) {
+        match (distance(a.pos, b.pos), distance(a.velocity, b.velocity)) {
+            (Some(p), Some(v)) => {
+                positions.push(p);
+                velocities.push(v);
+            }
+            _ => mismatched += 1,
+        }
+    }
+
+    let identical = positions
+        .iter()
+        .zip(&velocities)
+        .filter(|(p, v)| **p == 0.0 && **v == 0.0)
+        .count();
+
+    println!(
+        "particles: {}, identical: {}, finite in only one: {}",
+        a.vertices.len(),
+        identical,
+        mismatched
+    );
+    println!("                  p50          p90          p99          max");
+
+    for (name, values) in [("position", &mut positions), ("velocity", &mut velocities)] {
+        let [p50, p90, p99, max] = percentiles(values);
+        println!(
+            "{:<10} {:>12.4e} {:>12.4e} {:>12.4e} {:>12.4e}",
+            name, p50, p90, p99, max
+        );
+    }
+
+    Ok(())
+}
+
+/// Distance between two vectors, where two non-finite vectors (particles
+/// the passes skip) count as equal. `None` if only one of them is finite.
+fn distance(a: Vec2, b: Vec2) -> Option<f32> {
+    let a_finite = a.x.is_finite() && a.y.is_finite();
+    let b_finite = b.x.is_finite() && b.y.is_finite();
+
+    match (a_finite, b_finite) {
+        (true, true) => Some((a - b).magnitude()),
+        (false, false) => Some(0.0),
+        _ => None,
+    }
+}
@@ -0,0 +1,160 @@
+//! Compares depositing every particle into all levels of the mass field
+//! with depositing into the finest level only and building the coarser
+//! levels by reduction, in time and in the resulting cells.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use cgmath::InnerSpace;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{DomainMode, MassFieldConfig, Multipoles, RunConfig, SolverKind};
+use crate::cpu::mass_field::MassField;
+use crate::data::globals;
+use crate::headless_app::HeadlessApp;
+use crate::snapshot::Snapshot;
+
+/// Prints the time of `repeat` deposits of both kinds on the CPU, and with
+/// `gpu` on the device, and how far the cells of their levels differ.
+pub fn deposit_timing(
+    path: &Path,
+    domain: DomainMode,
+    multipoles: Multipoles,
+    mass_field: MassFieldConfig,
+    repeat: u32,
+    gpu: bool,
+) -> Result<()> {
+    mass_field.validate()?;
+    let snapshot = Snapshot::load(path)?;
+    let physics = snapshot.header.physics;
+    let config = RunConfig {
+        physics,
+        domain,
+        multipoles,
+        mass_field,
+        ..Default::default()
+    };
+    let repeat = repeat.max(1);
+
+    let mut deposited = MassField::new(config.mass_field_domain(), multipoles, &mass_field);
+    let mut reduced = MassField::new(config.mass_field_domain(), multipoles, &mass_field);
+    let levels = deposited.levels.len();
+
+    let start = Instant::now();
+    for _ in 0..repeat {
+        deposited.accumulate_levels(&snapshot.vertices, &physics, levels);
+        deposited.resolve(&physics);
+    }
+    let deposit_time = start.elapsed();
+
+    let start = Instant::now();
+    for _ in 0..repeat {
+        reduced.accumulate(&snapshot.vertices, &physics);
+        reduced.resolve(&physics);
+    }
+    let reduce_time = start.elapsed();
+
+    println!(
+        "{} particles, {} deposits, {:?} multipoles",
+        snapshot.vertices.len(),
+        repeat,
+        multipoles
+    );
+    print_times("cpu", deposit_time, reduce_time, repeat);
+
+    if gpu {
+        let (deposit_time, reduce_time) = time_on_device(&snapshot, &config, levels, repeat)?;
+        print_times("gpu", deposit_time, reduce_time, repeat);
+    }
+
+    // The whole mass has to agree exactly, the centers and moments up to
+    // the rounding of the reduction. Depositing into every level rounds the
+    // pixel of a particle on every level on its own though, which puts a
+    // few particles close to pixel borders into pixels that do not cover
+    // their finest pixel. Those pixels are left out of the comparison.
+    deposited.accumulate_levels(&snapshot.vertices, &physics, levels);
+    reduced.accumulate(&snapshot.vertices, &physics);
+    let masses = deposited
+        .levels
+        .iter()
+        .zip(&reduced.levels)
+        .map(|(a, b)| a.accumulated_mass() == b.accumulated_mass())
+        .collect::<Vec<_>>();
+    deposited.resolve(&physics);
+    reduced.resolve(&physics);
+
+    let mut failures = 0;
+    for (i, (a, b)) in deposited.levels.iter().zip(&reduced.levels).enumerate() {
+        let mut center = 0.0f32;
+        let mut moments = 0.0f32;
+        let mut moved = 0;
+        for (a, b) in a.cells.iter().zip(&b.cells) {
+            if a.mass != b.mass {
+                moved += 1;
+                continue;
+            }
+
+            center = center.max((a.center - b.center).magnitude());
+            moments = moments.max((a.moments - b.moments).magnitude());
+        }
+
+        let mass_equal = masses[i];
+        failures += !mass_equal as usize;
+        println!(
+            "level {} ({}x{}): mass {}, {} pixels of other mass, center {:.3e} pixels, moments {:.3e} pixels squared",
+            i,
+            a.size,
+            a.size,
+            if mass_equal { "equal" } else { "differs" },
+            moved,
+            center,
+            moments
+        );
+    }
+
+    if failures > 0 {
+        return Err(anyhow!(
+            "The reduced mass differs from the deposited one on {} levels",
+            failures
+        ));
+    }
+
+    Ok(())
+}
+
+fn print_times(device: &str, deposit: Duration, reduce: Duration, repeat: u32) {
+    let deposit = deposit.as_secs_f64() * 1e3 / repeat as f64;
+    let reduce = reduce.as_secs_f64() * 1e3 / repeat as f64;
+    println!(
+        "{}: every level {:.3} ms, finest level and reduction {:.3} ms, {:.2}x",
+        device,
+        deposit,
+        reduce,
+        deposit / reduce
+    );
+}
+
+fn time_on_device(
+    snapshot: &Snapshot,
+    config: &RunConfig,
+    levels: usize,
+    repeat: u32,
+) -> Result<(Duration, Duration)> {
+    // The direct solver keeps the run from allocating a second mass field.
+    let run = RunConfig {
+        solver: SolverKind::Direct,
+        ..config.clone()
+    };
+
+    unsafe {
+        let mut app = HeadlessApp::create(snapshot.clone(), &run)?;
+        let deposit = app.deposit_time(&snapshot.vertices, config, levels as u32, repeat);
+        let reduce = app.deposit_time(&snapshot.vertices, config, 1, repeat);
+
+        globals::get_device().device_wait_idle()?;
+        app.destroy();
+
+        Ok((deposit?, reduce?))
+    }
+}
@@ -0,0 +1,130 @@
+use std::path::Path;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use cgmath::InnerSpace;
+use rand::seq::index;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{DomainMode, MassFieldConfig, Multipoles, RunConfig, SolverKind};
+use crate::cpu::{self, direct};
+use crate::data::globals;
+use crate::headless_app::HeadlessApp;
+use crate::snapshot::Snapshot;
+use crate::tools::percentiles;
+
+type Vec2 = cgmath::Vector2<f32>;
+
+/// Prints percentiles of `|a - a_direct| / |a_direct|` over a sample of
+/// particles, where `a` comes from the CPU version of `solver` and
+/// `a_direct` from direct summation.
+#[allow(clippy::too_many_arguments)]
+pub fn force_error(
+    path: &Path,
+    solver: SolverKind,
+    theta: f32,
+    grid_size: u32,
+    domain: DomainMode,
+    multipoles: Multipoles,
+    mass_field: MassFieldConfig,
+    sample: u32,
+    seed: u64,
+    gpu: bool,
+) -> Result<()> {
+    mass_field.validate()?;
+    let snapshot = Snapshot::load(path)?;
+    let physics = snapshot.header.physics;
+    let count = snapshot.vertices.len();
+
+    let mut targets = if sample as usize >= count {
+        (0..count as u32).collect::<Vec<_>>()
+    } else {
+        index::sample(&mut ChaCha8Rng::seed_from_u64(seed), count, sample as usize)
+            .into_iter()
+            .map(|i| i as u32)
+            .collect()
+    };
+    targets.sort_unstable();
+
+    let start = Instant::now();
+    let exact = if gpu {
+        direct_on_device(&snapshot, &targets)?
+    } else {
+        direct::accelerations(&snapshot.vertices, &targets, &physics)
+    };
+    println!(
+        "direct summation for {} of {} particles took {:.2?}",
+        targets.len(),
+        count,
+        start.elapsed()
+    );
+
+    let start = Instant::now();
+    let config = RunConfig {
+        physics,
+        solver,
+        theta,
+        grid_size,
+        domain,
+        multipoles,
+        mass_field,
+        ..Default::default()
+    };
+    let approximate =
+        cpu::create_solver(&config).accelerations(&snapshot.vertices, &targets, &physics);
+    println!("{:?} solver took {:.2?}", solver, start.elapsed());
+
+    let mut errors = vec![];
+    let mut skipped = 0;
+
+    for (exact, approximate) in exact.into_iter().zip(approximate) {
+        let exact_magnitude = exact.magnitude();
+        if exact_magnitude == 0.0 || !exact_magnitude.is_finite() {
+            continue;
+        }
+
+        match approximate {
+            Some(approximate) => errors.push((approximate - exact).magnitude() / exact_magnitude),
+            None => skipped += 1,
+        }
+    }
+
+    if errors.is_empty() {
+        return Err(anyhow!("No particle with a force to compare"));
+    }
+
+    let mean = errors.iter().map(|e| *e as f64).sum::<f64>() / errors.len() as f64;
+    let [p50, p90, p99, max] = percentiles(&mut errors);
+
+    println!(
+        "compared: {}, ignored by the solver: {}",
+        errors.len(),
+        skipped
+    );
+    println!("relative force error");
+    println!(
+        "  mean {:.4e}  p50 {:.4e}  p90 {:.4e}  p99 {:.4e}  max {:.4e}",
+        mean, p50, p90, p99, max
+    );
+
+    Ok(())
+}
+
+fn direct_on_device(snapshot: &Snapshot, targets: &[u32]) -> Result<Vec<Vec2>> {
+    let config = RunConfig {
+        physics: snapshot.header.physics,
+        ..Default::default()
+    };
+
+    unsafe {
+        let mut app = HeadlessApp::create(snapshot.clone(), &config)?;
+        let accelerations = app.direct_accelerations(targets);
+
+        globals::get_device().device_wait_idle()?;
+        app.destroy();
+
+        accelerations
+    }
+}
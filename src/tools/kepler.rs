@@ -0,0 +1,311 @@
+use std::f64::consts::TAU;
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use cgmath::{vec2, InnerSpace};
+use clap::ValueEnum;
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{
+    Integrator, Physics, RunConfig, SolverKind, TimestepCriterion, DEFAULT_BLOCK_LEVELS,
+};
+use crate::cpu::simulation::CpuSimulation;
+use crate::data::globals;
+use crate::data::vertex::Vertex;
+use crate::headless_app::HeadlessApp;
+use crate::snapshot::{Snapshot, SnapshotHeader};
+
+type Vec2 = cgmath::Vector2<f64>;
+
+/// Energy samples taken per orbit.
+const SAMPLES_PER_ORBIT: u64 = 1000;
+/// Softening length of the orbit.
+const KEPLER_SOFTENING: f32 = 0.02;
+
+/// Two equal particles on a bound orbit around their centre of mass, which
+/// sits at the origin.
+struct Orbit {
+    /// Gravitational parameter `G (m + m)` of the relative motion.
+    mu: f64,
+    semi_major_axis: f64,
+    eccentricity: f64,
+    /// Mass `m` of both particles.
+    particle_mass: f32,
+}
+
+impl Orbit {
+    /// The orbit of the two particles with the default physics and a
+    /// softening that fits inside periapsis.
+    fn new(eccentricity: f32, semi_major_axis: f32) -> Result<(Self, Physics)> {
+        if !(0.0..1.0).contains(&eccentricity) {
+            return Err(anyhow!("The eccentricity has to be in [0, 1)"));
+        }
+
+        let extent = semi_major_axis * (1.0 + eccentricity) * 0.5;
+        if semi_major_axis <= 0.0 || extent >= 1.0 {
+            return Err(anyhow!("The orbit does not fit into the simulation area"));
+        }
+
+        // The softening cuts the force off below its distance, so it has to
+        // stay inside periapsis. The acceleration criteria of the adaptive
+        // and block timesteps need one.
+        let physics = Physics {
+            softening: KEPLER_SOFTENING,
+            ..Default::default()
+        };
+        if semi_major_axis * (1.0 - eccentricity) <= KEPLER_SOFTENING {
+            return Err(anyhow!("The periapsis is inside the softening length"));
+        }
+
+        let orbit = Self {
+            mu: 2.0 * physics.gravitational_constant as f64 * physics.particle_mass as f64,
+            semi_major_axis: semi_major_axis as f64,
+            eccentricity: eccentricity as f64,
+            particle_mass: physics.particle_mass,
+        };
+        Ok((orbit, physics))
+    }
+
+    /// Snapshot of the particles at periapsis.
+    fn snapshot(&self, physics: Physics, delta_t: f32) -> Snapshot {
+        Snapshot {
+            header: SnapshotHeader {
+                particle_count: 2,
+                step: 0,
+                sim_time: 0.0,
+                seed: None,
+                dt: delta_t,
+                physics,
+            },
+            vertices: self.initial(),
+        }
+    }
+
+    fn period(&self) -> f64 {
+        TAU * (self.semi_major_axis.powi(3) / self.mu).sqrt()
+    }
+
+    /// Both particles at periapsis, moving counterclockwise.
+    fn initial(&self) -> Vec<Vertex> {
+        let distance = self.semi_major_axis * (1.0 - self.eccentricity);
+        let speed = (self.mu * (1.0 + self.eccentricity) / distance).sqrt();
+
+        let pos = vec2(distance as f32 * 0.5, 0.0);
+        let velocity = vec2(0.0, speed as f32 * 0.5);
+        vec![
+            Vertex::new(pos, velocity).with_species(0, self.particle_mass),
+            Vertex::new(-pos, -velocity).with_species(0, self.particle_mass),
+        ]
+    }
+
+    /// Relative position and velocity of the two particles.
+    fn relative(particles: &[Vertex]) -> (Vec2, Vec2) {
+        let d = particles[0].pos - particles[1].pos;
+        let v = particles[0].velocity - particles[1].velocity;
+        (vec2(d.x as f64, d.y as f64), vec2(v.x as f64, v.y as f64))
+    }
+
+    /// Specific orbital energy of the relative motion.
+    fn energy(&self, particles: &[Vertex]) -> f64 {
+        let (r, v) = Self::relative(particles);
+        0.5 * v.magnitude2() - self.mu / r.magnitude()
+    }
+
+    /// Relative position at `time` after periapsis, from Kepler's equation.
+    fn position(&self, time: f64) -> Vec2 {
+        let (a, e) = (self.semi_major_axis, self.eccentricity);
+        let mean_anomaly = (TAU * time / self.period()) % TAU;
+
+        let mut anomaly = mean_anomaly;
+        for _ in 0..50 {
+            anomaly -= (anomaly - e * anomaly.sin() - mean_anomaly) / (1.0 - e * anomaly.cos());
+        }
+
+        vec2(
+            a * (anomaly.cos() - e),
+            a * (1.0 - e * e).sqrt() * anomaly.sin(),
+        )
+    }
+
+    /// Distance to the analytic orbit relative to the semi-major axis.
+    fn position_error(&self, particles: &[Vertex], time: f64) -> f64 {
+        let (r, _) = Self::relative(particles);
+        (r - self.position(time)).magnitude() / self.semi_major_axis
+    }
+}
+
+/// Runs the orbit for `orbits` periods with each integrator and prints the
+/// largest relative energy error and how far the particles end up from the
+/// analytic orbit. With `gpu` the device runs the same steps with the
+/// direct solver and the difference to the CPU is printed as well.
+#[allow(clippy::too_many_arguments)]
+pub fn kepler(
+    integrator: Option<Integrator>,
+    orbits: u32,
+    eccentricity: f32,
+    semi_major_axis: f32,
+    dt: Option<f32>,
+    adaptive: Option<TimestepCriterion>,
+    eta: f32,
+    gpu: bool,
+) -> Result<()> {
+    let (orbit, physics) = Orbit::new(eccentricity, semi_major_axis)?;
+
+    let delta_t = dt.unwrap_or(globals::DEFAULT_DELTA_T);
+    let steps_per_orbit = (orbit.period() / delta_t as f64).round() as u64;
+    let steps = steps_per_orbit * orbits as u64;
+
+    println!(
+        "period {:.1}, {} steps per orbit, {} orbits",
+        orbit.period(),
+        steps_per_orbit,
+        orbits
+    );
+
+    let initial = orbit.snapshot(physics, delta_t);
+    let initial_energy = orbit.energy(&initial.vertices);
+
+    let all = integrator.is_none();
+    let integrators = match integrator {
+        Some(integrator) => vec![integrator],
+        None => Integrator::value_variants().to_vec(),
+    };
+
+    for integrator in integrators {
+        let config = RunConfig {
+            physics,
+            solver: SolverKind::Direct,
+            integrator,
+            dt: Some(delta_t),
+            adaptive,
+            eta,
+            block_levels: DEFAULT_BLOCK_LEVELS,
+            report_every: Some(steps),
+            ..Default::default()
+        };
+        // Combinations that cannot run are skipped when checking them all.
+        if let Err(error) = config.validate() {
+            if !all {
+                return Err(error);
+            }
+            println!("{:?} skipped: {}", integrator, error);
+            continue;
+        }
+
+        let start = Instant::now();
+        let (cpu, step, max_energy_error) = run_on_cpu(&orbit, &initial, &config, steps);
+        println!(
+            "{:?} on the cpu took {:.2?} and {} steps: max energy error {:.3e}, final energy error {:.3e}, position error {:.3e}",
+            integrator,
+            start.elapsed(),
+            step,
+            max_energy_error,
+            ((orbit.energy(&cpu.vertices) - initial_energy) / initial_energy).abs(),
+            orbit.position_error(&cpu.vertices, cpu.header.sim_time)
+        );
+
+        if gpu {
+            let start = Instant::now();
+            let device = run_on_device(initial.clone(), &config, step)?;
+            let difference = device
+                .vertices
+                .iter()
+                .zip(&cpu.vertices)
+                .map(|(a, b)| (a.pos - b.pos).magnitude() as f64 / orbit.semi_major_axis)
+                .fold(0.0, f64::max);
+
+            println!(
+                "{:?} on the device took {:.2?}: final energy error {:.3e}, position error {:.3e}, distance to the cpu {:.3e}",
+                integrator,
+                start.elapsed(),
+                ((orbit.energy(&device.vertices) - initial_energy) / initial_energy).abs(),
+                orbit.position_error(&device.vertices, device.header.sim_time),
+                difference
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs `steps` steps of `config` from `initial`, or with adaptive steps as
+/// many as cover the same time. Returns the final snapshot, the steps taken
+/// and the largest relative energy error of the samples.
+fn run_on_cpu(
+    orbit: &Orbit,
+    initial: &Snapshot,
+    config: &RunConfig,
+    steps: u64,
+) -> (Snapshot, u64, f64) {
+    let delta_t = initial.header.dt as f64;
+    let end_time = steps as f64 * delta_t;
+    let steps_per_orbit = (orbit.period() / delta_t).round() as u64;
+    let sample_every = (steps_per_orbit / SAMPLES_PER_ORBIT).max(1);
+    let initial_energy = orbit.energy(&initial.vertices);
+
+    let mut simulation = CpuSimulation::create(initial.clone(), config);
+    let mut max_energy_error = 0.0f64;
+
+    let mut step = 0;
+    while step < steps || (config.adaptive.is_some() && simulation.sim_time() < end_time) {
+        simulation.advance();
+        step += 1;
+
+        if step % sample_every == 0 {
+            let energy = orbit.energy(&simulation.snapshot().vertices);
+            let error = ((energy - initial_energy) / initial_energy).abs();
+            max_energy_error = max_energy_error.max(error);
+        }
+    }
+
+    (simulation.snapshot(), step, max_energy_error)
+}
+
+fn run_on_device(initial: Snapshot, config: &RunConfig, steps: u64) -> Result<Snapshot> {
+    unsafe {
+        let mut app = HeadlessApp::create(initial, config)?;
+        let snapshot = app.run(steps).and_then(|_| app.snapshot());
+
+        globals::get_device().device_wait_idle()?;
+        app.destroy();
+
+        snapshot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One orbit of the default eccentric orbit with the leapfrog, in few
+    /// enough steps to run quickly.
+    #[test]
+    fn leapfrog_returns_to_periapsis_after_one_period() {
+        let (orbit, physics) = Orbit::new(0.5, 0.6).unwrap();
+        let delta_t = 1.0;
+        let initial = orbit.snapshot(physics, delta_t);
+        let config = RunConfig {
+            physics,
+            solver: SolverKind::Direct,
+            integrator: Integrator::Leapfrog,
+            dt: Some(delta_t),
+            ..Default::default()
+        };
+        config.validate().unwrap();
+
+        let steps = (orbit.period() / delta_t as f64).round() as u64;
+        let (end, _, max_energy_error) = run_on_cpu(&orbit, &initial, &config, steps);
+
+        let position_error = orbit.position_error(&end.vertices, end.header.sim_time);
+        let (start, _) = Orbit::relative(&initial.vertices);
+        let (finish, _) = Orbit::relative(&end.vertices);
+        let periapsis_error = (finish - start).magnitude() / orbit.semi_major_axis;
+        assert!(max_energy_error < 5e-4, "energy error {}", max_energy_error);
+        assert!(position_error < 1e-2, "position error {}", position_error);
+        assert!(
+            periapsis_error < 1e-2,
+            "distance to periapsis {}",
+            periapsis_error
+        );
+    }
+}
@@ -0,0 +1,140 @@
+//! Checks that depositing the particles conserves their mass: every level
+//! of the mass field has to hold exactly the fixed point mass of the
+//! particles inside of the domain. Unsynchronized deposits into the same
+//! pixel would lose some of it. With refinement every patch has to hold
+//! exactly the mass of the tile it refines.
+
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use vulkanalia::prelude::v1_0::*;
+
+use crate::config::{DomainMode, MassFieldConfig, RunConfig, SolverKind};
+use crate::cpu::domain::{self, Domain};
+use crate::cpu::mass_field::{fixed_point_scale, MassField};
+use crate::data::globals;
+use crate::headless_app::HeadlessApp;
+use crate::snapshot::Snapshot;
+
+/// Prints the mass of every level of the CPU deposit, and with `gpu` of the
+/// device one, against the mass of the particles inside of the domain.
+pub fn mass_check(
+    path: &Path,
+    domain: DomainMode,
+    mass_field: MassFieldConfig,
+    gpu: bool,
+) -> Result<()> {
+    mass_field.validate()?;
+    let snapshot = Snapshot::load(path)?;
+    let physics = snapshot.header.physics;
+    let config = RunConfig {
+        physics,
+        domain,
+        mass_field,
+        ..Default::default()
+    };
+
+    let mut field = MassField::new(
+        config.mass_field_domain(),
+        config.multipoles,
+        &config.mass_field,
+    );
+    field.accumulate(&snapshot.vertices, &physics);
+    let cpu = field
+        .levels
+        .iter()
+        .map(|level| level.accumulated_mass())
+        .collect::<Vec<_>>();
+    let patches = field.patches.as_ref().map(|patches| {
+        let tiles = field.levels[patches.tile_level].sums();
+        let masses = patches.accumulated_masses();
+        let differing = masses
+            .iter()
+            .filter(|&&(tile, mass)| tiles[tile as usize].mass() != mass)
+            .count();
+        (masses.len(), differing)
+    });
+    field.resolve(&physics);
+
+    let (scale, quantum) = fixed_point_scale(&physics);
+    let deposited = snapshot
+        .vertices
+        .iter()
+        .filter(|p| domain::is_live(p) && Domain::contains(field.domain.normalize(p.pos)))
+        .collect::<Vec<_>>();
+    let expected = deposited
+        .iter()
+        .map(|p| (p.mass * scale).round_ties_even() as u64)
+        .fold(0, u64::wrapping_add);
+
+    println!(
+        "deposited {} of {} particles, {} x particle mass = {:.6e}",
+        deposited.len(),
+        snapshot.vertices.len(),
+        deposited.len(),
+        deposited.len() as f64 * physics.particle_mass as f64
+    );
+    println!(
+        "mass of the deposited particles {:.6e}, {} fixed point units",
+        expected as f64 * quantum as f64,
+        expected
+    );
+
+    let device = if gpu {
+        Some(deposit_on_device(&snapshot, &config)?)
+    } else {
+        None
+    };
+
+    let mut failures = 0;
+    for (i, level) in field.levels.iter().enumerate() {
+        let mut line = format!(
+            "level {} ({}x{}): cpu {}",
+            i, level.size, level.size, cpu[i]
+        );
+        failures += (cpu[i] != expected) as usize;
+
+        if let Some(device) = &device {
+            line += &format!(", gpu {}", device[i]);
+            failures += (device[i] != expected) as usize;
+        }
+
+        println!("{}", line);
+    }
+
+    if let Some((count, differing)) = patches {
+        println!(
+            "patches: cpu {} of them, {} with other mass than their tile",
+            count, differing
+        );
+        failures += differing;
+    }
+
+    if failures > 0 {
+        return Err(anyhow!(
+            "The deposited mass differs from the particles {} times",
+            failures
+        ));
+    }
+
+    println!("mass conserved on every level");
+    Ok(())
+}
+
+fn deposit_on_device(snapshot: &Snapshot, config: &RunConfig) -> Result<Vec<u64>> {
+    // The direct solver keeps the run from allocating a second mass field.
+    let run = RunConfig {
+        solver: SolverKind::Direct,
+        ..config.clone()
+    };
+
+    unsafe {
+        let mut app = HeadlessApp::create(snapshot.clone(), &run)?;
+        let totals = app.accumulated_mass(&snapshot.vertices, config);
+
+        globals::get_device().device_wait_idle()?;
+        app.destroy();
+
+        totals
+    }
+}
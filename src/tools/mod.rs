@@ -0,0 +1,91 @@
+use anyhow::Result;
+
+use crate::config::cli::Command;
+
+pub mod compare;
+pub mod deposit_timing;
+pub mod force_error;
+pub mod kepler;
+pub mod mass_check;
+
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Compare { a, b } => compare::compare_snapshots(&a, &b),
+        Command::ForceError {
+            snapshot,
+            solver,
+            theta,
+            grid_size,
+            domain,
+            multipoles,
+            mass_field,
+            sample,
+            seed,
+            gpu,
+        } => force_error::force_error(
+            &snapshot,
+            solver,
+            theta,
+            grid_size,
+            domain,
+            multipoles,
+            mass_field.config(None),
+            sample,
+            seed,
+            gpu,
+        ),
+        Command::MassCheck {
+            snapshot,
+            domain,
+            mass_field,
+            gpu,
+        } => mass_check::mass_check(&snapshot, domain, mass_field.config(None), gpu),
+        Command::DepositTiming {
+            snapshot,
+            domain,
+            multipoles,
+            mass_field,
+            repeat,
+            gpu,
+        } => deposit_timing::deposit_timing(
+            &snapshot,
+            domain,
+            multipoles,
+            mass_field.config(None),
+            repeat,
+            gpu,
+        ),
+        Command::Kepler {
+            integrator,
+            orbits,
+            eccentricity,
+            semi_major_axis,
+            dt,
+            adaptive,
+            eta,
+            gpu,
+        } => kepler::kepler(
+            integrator,
+            orbits,
+            eccentricity,
+            semi_major_axis,
+            dt,
+            adaptive,
+            eta,
+            gpu,
+        ),
+    }
+}
+
+/// Median, 90th, 99th percentile and maximum of `values`, which are sorted
+/// in place.
+pub fn percentiles(values: &mut [f32]) -> [f32; 4] {
+    if values.is_empty() {
+        return [0.0; 4];
+    }
+
+    values.sort_unstable_by(f32::total_cmp);
+    let at = |p: f32| values[((values.len() - 1) as f32 * p).round() as usize];
+
+    [at(0.5), at(0.9), at(0.99), at(1.0)]
+}
@@ -0,0 +1,58 @@
+use crate::config::MassFieldConfig;
+use crate::data::globals;
+
+/// Levels a field of `size` can have with every level covering exactly
+/// `downsampling` pixels of the one below it on each side.
+pub fn divisible_levels(size: u32, downsampling: u32) -> u32 {
+    let mut levels = 1;
+    let mut size = size;
+
+    while size.is_multiple_of(downsampling) {
+        size /= downsampling;
+        levels += 1;
+    }
+
+    levels
+}
+
+/// Side lengths of the mass field images, from the finest level down to the
+/// configured number of levels, or to the first one that fits into the
+/// pixels within reach of a particle.
+pub fn level_sizes(config: &MassFieldConfig) -> Vec<u32> {
+    let levels = config.levels.unwrap_or_else(|| {
+        let mut levels = 1;
+        let mut size = config.size;
+
+        while size > 2 * config.reach + 1 && size.is_multiple_of(config.downsampling) {
+            size /= config.downsampling;
+            levels += 1;
+        }
+
+        levels.max(2)
+    });
+
+    (0..levels)
+        .map(|level| config.size / config.downsampling.pow(level))
+        .collect()
+}
+
+/// Pixels of all levels together.
+pub fn cell_count(config: &MassFieldConfig) -> u32 {
+    level_sizes(config).iter().map(|size| size * size).sum()
+}
+
+/// Level whose pixels are the tiles of the refinement patches.
+pub fn tile_level(config: &MassFieldConfig) -> Option<usize> {
+    level_sizes(config)
+        .iter()
+        .position(|size| size * config.patch_size == config.size)
+        .filter(|&level| level > 0)
+}
+
+/// Fixed point mass above which a tile gets a refinement patch, the
+/// refinement density times the finest pixels of a tile in particles.
+pub fn refine_threshold(config: &MassFieldConfig) -> Option<u64> {
+    let density = config.refine_density? as f64;
+    let pixels = (config.patch_size * config.patch_size) as f64;
+    Some((density * pixels * globals::MASS_FIXED_POINT_SCALE as f64).round() as u64)
+}
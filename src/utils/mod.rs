@@ -1,3 +1,4 @@
+pub mod mass_field;
 pub mod queue_family_indices;
 pub mod resources;
 pub mod swapchain_support;
@@ -8,7 +8,7 @@ use crate::data::common_data::CommonData;
 #[derive(Copy, Clone, Debug)]
 pub struct QueueFamilyIndices {
     pub graphics_compute: u32,
-    pub present: u32,
+    pub present: Option<u32>,
 }
 
 impl QueueFamilyIndices {
@@ -19,33 +19,48 @@ impl QueueFamilyIndices {
     ) -> Result<Self> {
         let properties = instance.get_physical_device_queue_family_properties(physical_device);
 
+        // Headless runs only dispatch compute work, so any compute capable
+        // family will do, even on devices that have no graphics queue at all.
+        let required_flags = if common.headless {
+            vk::QueueFlags::COMPUTE
+        } else {
+            vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE
+        };
+
         let graphics: Option<u32> = properties
             .iter()
-            .position(|p| {
-                p.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                    || p.queue_flags.contains(vk::QueueFlags::COMPUTE)
-            })
+            .position(|p| p.queue_flags.contains(required_flags))
             .map(|i| i as u32);
 
         let mut present = None;
-        for (index, _properties) in properties.iter().enumerate() {
-            if instance.get_physical_device_surface_support_khr(
-                physical_device,
-                index as u32,
-                common.surface,
-            )? {
-                present = Some(index as u32);
-                break;
+        if !common.headless {
+            for (index, _properties) in properties.iter().enumerate() {
+                if instance.get_physical_device_surface_support_khr(
+                    physical_device,
+                    index as u32,
+                    common.surface,
+                )? {
+                    present = Some(index as u32);
+                    break;
+                }
             }
         }
 
-        if let (Some(graphics), Some(present)) = (graphics, present) {
-            Ok(Self {
+        match (graphics, present) {
+            (Some(graphics), None) if common.headless => Ok(Self {
                 graphics_compute: graphics,
-                present,
-            })
-        } else {
-            Err(anyhow!("Missing required queue families."))
+                present: None,
+            }),
+            (Some(graphics), Some(present)) => Ok(Self {
+                graphics_compute: graphics,
+                present: Some(present),
+            }),
+            _ => Err(anyhow!("Missing required queue families.")),
         }
     }
+
+    pub fn present(&self) -> Result<u32> {
+        self.present
+            .ok_or_else(|| anyhow!("Missing present queue family."))
+    }
 }
@@ -180,6 +180,7 @@ pub unsafe fn end_single_time_commands(
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub unsafe fn create_image(
     instance: &Instance,
     common: &CommonData,
@@ -252,14 +253,37 @@ pub unsafe fn create_image_view(
 }
 
 pub unsafe fn create_image_views(
-    images: &Vec<vk::Image>,
+    images: &[vk::Image],
     format: vk::Format,
     aspect: vk::ImageAspectFlags,
 ) -> Result<Vec<vk::ImageView>> {
-    Ok(images
+    images
         .iter()
         .map(|i| create_image_view(*i, format, aspect, 0, 1))
-        .collect::<Result<Vec<_>, _>>()?)
+        .collect::<Result<Vec<_>, _>>()
+}
+
+/// Records a global memory barrier between two stages.
+pub unsafe fn memory_barrier(
+    command_buffer: vk::CommandBuffer,
+    src_stage_mask: vk::PipelineStageFlags,
+    src_access_mask: vk::AccessFlags,
+    dst_stage_mask: vk::PipelineStageFlags,
+    dst_access_mask: vk::AccessFlags,
+) {
+    let barrier = vk::MemoryBarrier::builder()
+        .src_access_mask(src_access_mask)
+        .dst_access_mask(dst_access_mask);
+
+    globals::get_device().cmd_pipeline_barrier(
+        command_buffer,
+        src_stage_mask,
+        dst_stage_mask,
+        vk::DependencyFlags::empty(),
+        &[barrier],
+        &[] as &[vk::BufferMemoryBarrier],
+        &[] as &[vk::ImageMemoryBarrier],
+    );
 }
 
 //pub fn get_mip_levels(swapchain: &SwapchainData) -> u32 {